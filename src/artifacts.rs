@@ -0,0 +1,189 @@
+//! Naming of the files the crate creates next to a vault.
+//!
+//! Everything written besides the vault itself goes through here, so cleanup
+//! code (`destroy`, …) can find it again without guessing.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File name of `path` as a string, or an empty string for odd paths like `/`.
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Directory holding `path`, with `.` standing in for a bare file name.
+pub fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    }
+}
+
+/// Prefix of temp files used by `atomic_write`: `.<name>.`
+pub fn temp_prefix(path: &Path) -> String {
+    format!(".{}.", file_name(path))
+}
+
+/// Suffix of temp files used by `atomic_write`.
+pub const TEMP_SUFFIX: &str = ".tmp";
+
+/// Length of the random, alphanumeric part of temp file names, between
+/// [`temp_prefix`] and [`TEMP_SUFFIX`].
+pub const TEMP_RANDOM_LEN: usize = 6;
+
+/// Temp files left behind next to `path` by interrupted saves: exactly
+/// `.<name>.XXXXXX.tmp`, so those of a sibling vault such as `<name>.bak`
+/// (`.<name>.bak.XXXXXX.tmp`) don't match.
+pub fn temp_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = temp_prefix(path);
+    siblings(path, |name| {
+        name.strip_prefix(&prefix)
+            .and_then(|rest| rest.strip_suffix(TEMP_SUFFIX))
+            .is_some_and(|random| {
+                random.len() == TEMP_RANDOM_LEN && random.bytes().all(|b| b.is_ascii_alphanumeric())
+            })
+    })
+}
+
+/// Prefix of timestamped backups: `<name>.bak-`
+fn backup_prefix(path: &Path) -> String {
+    format!("{}.bak-", file_name(path))
+}
+
+/// Backup of `path` taken at `stamp`: `<name>.bak-<stamp>`
+pub fn backup_path(path: &Path, stamp: &str) -> PathBuf {
+    parent_dir(path).join(format!("{}{stamp}", backup_prefix(path)))
+}
+
+/// Timestamp part of a backup file name, if `backup` is a backup of `path`.
+pub fn backup_stamp<'a>(path: &Path, backup: &'a Path) -> Option<&'a str> {
+    backup
+        .file_name()?
+        .to_str()?
+        .strip_prefix(&backup_prefix(path))
+}
+
+/// Existing backups of `path`, oldest first.
+pub fn backups(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = backup_prefix(path);
+    siblings(path, |name| name.starts_with(&prefix))
+}
+
+/// Prefix of archived log segments: `<name>.seg-`
+fn segment_prefix(path: &Path) -> String {
+    format!("{}.seg-", file_name(path))
+}
+
+/// Log segment of `path` archived at `stamp`: `<name>.seg-<stamp>`
+pub fn segment_path(path: &Path, stamp: &str) -> PathBuf {
+    parent_dir(path).join(format!("{}{stamp}", segment_prefix(path)))
+}
+
+/// Archived segments of the log at `path`, oldest first.
+pub fn segments(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let prefix = segment_prefix(path);
+    siblings(path, |name| name.starts_with(&prefix))
+}
+
+/// Directory holding labelled snapshots: `.<name>.snapshots/`
+pub fn snapshot_dir(path: &Path) -> PathBuf {
+    parent_dir(path).join(format!(".{}.snapshots", file_name(path)))
+}
+
+/// Extension of snapshot files inside the snapshot directory.
+const SNAPSHOT_EXT: &str = "svlt";
+
+/// Snapshot of `path` saved under `label`. The label must already be validated.
+pub fn snapshot_path(path: &Path, label: &str) -> PathBuf {
+    snapshot_dir(path).join(format!("{label}.{SNAPSHOT_EXT}"))
+}
+
+/// Label of a snapshot file, if `file` is one.
+pub fn snapshot_label(file: &Path) -> Option<String> {
+    let name = file.file_name()?.to_str()?;
+    name.strip_suffix(&format!(".{SNAPSHOT_EXT}"))
+        .map(str::to_owned)
+}
+
+/// Existing snapshot files of `path`, sorted by label.
+pub fn snapshots(path: &Path) -> io::Result<Vec<PathBuf>> {
+    list_dir(&snapshot_dir(path), |name| {
+        name.ends_with(&format!(".{SNAPSHOT_EXT}"))
+    })
+}
+
+/// Sidecar holding archived revisions: `.<name>.history`
+pub fn history_path(path: &Path) -> PathBuf {
+    parent_dir(path).join(format!(".{}.history", file_name(path)))
+}
+
+/// Lock file used to serialize writers: `.<name>.lock`
+pub fn lock_path(path: &Path) -> PathBuf {
+    parent_dir(path).join(format!(".{}.lock", file_name(path)))
+}
+
+/// Every managed artifact of `path` that currently exists, excluding the vault itself.
+pub fn all(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut found = temp_files(path)?;
+    found.extend(backups(path)?);
+    found.extend(segments(path)?);
+    found.extend(list_dir(&snapshot_dir(path), |_| true)?);
+    for file in [history_path(path), lock_path(path)] {
+        if file.exists() {
+            found.push(file);
+        }
+    }
+    Ok(found)
+}
+
+/// Entries in the vault's directory whose file name satisfies `pred`.
+fn siblings(path: &Path, pred: impl Fn(&str) -> bool) -> io::Result<Vec<PathBuf>> {
+    list_dir(parent_dir(path), pred)
+}
+
+/// Entries of `dir` whose file name satisfies `pred`, sorted. A missing
+/// directory has no entries.
+fn list_dir(dir: &Path, pred: impl Fn(&str) -> bool) -> io::Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut found = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if pred(&entry.file_name().to_string_lossy()) {
+            found.push(entry.path());
+        }
+    }
+    found.sort();
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_temp_files_skip_sibling_vaults() {
+        let dir = tempdir().unwrap();
+        for name in [
+            ".vault.svlt.a1B2c3.tmp",
+            ".vault.svlt.x.a1B2c3.tmp",
+            ".vault.svlt.bak.a1B2c3.tmp",
+            ".vault.svlt.a1B2c3d.tmp",
+        ] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let found = temp_files(&dir.path().join("vault.svlt")).unwrap();
+        assert_eq!(found, [dir.path().join(".vault.svlt.a1B2c3.tmp")]);
+        let sibling = temp_files(&dir.path().join("vault.svlt.x")).unwrap();
+        assert_eq!(sibling, [dir.path().join(".vault.svlt.x.a1B2c3.tmp")]);
+    }
+}
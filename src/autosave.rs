@@ -0,0 +1,314 @@
+//! Continuously persisted values.
+
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::SerdeVaultError;
+use crate::vault::VaultFile;
+
+/// A value that writes itself back to its vault shortly after it changes.
+///
+/// Mutations go through [`lock`](Self::lock). Once no further change has been
+/// made for the debounce interval, a background thread saves the latest state,
+/// so a burst of edits (a slider being dragged, say) costs a single save.
+/// Pending changes are also saved by [`flush`](Self::flush) and on drop.
+///
+/// Background saves can't return errors; the most recent one is kept for
+/// [`take_error`](Self::take_error).
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use serdevault::{AutoSaved, VaultFile};
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Default)]
+/// struct Settings { volume: u8 }
+///
+/// let vault = VaultFile::open("~/.settings.vault", "my_password");
+/// let settings = AutoSaved::<Settings>::load_or_default(vault, Duration::from_millis(500))?;
+/// settings.lock().volume = 80; // saved about half a second later
+/// # Ok::<(), serdevault::SerdeVaultError>(())
+/// ```
+pub struct AutoSaved<T: Serialize + Send + 'static> {
+    shared: Arc<Shared<T>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+struct Shared<T> {
+    vault: VaultFile,
+    debounce: Duration,
+    state: Mutex<State<T>>,
+    changed: Condvar,
+    /// Held across snapshot and save, so an older snapshot never lands last.
+    saving: Mutex<()>,
+}
+
+struct State<T> {
+    data: T,
+    /// When the last unsaved change was made; `None` when clean.
+    last_change: Option<Instant>,
+    last_error: Option<SerdeVaultError>,
+    shutdown: bool,
+}
+
+impl<T: Serialize + Send + 'static> AutoSaved<T> {
+    /// Wrap `data`, which will be saved to `vault` after it is next changed.
+    pub fn new(vault: VaultFile, data: T, debounce: Duration) -> Self {
+        let shared = Arc::new(Shared {
+            vault,
+            debounce,
+            state: Mutex::new(State {
+                data,
+                last_change: None,
+                last_error: None,
+                shutdown: false,
+            }),
+            changed: Condvar::new(),
+            saving: Mutex::new(()),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || shared.run())
+        };
+        Self {
+            shared,
+            worker: Some(worker),
+        }
+    }
+
+    /// Load the vault's current contents, or start from `T::default()` if it
+    /// doesn't exist yet.
+    pub fn load_or_default(vault: VaultFile, debounce: Duration) -> Result<Self, SerdeVaultError>
+    where
+        T: DeserializeOwned + Default,
+    {
+        let data = vault.load_opt()?.unwrap_or_default();
+        Ok(Self::new(vault, data, debounce))
+    }
+
+    /// Access the value. Mutating it through the guard schedules a save.
+    pub fn lock(&self) -> AutoSavedGuard<'_, T> {
+        AutoSavedGuard {
+            state: self.shared.lock(),
+            changed: &self.shared.changed,
+        }
+    }
+
+    /// Save pending changes now, if there are any.
+    pub fn flush(&self) -> Result<(), SerdeVaultError> {
+        self.shared.save_pending()
+    }
+
+    /// Whether there are changes that haven't been saved yet.
+    pub fn is_dirty(&self) -> bool {
+        self.shared.lock().last_change.is_some()
+    }
+
+    /// The error from the most recent failed background save, if any.
+    pub fn take_error(&self) -> Option<SerdeVaultError> {
+        self.shared.lock().last_error.take()
+    }
+
+    /// The vault this value is saved to.
+    pub fn vault(&self) -> &VaultFile {
+        &self.shared.vault
+    }
+}
+
+impl<T: Serialize + Send + 'static> Drop for AutoSaved<T> {
+    fn drop(&mut self) {
+        self.shared.lock().shutdown = true;
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<T: Serialize + Send + 'static> fmt::Debug for AutoSaved<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AutoSaved")
+            .field("path", &self.shared.vault.path())
+            .field("debounce", &self.shared.debounce)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: Serialize> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // A panic while holding the guard leaves the data as the caller left it.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Background loop: save once changes have settled, and once more on shutdown.
+    /// After a failed save, the next attempt waits another debounce interval.
+    fn run(&self) {
+        let mut failed_at = None;
+        let mut state = self.lock();
+        loop {
+            if state.shutdown {
+                drop(state);
+                self.record(self.save_pending());
+                return;
+            }
+            state = match state.last_change {
+                None => self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Some(at) => {
+                    let since = failed_at.map_or(at, |failed: Instant| failed.max(at));
+                    let due = since + self.debounce;
+                    let now = Instant::now();
+                    if now < due {
+                        self.changed
+                            .wait_timeout(state, due - now)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0
+                    } else {
+                        drop(state);
+                        let result = self.save_pending();
+                        failed_at = result.is_err().then(Instant::now);
+                        self.record(result);
+                        self.lock()
+                    }
+                }
+            };
+        }
+    }
+
+    fn record(&self, result: Result<(), SerdeVaultError>) {
+        if let Err(e) = result {
+            self.lock().last_error = Some(e);
+        }
+    }
+
+    /// Snapshot the data under the lock, then save it without holding the lock,
+    /// so the key derivation doesn't block writers. The value stays dirty if the
+    /// save fails or it was changed again in the meantime.
+    fn save_pending(&self) -> Result<(), SerdeVaultError> {
+        let _saving = self.saving.lock().unwrap_or_else(|e| e.into_inner());
+        let state = self.lock();
+        let Some(changed_at) = state.last_change else {
+            return Ok(());
+        };
+        let snapshot = serde_json::to_value(&state.data)
+            .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?;
+        drop(state);
+        self.vault.save(&snapshot)?;
+        let mut state = self.lock();
+        if state.last_change == Some(changed_at) {
+            state.last_change = None;
+        }
+        Ok(())
+    }
+}
+
+/// Exclusive access to an [`AutoSaved`] value; see [`AutoSaved::lock`].
+pub struct AutoSavedGuard<'a, T> {
+    state: MutexGuard<'a, State<T>>,
+    changed: &'a Condvar,
+}
+
+impl<T> Deref for AutoSavedGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.state.data
+    }
+}
+
+impl<T> DerefMut for AutoSavedGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.state.last_change = Some(Instant::now());
+        &mut self.state.data
+    }
+}
+
+impl<T> Drop for AutoSavedGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.state.last_change.is_some() {
+            self.changed.notify_all();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn vault(dir: &tempfile::TempDir) -> VaultFile {
+        VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1)
+    }
+
+    #[test]
+    fn test_debounced_save() {
+        let dir = tempdir().unwrap();
+        let value =
+            AutoSaved::<Vec<u32>>::load_or_default(vault(&dir), Duration::from_millis(50)).unwrap();
+
+        for i in 0..10 {
+            value.lock().push(i);
+        }
+        assert!(value.is_dirty());
+
+        // The worker saves on its own; poll until the file shows up.
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let saved = loop {
+            match vault(&dir).load_opt::<Vec<u32>>().unwrap() {
+                Some(saved) => break saved,
+                None if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                None => panic!("no background save within 10s"),
+            }
+        };
+        assert_eq!(saved, (0..10).collect::<Vec<_>>());
+        assert!(!value.is_dirty());
+        assert!(value.take_error().is_none());
+    }
+
+    #[test]
+    fn test_reads_do_not_schedule_saves() {
+        let dir = tempdir().unwrap();
+        let value = AutoSaved::new(vault(&dir), 7u32, Duration::from_millis(1));
+        assert_eq!(*value.lock(), 7);
+        assert!(!value.is_dirty());
+        drop(value);
+        assert!(!vault(&dir).exists());
+    }
+
+    #[test]
+    fn test_flush_and_drop_save_pending_changes() {
+        let dir = tempdir().unwrap();
+        let value = AutoSaved::new(vault(&dir), 1u32, Duration::from_secs(3600));
+
+        *value.lock() = 2;
+        value.flush().unwrap();
+        assert_eq!(vault(&dir).load::<u32>().unwrap(), 2);
+
+        *value.lock() = 3;
+        drop(value);
+        assert_eq!(vault(&dir).load::<u32>().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_failed_save_keeps_changes_pending() {
+        let dir = tempdir().unwrap();
+        vault(&dir).save(&1u32).unwrap();
+        let read_only = VaultFile::open_read_only(vault(&dir).path(), "pwd");
+        let value = AutoSaved::new(read_only, 1u32, Duration::from_millis(10));
+
+        *value.lock() = 2;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while value.take_error().is_none() {
+            assert!(Instant::now() < deadline, "no background save within 10s");
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(value.is_dirty());
+        assert!(value.flush().is_err());
+        assert!(value.is_dirty());
+        assert_eq!(vault(&dir).load::<u32>().unwrap(), 1);
+    }
+}
@@ -0,0 +1,32 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::artifacts;
+use crate::time::utc_stamp;
+
+/// Preserve the current vault file as `<name>.bak-<timestamp>` and delete all
+/// but the `keep` newest backups. Does nothing if the vault doesn't exist yet.
+///
+/// Saves replace the vault by renaming a new file over it, so the old contents
+/// live on in the old inode; a hard link keeps it without copying. Filesystems
+/// without hard links get a plain copy, as does a vault path that is a symlink
+/// (linking the link would only preserve the pointer, not the contents).
+pub fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let backup = artifacts::backup_path(path, &utc_stamp(SystemTime::now()));
+    if path.is_symlink() || fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+
+    let backups = artifacts::backups(path)?;
+    let excess = backups.len().saturating_sub(keep);
+    for old in &backups[..excess] {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
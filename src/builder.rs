@@ -0,0 +1,443 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use argon2::Params;
+use zeroize::Zeroizing;
+
+use crate::bytes::BinaryBloat;
+use crate::crypto::kdf::{ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST, KEY_SIZE};
+use crate::error::SerdeVaultError;
+use crate::migration::Migrations;
+use crate::paths;
+use crate::serializer::Format;
+use crate::vault::{SymlinkPolicy, VaultCache, VaultFile};
+
+/// File name used by [`VaultFile::for_app`].
+const APP_VAULT_FILE: &str = "vault.svlt";
+
+/// Callback for [`VaultFileBuilder::binary_lint`].
+type BinaryLint = Box<dyn Fn(&BinaryBloat) + Send + Sync>;
+
+/// Authenticated cipher used for the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Cipher {
+    #[default]
+    Aes256Gcm,
+}
+
+/// Password-based key derivation function and its cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Kdf {
+    /// Argon2id. `m_cost` is in kibibytes.
+    Argon2id {
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    },
+}
+
+impl Default for Kdf {
+    /// OWASP 2023 parameters: 64 MB, 3 iterations, 1 lane.
+    fn default() -> Self {
+        Kdf::Argon2id {
+            m_cost: ARGON2_M_COST,
+            t_cost: ARGON2_T_COST,
+            p_cost: ARGON2_P_COST,
+        }
+    }
+}
+
+/// Step-by-step configuration of a [`VaultFile`].
+///
+/// Every option is checked in [`build`](Self::build), so a bad configuration
+/// fails at startup instead of on the first `save`.
+///
+/// # Example
+///
+/// ```no_run
+/// use serdevault::{Format, Kdf, VaultFile};
+///
+/// let vault = VaultFile::builder()
+///     .path("~/.my.vault")
+///     .password("my_password")
+///     .kdf(Kdf::Argon2id { m_cost: 19456, t_cost: 2, p_cost: 1 })
+///     .serializer(Format::Json)
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Default)]
+pub struct VaultFileBuilder {
+    path: Option<PathBuf>,
+    root: Option<PathBuf>,
+    password: Option<Zeroizing<String>>,
+    cipher: Cipher,
+    kdf: Kdf,
+    format: Format,
+    dir_sync: Option<bool>,
+    streaming: Option<bool>,
+    strict_permissions: Option<bool>,
+    backups: Option<usize>,
+    history: Option<usize>,
+    symlinks: Option<SymlinkPolicy>,
+    migrations: Option<Migrations>,
+    deterministic: Option<bool>,
+    schema_hash: Option<bool>,
+    lock_timeout: Option<Duration>,
+    locked_loads: Option<bool>,
+    cache: Option<VaultCache>,
+    binary_lint: Option<BinaryLint>,
+    read_only: bool,
+}
+
+impl VaultFileBuilder {
+    /// Location of the vault file. `~/` and `${VAR}` are expanded, as in
+    /// [`VaultFile::open`]. Required.
+    pub fn path(mut self, path: impl AsRef<Path>) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Resolve a relative or `~/` [`path`](Self::path) inside `dir`, taking
+    /// precedence over the `SERDEVAULT_DIR` environment variable.
+    pub fn root(mut self, dir: impl AsRef<Path>) -> Self {
+        self.root = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Master password. Required.
+    pub fn password(mut self, password: &str) -> Self {
+        self.password = Some(Zeroizing::new(password.to_owned()));
+        self
+    }
+
+    /// Payload cipher. Defaults to AES-256-GCM.
+    pub fn cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Key derivation function used when saving. Defaults to [`Kdf::default`].
+    pub fn kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Plaintext encoding. Defaults to [`Format::Json`].
+    pub fn serializer(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// See [`VaultFile::with_dir_sync`].
+    pub fn dir_sync(mut self, enabled: bool) -> Self {
+        self.dir_sync = Some(enabled);
+        self
+    }
+
+    /// See [`VaultFile::with_streaming`].
+    pub fn streaming(mut self, enabled: bool) -> Self {
+        self.streaming = Some(enabled);
+        self
+    }
+
+    /// See [`VaultFile::with_strict_permissions`].
+    pub fn strict_permissions(mut self, strict: bool) -> Self {
+        self.strict_permissions = Some(strict);
+        self
+    }
+
+    /// See [`VaultFile::with_backups`].
+    pub fn backups(mut self, keep: usize) -> Self {
+        self.backups = Some(keep);
+        self
+    }
+
+    /// See [`VaultFile::with_history`].
+    pub fn history(mut self, keep: usize) -> Self {
+        self.history = Some(keep);
+        self
+    }
+
+    /// See [`VaultFile::with_symlink_policy`].
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = Some(policy);
+        self
+    }
+
+    /// See [`VaultFile::with_migrations`].
+    pub fn migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = Some(migrations);
+        self
+    }
+
+    /// See [`VaultFile::with_deterministic_encryption`]. Can't be combined with
+    /// [`streaming`](Self::streaming).
+    pub fn deterministic_encryption(mut self, enabled: bool) -> Self {
+        self.deterministic = Some(enabled);
+        self
+    }
+
+    /// See [`VaultFile::with_schema_hash`].
+    pub fn schema_hash(mut self, enabled: bool) -> Self {
+        self.schema_hash = Some(enabled);
+        self
+    }
+
+    /// See [`VaultFile::with_lock_timeout`].
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// See [`VaultFile::with_locked_loads`].
+    pub fn locked_loads(mut self, enabled: bool) -> Self {
+        self.locked_loads = Some(enabled);
+        self
+    }
+
+    /// See [`VaultFile::with_cache`].
+    pub fn cache(mut self, cache: VaultCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// See [`VaultFile::with_binary_lint`].
+    pub fn binary_lint(mut self, warn: impl Fn(&BinaryBloat) + Send + Sync + 'static) -> Self {
+        self.binary_lint = Some(Box::new(warn));
+        self
+    }
+
+    /// Build a read-only handle; see [`VaultFile::open_read_only`].
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Validate the configuration and create the handle. No I/O is performed.
+    pub fn build(self) -> Result<VaultFile, SerdeVaultError> {
+        let path = self
+            .path
+            .ok_or_else(|| SerdeVaultError::InvalidConfig("no vault path given".to_string()))?;
+        if path.as_os_str().is_empty() {
+            return Err(SerdeVaultError::InvalidConfig(
+                "vault path is empty".to_string(),
+            ));
+        }
+        let path = match &self.root {
+            Some(root) => paths::expand_in(&path, Some(&paths::expand(root))),
+            None => path,
+        };
+        let password = self
+            .password
+            .ok_or_else(|| SerdeVaultError::InvalidConfig("no password given".to_string()))?;
+
+        // Exhaustive on purpose: a new cipher must decide its own constraints here.
+        let Cipher::Aes256Gcm = self.cipher;
+
+        let Kdf::Argon2id {
+            m_cost,
+            t_cost,
+            p_cost,
+        } = self.kdf;
+        Params::new(m_cost, t_cost, p_cost, Some(KEY_SIZE))
+            .map_err(|e| SerdeVaultError::KdfError(e.to_string()))?;
+
+        if self.streaming == Some(true) && self.deterministic == Some(true) {
+            return Err(SerdeVaultError::InvalidConfig(
+                "streaming can't be combined with deterministic encryption, whose nonce \
+                 needs the whole plaintext"
+                    .to_string(),
+            ));
+        }
+
+        let base = if self.read_only {
+            VaultFile::open_read_only(path, &password)
+        } else {
+            VaultFile::open(path, &password)
+        };
+        let mut vault = base
+            .with_params(m_cost, t_cost, p_cost)
+            .with_format(self.format);
+        if let Some(enabled) = self.dir_sync {
+            vault = vault.with_dir_sync(enabled);
+        }
+        if let Some(enabled) = self.streaming {
+            vault = vault.with_streaming(enabled);
+        }
+        if let Some(strict) = self.strict_permissions {
+            vault = vault.with_strict_permissions(strict);
+        }
+        if let Some(keep) = self.backups {
+            vault = vault.with_backups(keep);
+        }
+        if let Some(keep) = self.history {
+            vault = vault.with_history(keep);
+        }
+        if let Some(policy) = self.symlinks {
+            vault = vault.with_symlink_policy(policy);
+        }
+        if let Some(migrations) = self.migrations {
+            vault = vault.with_migrations(migrations);
+        }
+        if let Some(enabled) = self.deterministic {
+            vault = vault.with_deterministic_encryption(enabled);
+        }
+        if let Some(enabled) = self.schema_hash {
+            vault = vault.with_schema_hash(enabled);
+        }
+        if let Some(timeout) = self.lock_timeout {
+            vault = vault.with_lock_timeout(timeout);
+        }
+        if let Some(enabled) = self.locked_loads {
+            vault = vault.with_locked_loads(enabled);
+        }
+        if let Some(cache) = self.cache {
+            vault = vault.with_cache(cache);
+        }
+        if let Some(warn) = self.binary_lint {
+            vault = vault.with_binary_lint(warn);
+        }
+        Ok(vault)
+    }
+}
+
+impl VaultFile {
+    /// Start configuring a vault handle. See [`VaultFileBuilder`].
+    pub fn builder() -> VaultFileBuilder {
+        VaultFileBuilder::default()
+    }
+
+    /// Start configuring a vault stored as `vault.svlt` in the platform's
+    /// per-user config directory for this application:
+    ///
+    /// | Platform | Directory |
+    /// |----------|-----------|
+    /// | Linux    | `$XDG_CONFIG_HOME/myapp` or `~/.config/myapp` |
+    /// | macOS    | `~/Library/Application Support/com.acme.myapp` |
+    /// | Windows  | `%APPDATA%\acme\myapp\config` |
+    ///
+    /// The directory is created on the first `save`. Fails with `InvalidConfig`
+    /// if the home directory can't be determined.
+    ///
+    /// ```no_run
+    /// use serdevault::VaultFile;
+    ///
+    /// let vault = VaultFile::for_app("com", "acme", "myapp")?
+    ///     .password("my_password")
+    ///     .build()?;
+    /// # Ok::<(), serdevault::SerdeVaultError>(())
+    /// ```
+    pub fn for_app(
+        qualifier: &str,
+        organization: &str,
+        application: &str,
+    ) -> Result<VaultFileBuilder, SerdeVaultError> {
+        let dir = paths::app_config_dir(qualifier, organization, application).ok_or_else(|| {
+            SerdeVaultError::InvalidConfig(
+                "cannot determine the user's config directory".to_string(),
+            )
+        })?;
+        Ok(Self::builder().path(dir.join(APP_VAULT_FILE)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn cheap_kdf() -> Kdf {
+        Kdf::Argon2id {
+            m_cost: 8,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    #[test]
+    fn test_builder_roundtrip() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::builder()
+            .path(dir.path().join("vault.svlt"))
+            .password("pwd")
+            .cipher(Cipher::Aes256Gcm)
+            .kdf(cheap_kdf())
+            .serializer(Format::Json)
+            .dir_sync(false)
+            .build()
+            .unwrap();
+
+        vault.save(&vec![1u32, 2, 3]).unwrap();
+        assert_eq!(vault.load::<Vec<u32>>().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_builder_root() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::builder()
+            .root(dir.path())
+            .path("~/.app.vault")
+            .password("pwd")
+            .kdf(cheap_kdf())
+            .build()
+            .unwrap();
+        assert_eq!(vault.path(), dir.path().join(".app.vault"));
+
+        vault.save(&1u32).unwrap();
+        assert!(dir.path().join(".app.vault").exists());
+    }
+
+    #[test]
+    fn test_builder_requires_path_and_password() {
+        let err = VaultFile::builder().password("pwd").build().err().unwrap();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+
+        let err = VaultFile::builder()
+            .path("vault.svlt")
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_kdf_params() {
+        // Argon2 needs at least 8 KiB of memory per lane.
+        let err = VaultFile::builder()
+            .path("vault.svlt")
+            .password("pwd")
+            .kdf(Kdf::Argon2id {
+                m_cost: 8,
+                t_cost: 1,
+                p_cost: 4,
+            })
+            .build()
+            .err()
+            .unwrap();
+        assert!(matches!(err, SerdeVaultError::KdfError(_)));
+    }
+
+    #[test]
+    fn test_builder_rejects_streaming_deterministic() {
+        let builder = || {
+            VaultFile::builder()
+                .path("vault.svlt")
+                .password("pwd")
+                .kdf(cheap_kdf())
+                .deterministic_encryption(true)
+        };
+        let err = builder().streaming(true).build().err().unwrap();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+
+        let vault = builder()
+            .schema_hash(true)
+            .lock_timeout(Duration::from_secs(1))
+            .locked_loads(true)
+            .cache(VaultCache::new(1))
+            .binary_lint(|_| ())
+            .build();
+        assert!(vault.is_ok());
+    }
+}
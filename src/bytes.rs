@@ -0,0 +1,187 @@
+//! Byte buffers as bytes rather than sequences of integers.
+//!
+//! Serde serializes `Vec<u8>` and `[u8; N]` as sequences, which JSON writes as
+//! `[104,101,108,108,111]`: three to four bytes per byte. Annotate such fields
+//! with `#[serde(with = "serdevault::bytes")]` so that serializers with a native
+//! byte type (most binary [`Codec`](crate::Codec)s) store them raw:
+//!
+//! ```
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Identity {
+//!     name: String,
+//!     #[serde(with = "serdevault::bytes")]
+//!     private_key: Vec<u8>,
+//! }
+//! ```
+//!
+//! JSON has no byte type, so there the field is still an integer array (and
+//! reads back either way). [`bloated_fields`] finds such arrays in a value, and
+//! [`VaultFile::with_binary_lint`](crate::VaultFile::with_binary_lint) reports
+//! them on every save of a JSON vault.
+
+use std::fmt;
+
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+use crate::error::SerdeVaultError;
+
+/// Shortest integer array [`bloated_fields`] reports.
+pub const MIN_LINT_LEN: usize = 32;
+
+/// Serialize a byte buffer with [`Serializer::serialize_bytes`].
+pub fn serialize<T, S>(bytes: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]> + ?Sized,
+    S: Serializer,
+{
+    serializer.serialize_bytes(bytes.as_ref())
+}
+
+/// Deserialize a byte buffer from bytes or from a sequence of integers.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: TryFrom<Vec<u8>>,
+    D: Deserializer<'de>,
+{
+    let bytes = deserializer.deserialize_byte_buf(BytesVisitor)?;
+    let len = bytes.len();
+    T::try_from(bytes).map_err(|_| de::Error::invalid_length(len, &"a buffer of matching length"))
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a byte buffer")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(bytes)
+    }
+}
+
+/// A byte buffer that JSON stores as an integer array, found by [`bloated_fields`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryBloat {
+    /// Where the array is, in `a.b[2].c` notation (empty for the root).
+    pub path: String,
+    /// Number of bytes in the buffer.
+    pub len: usize,
+    /// Size of the array as compact JSON.
+    pub json_len: usize,
+}
+
+/// Every array of at least [`MIN_LINT_LEN`] integers in `0..=255` in `data`'s
+/// JSON form: most likely binary data that a binary format would store in a
+/// quarter of the space.
+pub fn bloated_fields<T: Serialize + ?Sized>(
+    data: &T,
+) -> Result<Vec<BinaryBloat>, SerdeVaultError> {
+    let value = serde_json::to_value(data)
+        .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?;
+    let mut found = Vec::new();
+    collect_bloat(&value, &mut String::new(), &mut found);
+    Ok(found)
+}
+
+fn collect_bloat(value: &Value, path: &mut String, found: &mut Vec<BinaryBloat>) {
+    let start = path.len();
+    match value {
+        Value::Array(items) if items.len() >= MIN_LINT_LEN && items.iter().all(is_byte) => {
+            // Digits, plus a comma between items and the brackets.
+            let digits: usize = items.iter().map(|v| v.to_string().len()).sum();
+            found.push(BinaryBloat {
+                path: path.clone(),
+                len: items.len(),
+                json_len: digits + items.len() + 1,
+            });
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push_str(&format!("[{i}]"));
+                collect_bloat(item, path, found);
+                path.truncate(start);
+            }
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+                collect_bloat(item, path, found);
+                path.truncate(start);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_byte(value: &Value) -> bool {
+    value.as_u64().is_some_and(|n| n <= u64::from(u8::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Identity {
+        name: String,
+        #[serde(with = "crate::bytes")]
+        key: Vec<u8>,
+        #[serde(with = "crate::bytes")]
+        tag: [u8; 4],
+        history: Vec<Vec<u8>>,
+    }
+
+    fn identity() -> Identity {
+        Identity {
+            name: "a".into(),
+            key: (0..=255).collect(),
+            tag: [1, 2, 3, 4],
+            history: vec![vec![7; 8], vec![9; 40]],
+        }
+    }
+
+    #[test]
+    fn test_bytes_roundtrip_through_json() {
+        let json = serde_json::to_vec(&identity()).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<Identity>(&json).unwrap(),
+            identity()
+        );
+
+        let short = br#"{"name":"a","key":[],"tag":[1,2,3],"history":[]}"#;
+        assert!(serde_json::from_slice::<Identity>(short).is_err());
+    }
+
+    #[test]
+    fn test_bloated_fields() {
+        let found = bloated_fields(&identity()).unwrap();
+        let paths: Vec<_> = found.iter().map(|b| b.path.as_str()).collect();
+        assert_eq!(paths, ["history[1]", "key"]);
+        assert_eq!(found[1].len, 256);
+        let key = serde_json::to_string(&identity().key).unwrap();
+        assert_eq!(found[1].json_len, key.len());
+    }
+}
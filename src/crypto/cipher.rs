@@ -1,5 +1,5 @@
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm, Key, Nonce,
 };
 use rand::{rngs::OsRng, RngCore};
@@ -11,23 +11,36 @@ use crate::error::SerdeVaultError;
 /// Nonce size in bytes. 12 bytes is the standard for AES-GCM (96-bit nonce).
 pub const NONCE_SIZE: usize = 12;
 
-/// Encrypt `plaintext` with AES-256-GCM using the provided key.
+/// Size of the authentication tag AES-GCM appends to each ciphertext.
+pub const TAG_SIZE: usize = 16;
+
+/// A fresh random nonce.
+pub fn random_nonce() -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut nonce);
+    nonce
+}
+
+/// Encrypt `plaintext` with AES-256-GCM, authenticating `aad` along with it.
 pub fn encrypt(
     plaintext: &[u8],
     key: &Zeroizing<[u8; KEY_SIZE]>,
-) -> Result<(Vec<u8>, [u8; NONCE_SIZE]), SerdeVaultError> {
-    let mut nonce_bytes = [0u8; NONCE_SIZE];
-    OsRng.fill_bytes(&mut nonce_bytes);
-
+    nonce_bytes: &[u8; NONCE_SIZE],
+    aad: &[u8],
+) -> Result<Vec<u8>, SerdeVaultError> {
     let cipher_key = Key::<Aes256Gcm>::from_slice(key.as_ref());
     let cipher = Aes256Gcm::new(cipher_key);
-    let nonce = Nonce::from_slice(&nonce_bytes);
-
-    let ciphertext = cipher
-        .encrypt(nonce, plaintext)
-        .map_err(|e| SerdeVaultError::EncryptionError(e.to_string()))?;
+    let nonce = Nonce::from_slice(nonce_bytes);
 
-    Ok((ciphertext, nonce_bytes))
+    cipher
+        .encrypt(
+            nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|e| SerdeVaultError::EncryptionError(e.to_string()))
 }
 
 /// Decrypt `ciphertext` with AES-256-GCM.
@@ -35,14 +48,47 @@ pub fn decrypt(
     ciphertext: &[u8],
     key: &Zeroizing<[u8; KEY_SIZE]>,
     nonce_bytes: &[u8; NONCE_SIZE],
+    aad: &[u8],
 ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
     let cipher_key = Key::<Aes256Gcm>::from_slice(key.as_ref());
     let cipher = Aes256Gcm::new(cipher_key);
     let nonce = Nonce::from_slice(nonce_bytes);
 
     let plaintext = cipher
-        .decrypt(nonce, ciphertext)
+        .decrypt(
+            nonce,
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
         .map_err(|_| SerdeVaultError::DecryptionFailed)?;
 
     Ok(Zeroizing::new(plaintext))
 }
+
+/// Encrypt `plaintext` under a fresh random nonce and return `nonce || ciphertext`.
+pub fn seal(
+    plaintext: &[u8],
+    key: &Zeroizing<[u8; KEY_SIZE]>,
+    aad: &[u8],
+) -> Result<Vec<u8>, SerdeVaultError> {
+    let nonce = random_nonce();
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&encrypt(plaintext, key, &nonce, aad)?);
+    Ok(sealed)
+}
+
+/// Inverse of [`seal`].
+pub fn open(
+    sealed: &[u8],
+    key: &Zeroizing<[u8; KEY_SIZE]>,
+    aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+    if sealed.len() < NONCE_SIZE + TAG_SIZE {
+        return Err(SerdeVaultError::DecryptionFailed);
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+    let nonce: &[u8; NONCE_SIZE] = nonce.try_into().expect("split at NONCE_SIZE");
+    decrypt(ciphertext, key, nonce, aad)
+}
@@ -1,2 +1,3 @@
 pub mod cipher;
 pub mod kdf;
+pub mod stream;
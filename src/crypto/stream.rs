@@ -0,0 +1,207 @@
+//! Chunked AES-256-GCM in the STREAM construction (Hoang et al., 2015).
+//!
+//! The plaintext is split into `CHUNK_SIZE` chunks, each sealed separately
+//! under the nonce `prefix[7] || counter (u32 BE) || last_flag`. The counter
+//! stops chunks from being reordered, and the flag on the final chunk makes
+//! truncation at a chunk boundary fail authentication. Every chunk also
+//! authenticates the same associated data (the vault header).
+
+use std::io::{self, Write};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::cipher::{NONCE_SIZE, TAG_SIZE};
+use crate::crypto::kdf::KEY_SIZE;
+use crate::error::SerdeVaultError;
+
+/// Plaintext bytes per chunk.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Bytes of the header nonce used as the per-chunk nonce prefix.
+const PREFIX_SIZE: usize = NONCE_SIZE - 5;
+
+/// A writer that encrypts everything written to it chunk by chunk into `out`.
+///
+/// At most one chunk of plaintext is buffered, and that buffer is wiped after
+/// every chunk. Call [`finish`](Self::finish) to seal the final chunk; dropping
+/// the encryptor without it leaves a stream that fails to decrypt.
+pub struct ChunkedEncryptor<W: Write> {
+    out: W,
+    cipher: Aes256Gcm,
+    prefix: [u8; PREFIX_SIZE],
+    aad: Vec<u8>,
+    counter: u32,
+    buf: Zeroizing<Vec<u8>>,
+}
+
+impl<W: Write> ChunkedEncryptor<W> {
+    /// Encrypt into `out` under `key`, deriving chunk nonces from `nonce` and
+    /// authenticating `aad` with every chunk.
+    pub fn new(
+        out: W,
+        key: &Zeroizing<[u8; KEY_SIZE]>,
+        nonce: &[u8; NONCE_SIZE],
+        aad: Vec<u8>,
+    ) -> Self {
+        let mut prefix = [0u8; PREFIX_SIZE];
+        prefix.copy_from_slice(&nonce[..PREFIX_SIZE]);
+        Self {
+            out,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref())),
+            prefix,
+            aad,
+            counter: 0,
+            buf: Zeroizing::new(Vec::with_capacity(CHUNK_SIZE)),
+        }
+    }
+
+    /// Seal the buffered plaintext as the last chunk and return the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.seal_chunk(true)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn seal_chunk(&mut self, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(&self.prefix, self.counter, last);
+        let payload = Payload {
+            msg: self.buf.as_slice(),
+            aad: &self.aad,
+        };
+        let sealed = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        self.buf.zeroize();
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| io::Error::other("too many chunks for one vault"))?;
+        self.out.write_all(&sealed)
+    }
+}
+
+impl<W: Write> Write for ChunkedEncryptor<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&data[..n]);
+        if self.buf.len() == CHUNK_SIZE {
+            self.seal_chunk(false)?;
+        }
+        Ok(n)
+    }
+
+    /// Flushes `out` only: a partial chunk can't be sealed before `finish`.
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Size of the stream [`ChunkedEncryptor`] produces for `len` bytes of plaintext.
+pub fn sealed_len(len: u64) -> u64 {
+    len + (len / CHUNK_SIZE as u64 + 1) * TAG_SIZE as u64
+}
+
+/// Decrypt a complete stream written by [`ChunkedEncryptor`].
+pub fn decrypt_chunked(
+    ciphertext: &[u8],
+    key: &Zeroizing<[u8; KEY_SIZE]>,
+    nonce: &[u8; NONCE_SIZE],
+    aad: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key.as_ref()));
+    let mut prefix = [0u8; PREFIX_SIZE];
+    prefix.copy_from_slice(&nonce[..PREFIX_SIZE]);
+
+    let mut plaintext = Zeroizing::new(Vec::with_capacity(ciphertext.len()));
+    let mut rest = ciphertext;
+    let mut counter = 0u32;
+    loop {
+        // Full chunks are always followed by at least the (possibly empty) last one.
+        let last = rest.len() <= CHUNK_SIZE + TAG_SIZE;
+        let (chunk, tail) = rest.split_at(if last {
+            rest.len()
+        } else {
+            CHUNK_SIZE + TAG_SIZE
+        });
+        let nonce = chunk_nonce(&prefix, counter, last);
+        let opened = Zeroizing::new(
+            cipher
+                .decrypt(Nonce::from_slice(&nonce), Payload { msg: chunk, aad })
+                .map_err(|_| SerdeVaultError::DecryptionFailed)?,
+        );
+        plaintext.extend_from_slice(&opened);
+        if last {
+            return Ok(plaintext);
+        }
+        rest = tail;
+        counter = counter
+            .checked_add(1)
+            .ok_or(SerdeVaultError::DecryptionFailed)?;
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; PREFIX_SIZE], counter: u32, last: bool) -> [u8; NONCE_SIZE] {
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce[..PREFIX_SIZE].copy_from_slice(prefix);
+    nonce[PREFIX_SIZE..NONCE_SIZE - 1].copy_from_slice(&counter.to_be_bytes());
+    nonce[NONCE_SIZE - 1] = u8::from(last);
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(len: usize) {
+        let key = Zeroizing::new([7u8; KEY_SIZE]);
+        let nonce = [3u8; NONCE_SIZE];
+        let data: Vec<u8> = (0..len).map(|i| i as u8).collect();
+
+        let mut enc = ChunkedEncryptor::new(Vec::new(), &key, &nonce, b"aad".to_vec());
+        // Odd-sized writes straddle chunk boundaries.
+        for piece in data.chunks(1000) {
+            enc.write_all(piece).unwrap();
+        }
+        let sealed = enc.finish().unwrap();
+        assert_eq!(sealed.len() as u64, sealed_len(len as u64));
+        assert_eq!(
+            *decrypt_chunked(&sealed, &key, &nonce, b"aad").unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_at_chunk_boundaries() {
+        for len in [
+            0,
+            1,
+            CHUNK_SIZE - 1,
+            CHUNK_SIZE,
+            CHUNK_SIZE + 1,
+            3 * CHUNK_SIZE,
+        ] {
+            roundtrip(len);
+        }
+    }
+
+    #[test]
+    fn test_truncation_is_detected() {
+        let key = Zeroizing::new([7u8; KEY_SIZE]);
+        let nonce = [3u8; NONCE_SIZE];
+        let mut enc = ChunkedEncryptor::new(Vec::new(), &key, &nonce, b"aad".to_vec());
+        enc.write_all(&vec![1u8; 2 * CHUNK_SIZE + 10]).unwrap();
+        let sealed = enc.finish().unwrap();
+
+        // Drop the final chunk: what remains ends on a non-final chunk.
+        let truncated = &sealed[..2 * (CHUNK_SIZE + TAG_SIZE)];
+        assert!(matches!(
+            decrypt_chunked(truncated, &key, &nonce, b"aad"),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+    }
+}
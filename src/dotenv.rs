@@ -0,0 +1,334 @@
+//! Encrypted `.env` files.
+//!
+//! A [`DotEnvVault`] keeps the variables of a `.env` file in a vault, so the
+//! file itself no longer needs to be committed in plaintext. Variables can be
+//! read and changed one at a time, and a plaintext `.env` can be compared with
+//! the vault and merged into it:
+//!
+//! ```no_run
+//! use serdevault::DotEnvVault;
+//!
+//! let env = DotEnvVault::open(".env.vault", "team_password");
+//! env.import(&std::fs::read_to_string(".env")?)?;
+//! env.set("API_TOKEN", "s3cr3t")?;
+//! for change in env.diff(&std::fs::read_to_string(".env.local")?)? {
+//!     println!("{change}");
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::error::SerdeVaultError;
+use crate::vault::VaultFile;
+
+/// Variables by name, as kept in the vault.
+pub type EnvVars = BTreeMap<String, String>;
+
+/// A vault holding the variables of a `.env` file.
+///
+/// Every call reads the file; changes run under the vault's update lock. All
+/// settings come from the wrapped [`VaultFile`].
+#[derive(Clone)]
+pub struct DotEnvVault {
+    vault: VaultFile,
+}
+
+/// One difference between two sets of variables, from [`DotEnvVault::diff`].
+///
+/// Displays as `+ KEY`, `~ KEY` or `- KEY`, without the value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvChange {
+    Added { key: String, value: String },
+    Changed { key: String, value: String },
+    Removed { key: String },
+}
+
+impl EnvChange {
+    pub fn key(&self) -> &str {
+        match self {
+            EnvChange::Added { key, .. }
+            | EnvChange::Changed { key, .. }
+            | EnvChange::Removed { key } => key,
+        }
+    }
+}
+
+impl fmt::Display for EnvChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = match self {
+            EnvChange::Added { .. } => '+',
+            EnvChange::Changed { .. } => '~',
+            EnvChange::Removed { .. } => '-',
+        };
+        write!(f, "{sign} {}", self.key())
+    }
+}
+
+impl DotEnvVault {
+    /// A `.env` vault at `path` with default vault settings. No I/O is
+    /// performed.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Self {
+        VaultFile::open(path, password).into()
+    }
+
+    /// The vault the variables are kept in.
+    pub fn vault(&self) -> &VaultFile {
+        &self.vault
+    }
+
+    /// All variables, sorted by name. Empty if the vault doesn't exist.
+    pub fn vars(&self) -> Result<EnvVars, SerdeVaultError> {
+        Ok(self.vault.load_opt()?.unwrap_or_default())
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, SerdeVaultError> {
+        Ok(self.vars()?.remove(key))
+    }
+
+    /// Set the variable `key`, creating the vault if needed.
+    pub fn set(&self, key: &str, value: &str) -> Result<(), SerdeVaultError> {
+        check_key(key)?;
+        self.modify(|vars| {
+            vars.insert(key.to_string(), value.to_string());
+        })
+    }
+
+    /// Remove the variable `key`. Returns whether there was one.
+    pub fn unset(&self, key: &str) -> Result<bool, SerdeVaultError> {
+        self.modify(|vars| vars.remove(key).is_some())
+    }
+
+    /// Replace every variable with those of the `.env` text `dotenv`.
+    pub fn import(&self, dotenv: &str) -> Result<(), SerdeVaultError> {
+        let parsed = parse(dotenv)?;
+        self.modify(|vars| *vars = parsed)
+    }
+
+    /// The changes that would turn the vault's variables into those of the
+    /// `.env` text `dotenv`, by variable name.
+    pub fn diff(&self, dotenv: &str) -> Result<Vec<EnvChange>, SerdeVaultError> {
+        Ok(diff(&self.vars()?, &parse(dotenv)?))
+    }
+
+    /// Apply `changes`, e.g. the part of a [`diff`](Self::diff) to keep, in one
+    /// write.
+    pub fn patch(&self, changes: &[EnvChange]) -> Result<(), SerdeVaultError> {
+        for change in changes {
+            check_key(change.key())?;
+        }
+        self.modify(|vars| {
+            for change in changes {
+                match change {
+                    EnvChange::Added { key, value } | EnvChange::Changed { key, value } => {
+                        vars.insert(key.clone(), value.clone());
+                    }
+                    EnvChange::Removed { key } => {
+                        vars.remove(key);
+                    }
+                }
+            }
+        })
+    }
+
+    /// The variables as `.env` text, quoting values where needed.
+    pub fn to_dotenv(&self) -> Result<String, SerdeVaultError> {
+        Ok(render(&self.vars()?))
+    }
+
+    /// Change the stored variables under the update lock and save them.
+    fn modify<R>(&self, f: impl FnOnce(&mut EnvVars) -> R) -> Result<R, SerdeVaultError> {
+        let _lock = self.vault.lock_for_update()?;
+        let mut vars = self.vars()?;
+        let result = f(&mut vars);
+        self.vault.save(&vars)?;
+        Ok(result)
+    }
+}
+
+impl From<VaultFile> for DotEnvVault {
+    fn from(vault: VaultFile) -> Self {
+        Self { vault }
+    }
+}
+
+impl fmt::Debug for DotEnvVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DotEnvVault")
+            .field("path", &self.vault.path())
+            .finish()
+    }
+}
+
+/// Parse `.env` text: `KEY=VALUE` lines, optionally prefixed with `export`,
+/// with `#` comments and blank lines ignored.
+///
+/// Values may be single-quoted (taken literally) or double-quoted (with `\n`,
+/// `\t`, `\"` and `\\` escapes). Unquoted values end at a ` #` comment and are
+/// trimmed. A later line for the same key wins.
+pub fn parse(dotenv: &str) -> Result<EnvVars, SerdeVaultError> {
+    let mut vars = EnvVars::new();
+    for (number, line) in dotenv.lines().enumerate() {
+        let error = |message: &str| {
+            SerdeVaultError::InvalidFormat(format!(".env line {}: {message}", number + 1))
+        };
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected KEY=VALUE"))?;
+        let key = key.trim();
+        check_key(key).map_err(|_| error(&format!("invalid variable name {key:?}")))?;
+        let value = parse_value(value.trim_start()).map_err(error)?;
+        vars.insert(key.to_string(), value);
+    }
+    Ok(vars)
+}
+
+fn parse_value(value: &str) -> Result<String, &'static str> {
+    let (parsed, rest) = match value.chars().next() {
+        Some('\'') => {
+            let end = value[1..].find('\'').ok_or("unterminated single quote")?;
+            (value[1..=end].to_string(), &value[end + 2..])
+        }
+        Some('"') => {
+            let mut parsed = String::new();
+            let mut chars = value[1..].char_indices();
+            let end = loop {
+                match chars.next().ok_or("unterminated double quote")? {
+                    (i, '"') => break i + 2,
+                    (_, '\\') => match chars.next().ok_or("unterminated double quote")?.1 {
+                        'n' => parsed.push('\n'),
+                        'r' => parsed.push('\r'),
+                        't' => parsed.push('\t'),
+                        c => parsed.push(c),
+                    },
+                    (_, c) => parsed.push(c),
+                }
+            };
+            (parsed, &value[end..])
+        }
+        _ => {
+            let end = value.find(" #").unwrap_or(value.len());
+            return Ok(value[..end].trim_end().to_string());
+        }
+    };
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(parsed)
+    } else {
+        Err("unexpected text after closing quote")
+    }
+}
+
+fn check_key(key: &str) -> Result<(), SerdeVaultError> {
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+    match valid {
+        true => Ok(()),
+        false => Err(SerdeVaultError::InvalidConfig(format!(
+            "invalid variable name {key:?}"
+        ))),
+    }
+}
+
+fn diff(from: &EnvVars, to: &EnvVars) -> Vec<EnvChange> {
+    let mut changes = Vec::new();
+    for (key, value) in to {
+        match from.get(key) {
+            None => changes.push(EnvChange::Added {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(old) if old != value => changes.push(EnvChange::Changed {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for key in from.keys().filter(|key| !to.contains_key(*key)) {
+        changes.push(EnvChange::Removed { key: key.clone() });
+    }
+    changes.sort_by(|a, b| a.key().cmp(b.key()));
+    changes
+}
+
+fn render(vars: &EnvVars) -> String {
+    let mut out = String::new();
+    for (key, value) in vars {
+        let plain = !value.is_empty()
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-./:@,+=".contains(c));
+        if plain {
+            out.push_str(&format!("{key}={value}\n"));
+            continue;
+        }
+        let mut quoted = String::with_capacity(value.len() + 2);
+        for c in value.chars() {
+            match c {
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                '"' | '\\' => {
+                    quoted.push('\\');
+                    quoted.push(c);
+                }
+                c => quoted.push(c),
+            }
+        }
+        out.push_str(&format!("{key}=\"{quoted}\"\n"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const DOTENV: &str = r#"
+# Database
+export DB_URL=postgres://localhost/app  # local only
+DB_PASSWORD='p#ss "word"'
+GREETING="hello\n\"world\""
+EMPTY=
+"#;
+
+    #[test]
+    fn test_import_diff_and_patch() {
+        let dir = tempdir().unwrap();
+        let env: DotEnvVault = VaultFile::open(dir.path().join("env.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        env.import(DOTENV).unwrap();
+        let vars = env.vars().unwrap();
+        assert_eq!(vars["DB_URL"], "postgres://localhost/app");
+        assert_eq!(vars["DB_PASSWORD"], r#"p#ss "word""#);
+        assert_eq!(vars["GREETING"], "hello\n\"world\"");
+        assert_eq!(vars["EMPTY"], "");
+        assert_eq!(parse(&env.to_dotenv().unwrap()).unwrap(), vars);
+
+        env.set("API_TOKEN", "t0ken").unwrap();
+        assert!(env.unset("EMPTY").unwrap());
+        let changes = env.diff(DOTENV).unwrap();
+        let shown: Vec<_> = changes.iter().map(ToString::to_string).collect();
+        assert_eq!(shown, ["- API_TOKEN", "+ EMPTY"]);
+        env.patch(&changes[1..]).unwrap();
+        assert_eq!(env.get("EMPTY").unwrap().as_deref(), Some(""));
+        assert_eq!(env.get("API_TOKEN").unwrap().as_deref(), Some("t0ken"));
+
+        let err = parse("A=1\nnot a pair").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::InvalidFormat(m) if m.contains("line 2")));
+        assert!(env.set("1BAD", "x").is_err());
+    }
+}
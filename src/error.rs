@@ -1,9 +1,61 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum SerdeVaultError {
+    /// The vault file does not exist (yet).
+    #[error("Vault not found: {}", .0.display())]
+    VaultNotFound(PathBuf),
+
+    /// The OS refused access to the vault or one of its artifacts.
+    #[error("Permission denied: {0}")]
+    PermissionDenied(#[source] std::io::Error),
+
+    /// [`VaultFile::load_version`](crate::VaultFile::load_version) was asked for a
+    /// revision the history doesn't hold.
+    #[error("Vault version {0} not found in history")]
+    VersionNotFound(usize),
+
+    /// [`VaultFile::load_path`](crate::VaultFile::load_path) was given a JSON
+    /// pointer that doesn't exist in the stored document.
+    #[error("Nothing stored at JSON pointer {0:?}")]
+    PointerNotFound(String),
+
+    /// A [`VaultStore`](crate::VaultStore) has no entry of this name.
+    #[error("No entry named {0:?} in the store")]
+    EntryNotFound(String),
+
+    /// An imported or merged [`VaultStore`](crate::VaultStore) entry has the
+    /// name of an existing one, under [`OnConflict::Fail`](crate::store::OnConflict)
+    /// or [`MergeStrategy::ErrorOnConflict`](crate::store::MergeStrategy).
+    #[error("Entry {0:?} already exists in the store")]
+    EntryExists(String),
+
+    /// A [`VaultStore`](crate::VaultStore) entry was put with a TTL that has
+    /// passed.
+    #[error("Entry {0:?} has expired")]
+    EntryExpired(String),
+
+    /// A [`VaultStore`](crate::VaultStore) entry has a password of its own and
+    /// was read, or overwritten with `put`, without it.
+    #[error("Entry {0:?} is protected by its own password")]
+    EntryProtected(String),
+
+    /// [`VaultFile::save_if_unchanged`](crate::VaultFile::save_if_unchanged)
+    /// found that another writer saved the vault since it was loaded.
+    #[error("Vault changed since it was loaded: expected generation {expected}, found {found}")]
+    GenerationConflict { expected: u64, found: u64 },
+
+    /// A write would take a size-capped [`VaultStore`](crate::VaultStore)
+    /// over its cap, under [`Eviction::RejectWrites`](crate::store::Eviction)
+    /// or with an entry too large for the cap on its own.
+    #[error("Store would grow to {size} bytes, over its cap of {max}")]
+    StoreFull { size: usize, max: usize },
+
     #[error("I/O error: {0}")]
-    IoError(#[from] std::io::Error),
+    IoError(#[source] std::io::Error),
 
     #[error("Serialization error: {0}")]
     SerializationError(String),
@@ -11,6 +63,11 @@ pub enum SerdeVaultError {
     #[error("Deserialization error: {0}")]
     DeserializationError(String),
 
+    /// The payload's schema version is unknown, or an upgrade step couldn't read it.
+    /// See [`Migrations`](crate::migration::Migrations).
+    #[error("Schema migration failed: {0}")]
+    MigrationError(String),
+
     #[error("Encryption error: {0}")]
     EncryptionError(String),
 
@@ -22,9 +79,325 @@ pub enum SerdeVaultError {
     #[error("Key derivation error: {0}")]
     KdfError(String),
 
+    /// Rejected by [`VaultFileBuilder::build`](crate::VaultFileBuilder::build).
+    #[error("Invalid vault configuration: {0}")]
+    InvalidConfig(String),
+
     #[error("Invalid vault format: {0}")]
     InvalidFormat(String),
 
     #[error("Unsupported vault version: {0}")]
     UnsupportedVersion(u8),
+
+    /// The payload was written with a [`Format`](crate::Format) this build
+    /// doesn't know (id shown).
+    #[error("Unsupported payload format: {0}")]
+    UnsupportedFormat(u8),
+
+    /// The vault file is accessible to group or others (Unix mode shown in octal).
+    /// Only returned when strict permission checking is enabled.
+    #[error("Insecure vault permissions: {0:o} — file must not be group/world accessible")]
+    InsecurePermissions(u32),
+
+    /// [`VaultFile::load_checked`](crate::VaultFile::load_checked) found that
+    /// the loaded type doesn't have the shape of the data that was saved: fields
+    /// were added, removed or retyped since. Both shape hashes are given.
+    #[error("Schema changed: saved data has shape {stored:016x}, the loaded type {current:016x}")]
+    SchemaChanged { stored: u64, current: u64 },
+
+    /// An [`UnlockedVault`](crate::UnlockedVault) can no longer use its key,
+    /// because the vault was rewritten under a different salt.
+    #[error("Vault is locked — unlock it again with the password")]
+    Locked,
+
+    /// The vault path is a symbolic link and the handle uses
+    /// [`SymlinkPolicy::Refuse`](crate::SymlinkPolicy::Refuse).
+    #[error("Vault path is a symbolic link: {}", .0.display())]
+    SymlinkRefused(PathBuf),
+
+    /// Another handle, usually in another process, held the vault's advisory
+    /// lock for longer than [`VaultFile::with_lock_timeout`](crate::VaultFile::with_lock_timeout)
+    /// allows.
+    #[error("Vault is in use — its lock wasn't released in time")]
+    WouldBlock,
+
+    /// A thread holding a vault's shared lock asked for its exclusive lock,
+    /// e.g. by saving inside a locked load. Shared locks aren't upgraded: the
+    /// exclusive lock would wait on the thread's own shared one forever.
+    #[error("Vault's shared lock is held by this thread and can't be made exclusive")]
+    LockUpgrade,
+
+    /// The handle was opened with [`VaultFile::open_read_only`](crate::VaultFile::open_read_only).
+    #[error("Vault is opened read-only")]
+    ReadOnly,
+
+    /// An I/O or format error, annotated with the vault it concerns and what was
+    /// being done to it. Use [`root`](Self::root) or [`kind`](Self::kind) to look
+    /// at the underlying error.
+    #[error("{source} (while {operation} {})", path.display())]
+    Context {
+        operation: Operation,
+        path: PathBuf,
+        #[source]
+        source: Box<SerdeVaultError>,
+    },
+}
+
+/// What the crate was doing when an error occurred.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    Load,
+    Save,
+    Update,
+    Snapshot,
+    Restore,
+    Rename,
+    Destroy,
+    HardenPermissions,
+    Export,
+    Append,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Load => "loading",
+            Operation::Save => "saving",
+            Operation::Update => "updating",
+            Operation::Snapshot => "snapshotting",
+            Operation::Restore => "restoring",
+            Operation::Rename => "renaming",
+            Operation::Destroy => "deleting",
+            Operation::HardenPermissions => "hardening permissions of",
+            Operation::Export => "exporting to",
+            Operation::Append => "appending to",
+        })
+    }
+}
+
+/// Stable, matchable category of a [`SerdeVaultError`].
+///
+/// Variants may be added in minor releases; match with a wildcard arm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    NotFound,
+    PermissionDenied,
+    Io,
+    Serialization,
+    Deserialization,
+    Migration,
+    SchemaChanged,
+    Encryption,
+    DecryptionFailed,
+    Kdf,
+    InvalidConfig,
+    InvalidFormat,
+    UnsupportedVersion,
+    UnsupportedFormat,
+    InsecurePermissions,
+    ReadOnly,
+    SymlinkRefused,
+    Locked,
+    Conflict,
+    CapacityExceeded,
+    WouldBlock,
+}
+
+impl SerdeVaultError {
+    /// The category of this error, for programmatic handling.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            SerdeVaultError::VaultNotFound(_)
+            | SerdeVaultError::VersionNotFound(_)
+            | SerdeVaultError::PointerNotFound(_)
+            | SerdeVaultError::EntryNotFound(_)
+            | SerdeVaultError::EntryExpired(_) => ErrorKind::NotFound,
+            SerdeVaultError::PermissionDenied(_) => ErrorKind::PermissionDenied,
+            SerdeVaultError::IoError(_) => ErrorKind::Io,
+            SerdeVaultError::SerializationError(_) => ErrorKind::Serialization,
+            SerdeVaultError::DeserializationError(_) => ErrorKind::Deserialization,
+            SerdeVaultError::MigrationError(_) => ErrorKind::Migration,
+            SerdeVaultError::SchemaChanged { .. } => ErrorKind::SchemaChanged,
+            SerdeVaultError::EncryptionError(_) => ErrorKind::Encryption,
+            SerdeVaultError::DecryptionFailed => ErrorKind::DecryptionFailed,
+            SerdeVaultError::KdfError(_) => ErrorKind::Kdf,
+            SerdeVaultError::InvalidConfig(_) => ErrorKind::InvalidConfig,
+            SerdeVaultError::InvalidFormat(_) => ErrorKind::InvalidFormat,
+            SerdeVaultError::UnsupportedVersion(_) => ErrorKind::UnsupportedVersion,
+            SerdeVaultError::UnsupportedFormat(_) => ErrorKind::UnsupportedFormat,
+            SerdeVaultError::InsecurePermissions(_) => ErrorKind::InsecurePermissions,
+            SerdeVaultError::ReadOnly => ErrorKind::ReadOnly,
+            SerdeVaultError::SymlinkRefused(_) => ErrorKind::SymlinkRefused,
+            SerdeVaultError::Locked | SerdeVaultError::EntryProtected(_) => ErrorKind::Locked,
+            SerdeVaultError::EntryExists(_) | SerdeVaultError::GenerationConflict { .. } => {
+                ErrorKind::Conflict
+            }
+            SerdeVaultError::StoreFull { .. } => ErrorKind::CapacityExceeded,
+            SerdeVaultError::WouldBlock | SerdeVaultError::LockUpgrade => ErrorKind::WouldBlock,
+            SerdeVaultError::Context { source, .. } => source.kind(),
+        }
+    }
+
+    /// Stable diagnostic code, e.g. `serdevault::decryption_failed`.
+    pub fn code(&self) -> &'static str {
+        match self.kind() {
+            ErrorKind::NotFound => "serdevault::not_found",
+            ErrorKind::PermissionDenied => "serdevault::permission_denied",
+            ErrorKind::Io => "serdevault::io",
+            ErrorKind::Serialization => "serdevault::serialization",
+            ErrorKind::Deserialization => "serdevault::deserialization",
+            ErrorKind::Migration => "serdevault::migration",
+            ErrorKind::SchemaChanged => "serdevault::schema_changed",
+            ErrorKind::Encryption => "serdevault::encryption",
+            ErrorKind::DecryptionFailed => "serdevault::decryption_failed",
+            ErrorKind::Kdf => "serdevault::kdf",
+            ErrorKind::InvalidConfig => "serdevault::invalid_config",
+            ErrorKind::InvalidFormat => "serdevault::invalid_format",
+            ErrorKind::UnsupportedVersion => "serdevault::unsupported_version",
+            ErrorKind::UnsupportedFormat => "serdevault::unsupported_format",
+            ErrorKind::InsecurePermissions => "serdevault::insecure_permissions",
+            ErrorKind::ReadOnly => "serdevault::read_only",
+            ErrorKind::SymlinkRefused => "serdevault::symlink_refused",
+            ErrorKind::Locked => "serdevault::locked",
+            ErrorKind::Conflict => "serdevault::conflict",
+            ErrorKind::CapacityExceeded => "serdevault::capacity_exceeded",
+            ErrorKind::WouldBlock => "serdevault::would_block",
+        }
+    }
+
+    /// A remediation hint suitable for showing to end users, if there is one.
+    pub fn help(&self) -> Option<&'static str> {
+        match self.root() {
+            SerdeVaultError::VersionNotFound(_) => {
+                return Some("list_versions() shows which revisions the history holds")
+            }
+            SerdeVaultError::PointerNotFound(_) => {
+                return Some(
+                    "pointers look like /section/field; inspect the document with load_value()",
+                )
+            }
+            SerdeVaultError::EntryNotFound(_) => {
+                return Some("list() shows the names of the entries the store holds")
+            }
+            SerdeVaultError::EntryExists(_) => {
+                return Some(
+                    "pick another OnConflict or MergeStrategy, or remove or rename the entry first",
+                )
+            }
+            SerdeVaultError::GenerationConflict { .. } => return Some(
+                "load the vault again with load_with_generation(), reapply the change and retry",
+            ),
+            SerdeVaultError::EntryExpired(_) => {
+                return Some("put the entry again, or drop it with purge_expired()")
+            }
+            SerdeVaultError::EntryProtected(_) => {
+                return Some("read the entry with get_protected() and its entry password; to overwrite it, use put_protected() or remove it first")
+            }
+            SerdeVaultError::StoreFull { .. } => {
+                return Some(
+                    "remove entries, raise the cap, or evict with Eviction::LeastRecentlyUsed",
+                )
+            }
+            SerdeVaultError::LockUpgrade => {
+                return Some("finish reading and drop the shared lock before writing the vault")
+            }
+            _ => {}
+        }
+        Some(match self.kind() {
+            ErrorKind::NotFound => {
+                "the vault has not been created yet; use load_or_init() or load_opt() to handle first runs"
+            }
+            ErrorKind::PermissionDenied => {
+                "check that the current user owns the vault file and its directory"
+            }
+            ErrorKind::Deserialization => {
+                "the vault decrypted fine but holds a different type or an older layout of this struct"
+            }
+            ErrorKind::Migration => {
+                "the vault was written by a newer build, or a migration step expects a different older layout"
+            }
+            ErrorKind::SchemaChanged => {
+                "the struct changed since the vault was saved; add a migration, or save to accept the new layout"
+            }
+            ErrorKind::DecryptionFailed => {
+                "this often means the password is wrong; use verify_password() to check, otherwise restore the file from a backup"
+            }
+            ErrorKind::Kdf => "the Argon2 parameters are invalid; m_cost must be at least 8 * p_cost",
+            ErrorKind::InvalidFormat => "the file is not a serdevault vault, or it was truncated",
+            ErrorKind::UnsupportedVersion => {
+                "the vault was written by a newer version of serdevault; upgrade to read it"
+            }
+            ErrorKind::UnsupportedFormat => {
+                "the vault was saved with a serializer this build doesn't support; enable it or upgrade"
+            }
+            ErrorKind::InsecurePermissions => {
+                "restrict the file to its owner (chmod 600) or call harden_permissions()"
+            }
+            ErrorKind::Locked => "lock() the handle and unlock() it again with the password",
+            ErrorKind::WouldBlock => {
+                "another process is using the vault; retry later or raise with_lock_timeout()"
+            }
+            ErrorKind::SymlinkRefused => {
+                "open the link's target directly, or choose SymlinkPolicy::Follow"
+            }
+            _ => return None,
+        })
+    }
+
+    /// The underlying error, without any path/operation context.
+    pub fn root(&self) -> &SerdeVaultError {
+        match self {
+            SerdeVaultError::Context { source, .. } => source.root(),
+            other => other,
+        }
+    }
+
+    /// The vault path this error concerns, if known.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            SerdeVaultError::Context { path, .. }
+            | SerdeVaultError::VaultNotFound(path)
+            | SerdeVaultError::SymlinkRefused(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The operation that failed, if recorded.
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            SerdeVaultError::Context { operation, .. } => Some(*operation),
+            _ => None,
+        }
+    }
+
+    /// Attach the vault path and operation to I/O and format errors.
+    ///
+    /// Other errors (decryption, serde, …) are about the contents rather than the
+    /// file and are returned unchanged, as are errors that already have context.
+    pub(crate) fn context(self, operation: Operation, path: &Path) -> Self {
+        match self {
+            SerdeVaultError::PermissionDenied(_)
+            | SerdeVaultError::IoError(_)
+            | SerdeVaultError::InvalidFormat(_)
+            | SerdeVaultError::UnsupportedVersion(_)
+            | SerdeVaultError::UnsupportedFormat(_)
+            | SerdeVaultError::InsecurePermissions(_) => SerdeVaultError::Context {
+                operation,
+                path: path.to_path_buf(),
+                source: Box::new(self),
+            },
+            other => other,
+        }
+    }
+}
+
+impl From<std::io::Error> for SerdeVaultError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::PermissionDenied => SerdeVaultError::PermissionDenied(e),
+            _ => SerdeVaultError::IoError(e),
+        }
+    }
 }
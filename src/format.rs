@@ -4,56 +4,152 @@ use std::path::Path;
 
 use tempfile::NamedTempFile;
 
+use crate::artifacts;
 use crate::crypto::cipher::NONCE_SIZE;
 use crate::crypto::kdf::SALT_SIZE;
 use crate::error::SerdeVaultError;
+use crate::permissions;
 
 pub const MAGIC: &[u8; 4] = b"SVLT";
-pub const FORMAT_VERSION: u8 = 1;
+/// Version written by this build.
+pub const FORMAT_VERSION: u8 = 2;
+/// The original layout, still readable: no payload format, flags or extensions,
+/// and no associated data.
+pub const LEGACY_VERSION: u8 = 1;
 
-/// Layout:
+/// Flag bit: the body is a sequence of STREAM chunks (see `crypto::stream`)
+/// rather than a single AES-GCM ciphertext.
+pub const FLAG_CHUNKED: u8 = 1;
+
+/// Header extension: FNV-1a hash (u64 LE) of the payload's shape, written by
+/// handles `with_schema_hash(true)`.
+pub const EXT_SCHEMA_HASH: u8 = 1;
+
+/// Header extension: the vault's generation (u64 LE), one more than that of the
+/// file each save replaces. Not written in deterministic mode.
+pub const EXT_GENERATION: u8 = 2;
+
+/// Layout (version 2):
 ///   [4]  magic
 ///   [1]  version
+///   [1]  payload format (see `Format::id`)
+///   [1]  flags
 ///   [32] salt
 ///   [4]  m_cost (u32 LE)
 ///   [4]  t_cost (u32 LE)
 ///   [4]  p_cost (u32 LE)
 ///   [12] nonce
-///   ---- total: 61 bytes
-///   [N]  ciphertext + 16-byte GCM tag
-pub const HEADER_SIZE: usize = 4 + 1 + SALT_SIZE + 4 + 4 + 4 + NONCE_SIZE;
+///   [2]  extensions length n (u16 LE)
+///   [n]  extensions: entries of [1] tag, [2] length (u16 LE), [length] value
+///   ---- total: 66 + n bytes, authenticated as AES-GCM associated data
+///   [N]  ciphertext + 16-byte GCM tag, or 64 KiB chunks each followed by a
+///        16-byte tag if `FLAG_CHUNKED` is set
+///
+/// Version 1 has neither the format, flags nor extension fields (61 bytes).
+pub const HEADER_SIZE: usize = 4 + 1 + 1 + 1 + SALT_SIZE + 4 + 4 + 4 + NONCE_SIZE + 2;
+
+/// Size of a version 1 header, the smallest valid file.
+const LEGACY_HEADER_SIZE: usize = 4 + 1 + SALT_SIZE + 4 + 4 + 4 + NONCE_SIZE;
 
 /// Parsed vault header.
 pub struct VaultHeader {
+    pub version: u8,
+    /// Payload format id; 0 in version 1, which didn't record it.
+    pub format: u8,
+    pub flags: u8,
     pub salt: [u8; SALT_SIZE],
     pub m_cost: u32,
     pub t_cost: u32,
     pub p_cost: u32,
     pub nonce: [u8; NONCE_SIZE],
+    /// Raw extension area, carried along unparsed so it stays authenticated.
+    pub extensions: Vec<u8>,
+}
+
+impl VaultHeader {
+    /// Whether the body is chunked.
+    pub fn is_chunked(&self) -> bool {
+        self.flags & FLAG_CHUNKED != 0
+    }
+
+    /// Value of the extension entry tagged `tag`, if there is one.
+    pub fn extension(&self, tag: u8) -> Option<&[u8]> {
+        let mut rest = self.extensions.as_slice();
+        while rest.len() >= 3 {
+            let len = usize::from(u16::from_le_bytes([rest[1], rest[2]]));
+            let value = rest.get(3..3 + len)?;
+            if rest[0] == tag {
+                return Some(value);
+            }
+            rest = &rest[3 + len..];
+        }
+        None
+    }
+
+    /// Bytes authenticated together with the body: the whole encoded header,
+    /// or nothing for version 1.
+    pub fn associated_data(&self) -> Vec<u8> {
+        if self.version == LEGACY_VERSION {
+            Vec::new()
+        } else {
+            encode_header(self)
+        }
+    }
+}
+
+/// Append an entry to an extension area.
+pub fn push_extension(extensions: &mut Vec<u8>, tag: u8, value: &[u8]) {
+    let len = u16::try_from(value.len()).expect("extension values are small");
+    extensions.push(tag);
+    extensions.extend_from_slice(&len.to_le_bytes());
+    extensions.extend_from_slice(value);
+}
+
+/// Drop every entry tagged `tag` from an extension area.
+pub fn remove_extension(extensions: &mut Vec<u8>, tag: u8) {
+    let mut kept = Vec::with_capacity(extensions.len());
+    let mut rest = extensions.as_slice();
+    while rest.len() >= 3 {
+        let end = (3 + usize::from(u16::from_le_bytes([rest[1], rest[2]]))).min(rest.len());
+        if rest[0] != tag {
+            kept.extend_from_slice(&rest[..end]);
+        }
+        rest = &rest[end..];
+    }
+    *extensions = kept;
 }
 
 /// Serialize the header + ciphertext into bytes.
 pub fn encode(header: &VaultHeader, ciphertext: &[u8]) -> Vec<u8> {
-    let mut buf = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+    let mut buf = encode_header(header);
+    buf.extend_from_slice(ciphertext);
+    buf
+}
+
+/// Serialize just the header (always in the current layout), for writers that
+/// stream the ciphertext after it.
+pub fn encode_header(header: &VaultHeader) -> Vec<u8> {
+    let extensions_len =
+        u16::try_from(header.extensions.len()).expect("header extensions fit in 64 KiB");
+    let mut buf = Vec::with_capacity(HEADER_SIZE + header.extensions.len());
     buf.extend_from_slice(MAGIC);
     buf.push(FORMAT_VERSION);
+    buf.push(header.format);
+    buf.push(header.flags);
     buf.extend_from_slice(&header.salt);
     buf.extend_from_slice(&header.m_cost.to_le_bytes());
     buf.extend_from_slice(&header.t_cost.to_le_bytes());
     buf.extend_from_slice(&header.p_cost.to_le_bytes());
     buf.extend_from_slice(&header.nonce);
-    buf.extend_from_slice(ciphertext);
+    buf.extend_from_slice(&extensions_len.to_le_bytes());
+    buf.extend_from_slice(&header.extensions);
     buf
 }
 
 /// Parse the binary vault format. Returns `(header, ciphertext)`.
 pub fn decode(data: &[u8]) -> Result<(VaultHeader, &[u8]), SerdeVaultError> {
-    if data.len() < HEADER_SIZE {
-        return Err(SerdeVaultError::InvalidFormat(format!(
-            "file too small: {} bytes (minimum is {})",
-            data.len(),
-            HEADER_SIZE
-        )));
+    if data.len() < LEGACY_HEADER_SIZE {
+        return Err(too_small(data.len(), LEGACY_HEADER_SIZE));
     }
 
     if &data[0..4] != MAGIC {
@@ -63,48 +159,218 @@ pub fn decode(data: &[u8]) -> Result<(VaultHeader, &[u8]), SerdeVaultError> {
     }
 
     let version = data[4];
-    if version != FORMAT_VERSION {
-        return Err(SerdeVaultError::UnsupportedVersion(version));
-    }
+    let (format, flags, mut rest) = match version {
+        LEGACY_VERSION => (0, 0, &data[5..]),
+        FORMAT_VERSION if data.len() >= HEADER_SIZE => (data[5], data[6], &data[7..]),
+        FORMAT_VERSION => return Err(too_small(data.len(), HEADER_SIZE)),
+        _ => return Err(SerdeVaultError::UnsupportedVersion(version)),
+    };
 
-    let mut salt = [0u8; SALT_SIZE];
-    salt.copy_from_slice(&data[5..5 + SALT_SIZE]);
+    let salt = take::<SALT_SIZE>(&mut rest);
+    let m_cost = u32::from_le_bytes(take(&mut rest));
+    let t_cost = u32::from_le_bytes(take(&mut rest));
+    let p_cost = u32::from_le_bytes(take(&mut rest));
+    let nonce = take::<NONCE_SIZE>(&mut rest);
 
-    let o = 5 + SALT_SIZE; // = 37
-    let m_cost = u32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]);
-    let t_cost = u32::from_le_bytes([data[o + 4], data[o + 5], data[o + 6], data[o + 7]]);
-    let p_cost = u32::from_le_bytes([data[o + 8], data[o + 9], data[o + 10], data[o + 11]]);
-
-    let nonce_start = o + 12; // = 49
-    let mut nonce = [0u8; NONCE_SIZE];
-    nonce.copy_from_slice(&data[nonce_start..nonce_start + NONCE_SIZE]);
-
-    let ciphertext = &data[HEADER_SIZE..];
+    let mut extensions = Vec::new();
+    if version != LEGACY_VERSION {
+        let len = usize::from(u16::from_le_bytes(take(&mut rest)));
+        if rest.len() < len {
+            return Err(SerdeVaultError::InvalidFormat(format!(
+                "header extensions truncated: {} of {len} bytes present",
+                rest.len()
+            )));
+        }
+        let (ext, body) = rest.split_at(len);
+        extensions = ext.to_vec();
+        rest = body;
+    }
 
     Ok((
         VaultHeader {
+            version,
+            format,
+            flags,
             salt,
             m_cost,
             t_cost,
             p_cost,
             nonce,
+            extensions,
         },
-        ciphertext,
+        rest,
     ))
 }
 
+/// Split `N` bytes off the front of `data`, which the caller has checked is long enough.
+fn take<const N: usize>(data: &mut &[u8]) -> [u8; N] {
+    let (head, rest) = data.split_at(N);
+    *data = rest;
+    head.try_into().expect("split_at returned N bytes")
+}
+
+fn too_small(len: usize, min: usize) -> SerdeVaultError {
+    SerdeVaultError::InvalidFormat(format!("file too small: {len} bytes (minimum is {min})"))
+}
+
+/// Knobs for `atomic_write`.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions {
+    /// fsync the parent directory after the rename so the new directory entry
+    /// survives a crash. Costs one extra syscall round-trip per save.
+    pub sync_dir: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self { sync_dir: true }
+    }
+}
+
 /// Write vault bytes to disk atomically.
-pub fn atomic_write(path: &Path, data: &[u8]) -> Result<(), SerdeVaultError> {
-    let parent = path.parent().unwrap_or_else(|| Path::new("."));
+pub fn atomic_write(path: &Path, data: &[u8], opts: &WriteOptions) -> Result<(), SerdeVaultError> {
+    atomic_write_with(path, opts, |file| Ok(file.write_all(data)?))
+}
+
+/// Like [`atomic_write`], but let `write` produce the contents directly into
+/// the temp file. If it fails, the temp file is discarded and `path` is untouched.
+pub fn atomic_write_with(
+    path: &Path,
+    opts: &WriteOptions,
+    write: impl FnOnce(&mut fs::File) -> Result<(), SerdeVaultError>,
+) -> Result<(), SerdeVaultError> {
+    let parent = artifacts::parent_dir(path);
     fs::create_dir_all(parent)?;
 
-    let mut tmp = NamedTempFile::new_in(parent)?;
-    tmp.write_all(data)?;
+    // Name the temp file after the vault so leftovers from a crash can be found.
+    let mut tmp = tempfile::Builder::new()
+        .prefix(&artifacts::temp_prefix(path))
+        .suffix(artifacts::TEMP_SUFFIX)
+        .rand_bytes(artifacts::TEMP_RANDOM_LEN)
+        .tempfile_in(parent)?;
+    // The temp file becomes the vault, so this also tightens a vault that was
+    // previously created with a looser mode.
+    permissions::restrict(tmp.path())?;
+    write(tmp.as_file_mut())?;
     tmp.flush()?;
     tmp.as_file().sync_all()?;
 
-    tmp.persist(path)
-        .map_err(|e| SerdeVaultError::IoError(e.error))?;
+    replace(tmp, path)?;
+
+    // ReplaceFileW carries the replaced file's DACL over to the new one.
+    #[cfg(windows)]
+    permissions::restrict(path)?;
+
+    if opts.sync_dir {
+        sync_dir(parent)?;
+    }
+
+    Ok(())
+}
+
+/// Move the finished temp file over `path`.
+#[cfg(not(windows))]
+fn replace(tmp: NamedTempFile, path: &Path) -> std::io::Result<()> {
+    tmp.persist(path).map(|_| ()).map_err(|e| e.error)
+}
+
+/// How many times a replace blocked by another process is retried on Windows.
+#[cfg(windows)]
+const REPLACE_RETRIES: u32 = 8;
+
+/// Move the finished temp file over `path`.
+///
+/// Windows refuses to replace a file while anyone holds it open without
+/// `FILE_SHARE_DELETE` — antivirus scanners and indexers do this routinely, for
+/// a few milliseconds at a time. Use `ReplaceFileW` when the destination exists
+/// (it preserves the destination's attributes) and retry sharing
+/// violations with exponential backoff before giving up.
+#[cfg(windows)]
+fn replace(tmp: NamedTempFile, path: &Path) -> std::io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::time::Duration;
+
+    use windows_sys::Win32::Foundation::{
+        ERROR_ACCESS_DENIED, ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION,
+        ERROR_UNABLE_TO_MOVE_REPLACEMENT, ERROR_UNABLE_TO_REMOVE_REPLACED,
+    };
+    use windows_sys::Win32::Storage::FileSystem::{ReplaceFileW, REPLACEFILE_IGNORE_MERGE_ERRORS};
+
+    fn wide(p: &Path) -> Vec<u16> {
+        p.as_os_str()
+            .encode_wide()
+            .chain(std::iter::once(0))
+            .collect()
+    }
+
+    fn is_transient(err: &std::io::Error) -> bool {
+        matches!(
+            err.raw_os_error().map(|c| c as u32),
+            Some(
+                ERROR_ACCESS_DENIED
+                    | ERROR_SHARING_VIOLATION
+                    | ERROR_LOCK_VIOLATION
+                    | ERROR_UNABLE_TO_MOVE_REPLACEMENT
+                    | ERROR_UNABLE_TO_REMOVE_REPLACED
+            )
+        )
+    }
+
+    // Close our own handle first: ReplaceFileW opens the replacement itself.
+    let tmp_path = tmp.into_temp_path();
+    let mut delay = Duration::from_millis(5);
+    let mut attempt = 0;
+
+    loop {
+        let result = if path.exists() {
+            let dst = wide(path);
+            let src = wide(&tmp_path);
+            // SAFETY: both buffers are NUL-terminated and outlive the call; the
+            // backup name, exclude and reserved pointers are documented as optional.
+            let ok = unsafe {
+                ReplaceFileW(
+                    dst.as_ptr(),
+                    src.as_ptr(),
+                    std::ptr::null(),
+                    REPLACEFILE_IGNORE_MERGE_ERRORS,
+                    std::ptr::null(),
+                    std::ptr::null(),
+                )
+            };
+            if ok != 0 {
+                Ok(())
+            } else {
+                Err(std::io::Error::last_os_error())
+            }
+        } else {
+            std::fs::rename(&tmp_path, path)
+        };
+
+        match result {
+            Ok(()) => {
+                // The temp name no longer exists; don't let TempPath try to delete it.
+                let _ = tmp_path.keep();
+                return Ok(());
+            }
+            Err(e) if attempt < REPLACE_RETRIES && is_transient(&e) => {
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Flush a directory so a rename inside it is durable.
+#[cfg(unix)]
+pub(crate) fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
 
+/// Directory handles can't be fsynced portably elsewhere; NTFS journals the
+/// rename metadata itself.
+#[cfg(not(unix))]
+pub(crate) fn sync_dir(_dir: &Path) -> std::io::Result<()> {
     Ok(())
 }
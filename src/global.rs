@@ -0,0 +1,166 @@
+//! Process-wide registry of named vault handles.
+//!
+//! Configure each vault once at startup, then fetch it by name anywhere
+//! instead of threading handles through every constructor:
+//!
+//! ```no_run
+//! use serdevault::{global, VaultFile};
+//!
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Settings { theme: String }
+//!
+//! global::init("settings", VaultFile::open("~/.settings.vault", "my_password"))?;
+//!
+//! // ...elsewhere
+//! let settings: Settings = global::get::<Settings>("settings")?.load()?;
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+//!
+//! Unlocked sessions can be shared the same way, so the key is derived once
+//! for the whole process: [`init_unlocked`] registers an [`UnlockedVault`] and
+//! [`unlocked`] hands out `Arc`s to it. The key is wiped once the session is
+//! removed and the last `Arc` is dropped, or when it auto-locks. Both kinds of
+//! handle share one namespace.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::SerdeVaultError;
+use crate::typed::TypedVaultFile;
+use crate::vault::{UnlockedVault, VaultFile};
+
+#[derive(Default)]
+struct Registry {
+    vaults: HashMap<String, VaultFile>,
+    sessions: HashMap<String, Arc<UnlockedVault>>,
+}
+
+impl Registry {
+    fn claim(&self, name: &str) -> Result<(), SerdeVaultError> {
+        if self.vaults.contains_key(name) || self.sessions.contains_key(name) {
+            return Err(SerdeVaultError::InvalidConfig(format!(
+                "a vault is already registered as {name:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn registry() -> &'static RwLock<Registry> {
+    static REGISTRY: OnceLock<RwLock<Registry>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register `vault` under `name`. Fails with `InvalidConfig` if the name is taken.
+pub fn init(name: &str, vault: VaultFile) -> Result<(), SerdeVaultError> {
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    registry.claim(name)?;
+    registry.vaults.insert(name.to_string(), vault);
+    Ok(())
+}
+
+/// Register an unlocked session under `name` and return a shared handle to it.
+/// Fails with `InvalidConfig` if the name is taken.
+pub fn init_unlocked(
+    name: &str,
+    vault: UnlockedVault,
+) -> Result<Arc<UnlockedVault>, SerdeVaultError> {
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    registry.claim(name)?;
+    let vault = Arc::new(vault);
+    registry
+        .sessions
+        .insert(name.to_string(), Arc::clone(&vault));
+    Ok(vault)
+}
+
+/// The vault registered under `name`, bound to `T`.
+///
+/// Fails with `InvalidConfig` if nothing is registered under that name.
+pub fn get<T: Serialize + DeserializeOwned>(
+    name: &str,
+) -> Result<TypedVaultFile<T>, SerdeVaultError> {
+    vault(name)
+        .map(VaultFile::typed)
+        .ok_or_else(|| SerdeVaultError::InvalidConfig(format!("no vault registered as {name:?}")))
+}
+
+/// A copy of the handle registered under `name`, if any.
+pub fn vault(name: &str) -> Option<VaultFile> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.vaults.get(name).cloned()
+}
+
+/// The unlocked session registered under `name`, if any.
+pub fn unlocked(name: &str) -> Option<Arc<UnlockedVault>> {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    registry.sessions.get(name).cloned()
+}
+
+/// Unregister `name`, returning its handle.
+pub fn remove(name: &str) -> Option<VaultFile> {
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    registry.vaults.remove(name)
+}
+
+/// Unregister the unlocked session `name`, returning it. Its key is wiped
+/// once every other `Arc` to it is dropped too.
+pub fn remove_unlocked(name: &str) -> Option<Arc<UnlockedVault>> {
+    let mut registry = registry().write().unwrap_or_else(|e| e.into_inner());
+    registry.sessions.remove(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::LockedVault;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_registry() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        init(
+            "test_registry",
+            VaultFile::open(&path, "pwd").with_params(8, 1, 1),
+        )
+        .unwrap();
+
+        let err = init("test_registry", VaultFile::open(&path, "pwd")).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+
+        get::<u32>("test_registry").unwrap().save(&5).unwrap();
+        assert_eq!(get::<u32>("test_registry").unwrap().load().unwrap(), 5);
+        assert_eq!(vault("test_registry").unwrap().path(), path);
+
+        assert!(remove("test_registry").is_some());
+        assert!(get::<u32>("test_registry").is_err());
+    }
+
+    #[test]
+    fn test_registry_shares_sessions() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        let file = VaultFile::open(&path, "pwd").with_params(8, 1, 1);
+        file.save(&5u32).unwrap();
+        let session = LockedVault::from(file.clone()).unlock("pwd").unwrap();
+        let shared = init_unlocked("test_sessions", session).unwrap();
+
+        let err = init("test_sessions", file).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+        assert!(vault("test_sessions").is_none());
+
+        let fetched = unlocked("test_sessions").unwrap();
+        assert!(Arc::ptr_eq(&shared, &fetched));
+        fetched.save(&6u32).unwrap();
+        assert_eq!(shared.load::<u32>().unwrap(), 6);
+
+        drop((shared, fetched));
+        let last = remove_unlocked("test_sessions").unwrap();
+        assert_eq!(last.load::<u32>().unwrap(), 6);
+        assert!(unlocked("test_sessions").is_none());
+    }
+}
@@ -1,8 +1,41 @@
+mod artifacts;
+mod backup;
 mod crypto;
 mod format;
+mod lock;
+mod paths;
+mod permissions;
+mod shred;
+mod time;
+mod wipe;
 
+pub mod autosave;
+pub mod builder;
+pub mod bytes;
+pub mod dotenv;
 pub mod error;
+pub mod global;
+pub mod log;
+pub mod migration;
+pub mod save;
+pub mod schema;
+pub mod serializer;
+pub mod ssh;
+pub mod store;
+pub mod typed;
 pub mod vault;
 
-pub use error::SerdeVaultError;
-pub use vault::VaultFile;
+pub use autosave::AutoSaved;
+pub use builder::{Cipher, Kdf, VaultFileBuilder};
+pub use dotenv::DotEnvVault;
+pub use error::{ErrorKind, Operation, SerdeVaultError};
+pub use log::VaultLog;
+pub use save::SaveManager;
+pub use serializer::{Codec, Format};
+pub use ssh::SshKey;
+pub use store::{EntryMetadata, VaultStore};
+pub use typed::TypedVaultFile;
+pub use vault::{
+    EditGuard, EnvGuard, LockedVault, MergeConflict, Records, SymlinkPolicy, UnlockedVault,
+    VaultCache, VaultFile, VaultSecret, VersionInfo,
+};
@@ -0,0 +1,139 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+use crate::artifacts;
+use crate::error::SerdeVaultError;
+
+/// Longest pause between two attempts at a contended lock.
+const MAX_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// The lock files locked in this process, by the thread that took each lock and
+/// whether it is shared, so that nested calls (the `save` inside an `update`)
+/// don't wait on their own lock. Paths have a canonical parent directory, so
+/// that the same vault reached by another path is recognized.
+static HELD: Mutex<Vec<Held>> = Mutex::new(Vec::new());
+
+/// An entry of [`HELD`].
+#[derive(Clone, PartialEq, Eq)]
+struct Held {
+    thread: ThreadId,
+    lock_path: PathBuf,
+    shared: bool,
+}
+
+/// An advisory lock on a vault, held until dropped.
+///
+/// The lock is taken on a sibling lock file rather than the vault itself,
+/// because saves replace the vault's inode and a lock on the old one would
+/// protect nothing. Other processes only see it if they lock too — it does
+/// not stop a plain `open()`.
+///
+/// Locks are reentrant per thread: while a thread holds a vault's lock, taking
+/// it again succeeds at once and releases nothing when dropped, except that a
+/// shared lock isn't upgraded to an exclusive one
+/// ([`SerdeVaultError::LockUpgrade`]). Other threads of the process wait like
+/// other processes do.
+pub struct VaultLock {
+    /// The lock file and its entry in [`HELD`], if this guard took the lock.
+    /// Closing the file releases it.
+    held: Option<(File, Held)>,
+}
+
+impl VaultLock {
+    /// Block until the exclusive lock for `vault_path` is acquired.
+    pub fn exclusive(vault_path: &Path) -> Result<Self, SerdeVaultError> {
+        Self::acquire(vault_path, false, None)
+    }
+
+    /// Take the lock for `vault_path`, shared with other readers or exclusive,
+    /// waiting for at most `timeout` (forever if `None`) before failing with
+    /// [`SerdeVaultError::WouldBlock`].
+    pub fn acquire(
+        vault_path: &Path,
+        shared: bool,
+        timeout: Option<Duration>,
+    ) -> Result<Self, SerdeVaultError> {
+        let lock_path = canonical_lock_path(vault_path)?;
+        let thread = thread::current().id();
+        if let Some(held) = held()
+            .iter()
+            .find(|h| h.thread == thread && h.lock_path == lock_path)
+        {
+            if held.shared && !shared {
+                return Err(SerdeVaultError::LockUpgrade);
+            }
+            return Ok(Self { held: None });
+        }
+        let file = open_lock_file(&lock_path)?;
+        match timeout {
+            None if shared => file.lock_shared()?,
+            None => file.lock()?,
+            Some(timeout) => {
+                let deadline = Instant::now() + timeout;
+                let mut delay = Duration::from_millis(1);
+                loop {
+                    let attempt = if shared {
+                        file.try_lock_shared()
+                    } else {
+                        file.try_lock()
+                    };
+                    match attempt {
+                        Ok(()) => break,
+                        Err(TryLockError::Error(e)) => return Err(e.into()),
+                        Err(TryLockError::WouldBlock) => {}
+                    }
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(SerdeVaultError::WouldBlock);
+                    }
+                    thread::sleep(delay.min(deadline - now));
+                    delay = (delay * 2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+        let key = Held {
+            thread,
+            lock_path,
+            shared,
+        };
+        held().push(key.clone());
+        Ok(Self {
+            held: Some((file, key)),
+        })
+    }
+}
+
+impl Drop for VaultLock {
+    fn drop(&mut self) {
+        if let Some((_, key)) = &self.held {
+            held().retain(|k| k != key);
+        }
+    }
+}
+
+fn held() -> std::sync::MutexGuard<'static, Vec<Held>> {
+    HELD.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The lock file of `vault_path`, in its directory as resolved by the file
+/// system (created if needed), so `dir/../dir/v`, `./v` and a symlinked
+/// directory all name the same file.
+fn canonical_lock_path(vault_path: &Path) -> std::io::Result<PathBuf> {
+    let dir = artifacts::parent_dir(vault_path);
+    std::fs::create_dir_all(dir)?;
+    let lock_path = artifacts::lock_path(vault_path);
+    let name = lock_path.file_name().expect("lock files have a name");
+    Ok(std::fs::canonicalize(dir)?.join(name))
+}
+
+fn open_lock_file(lock_path: &Path) -> std::io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path)
+}
@@ -0,0 +1,458 @@
+//! Append-only encrypted logs.
+//!
+//! A [`VaultLog`] is a file of records that are encrypted one by one, so that
+//! an append writes only the new record instead of re-encrypting everything
+//! before it, and reading walks the file one record at a time:
+//!
+//! ```no_run
+//! use serde::{Deserialize, Serialize};
+//! use serdevault::VaultLog;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Event { user: String, action: String }
+//!
+//! let log = VaultLog::open("~/.audit.log", "master_password");
+//! log.append(&Event { user: "me".into(), action: "login".into() })?;
+//! for event in log.read::<Event>()? {
+//!     let event = event?;
+//!     println!("{}: {}", event.user, event.action);
+//! }
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+//!
+//! Each record is chained to the one before it, so records that were removed
+//! or reordered fail to decrypt; [`VaultLog::verify_chain`] finds the first.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, ErrorKind as IoErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use rand::{rngs::OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::crypto::cipher::{open, seal, NONCE_SIZE, TAG_SIZE};
+use crate::crypto::kdf::{
+    derive_key, ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST, KEY_SIZE, SALT_SIZE,
+};
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{atomic_write, WriteOptions};
+use crate::lock::VaultLock;
+use crate::paths;
+use crate::serializer::Format;
+
+mod chain;
+mod rotation;
+
+pub use rotation::Rotation;
+
+const LOG_MAGIC: &[u8; 4] = b"SVLG";
+const LOG_VERSION: u8 = 1;
+
+/// Layout:
+///   [4]  magic
+///   [1]  version
+///   [32] Argon2 salt
+///   [4]  m_cost, [4] t_cost, [4] p_cost (u32 LE)
+///   [44] key check: the anchor sealed under the key
+///   then, per record:
+///   [4]  len (u32 LE)
+///   [len] nonce || ciphertext of the JSON-encoded record
+///
+/// The key check authenticates the 49 bytes before it as associated data, and
+/// every record those 49 bytes followed by the link before it: the GCM tag of
+/// the previous record, or the anchor for the first one. A new log's anchor is
+/// zeroes; rotation sets it to the tag of the last record rotated out.
+const PARAMS_LEN: usize = 4 + 1 + SALT_SIZE + 12;
+const CHECK_LEN: usize = NONCE_SIZE + TAG_SIZE + TAG_SIZE;
+const HEADER_LEN: usize = PARAMS_LEN + CHECK_LEN;
+
+/// What chains a record to the one before it.
+type Link = [u8; TAG_SIZE];
+
+/// Largest record [`VaultLog::read`] accepts, so that a corrupted length can't
+/// make it allocate without bound.
+const MAX_RECORD: usize = 64 * 1024 * 1024;
+
+/// A handle to an append-only encrypted log.
+///
+/// Like [`VaultFile`](crate::VaultFile), opening performs no I/O; the file is
+/// created by the first [`append`](Self::append). The key is derived once per
+/// handle (and its clones), not once per record.
+#[derive(Clone)]
+pub struct VaultLog {
+    path: PathBuf,
+    password: Zeroizing<String>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    max_size: Option<u64>,
+    max_records: Option<usize>,
+    rotation: Rotation,
+    key: Arc<Mutex<Option<LogKey>>>,
+}
+
+/// A derived key and the header it belongs to.
+#[derive(Clone)]
+struct LogKey {
+    params: [u8; PARAMS_LEN],
+    key: Zeroizing<[u8; KEY_SIZE]>,
+}
+
+impl VaultLog {
+    /// A log at `path` (`~` is expanded) encrypted under `password`.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Self {
+        Self {
+            path: paths::expand(path.as_ref()),
+            password: Zeroizing::new(password.to_owned()),
+            m_cost: ARGON2_M_COST,
+            t_cost: ARGON2_T_COST,
+            p_cost: ARGON2_P_COST,
+            max_size: None,
+            max_records: None,
+            rotation: Rotation::default(),
+            key: Arc::default(),
+        }
+    }
+
+    /// Argon2id parameters for a log this handle creates. An existing log
+    /// keeps the parameters it was created with.
+    pub fn with_params(mut self, m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        self.m_cost = m_cost;
+        self.t_cost = t_cost;
+        self.p_cost = p_cost;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Encrypt `record` and append it, creating the log if needed.
+    ///
+    /// Only the new record is written, and synced to disk before returning,
+    /// unless the log has to [rotate](Self::with_rotation) first. Concurrent
+    /// appends from several processes are serialized by a lock file. Fails
+    /// with `DecryptionFailed` if the log was created under another password.
+    pub fn append<T: Serialize>(&self, record: &T) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(record)?;
+        let _lock = VaultLock::exclusive(&self.path).map_err(self.ctx(Operation::Append))?;
+        let header = match read_header(&self.path) {
+            Ok(header) => header,
+            Err(e) if e.kind() == IoErrorKind::NotFound => {
+                self.create().map_err(self.ctx(Operation::Append))?
+            }
+            Err(e) => return Err(self.read_error(e)),
+        };
+        let key = self.key_for(&header)?;
+
+        let len = frame_len(NONCE_SIZE + plaintext.len() + TAG_SIZE)?;
+        self.make_room(&key, 4 + len as usize)
+            .map_err(self.ctx(Operation::Append))?;
+        let link = self.last_link(&key).map_err(self.ctx(Operation::Append))?;
+        let sealed = seal(&plaintext, &key.key, &key.aad(&link))?;
+        let mut frame = Vec::with_capacity(4 + sealed.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&sealed);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .map_err(self.ctx(Operation::Append))?;
+        file.write_all(&frame)
+            .map_err(self.ctx(Operation::Append))?;
+        file.sync_data().map_err(self.ctx(Operation::Append))
+    }
+
+    /// Iterate over the records in the order they were appended, reading and
+    /// decrypting one at a time.
+    ///
+    /// A record that doesn't parse as `T` yields an error and iteration
+    /// continues; a record cut short (e.g. by a crash during an append) ends
+    /// the iteration with an `InvalidFormat` error.
+    pub fn read<T: DeserializeOwned>(&self) -> Result<LogReader<T>, SerdeVaultError> {
+        let mut file = BufReader::new(File::open(&self.path).map_err(|e| self.read_error(e))?);
+        let mut header = [0u8; HEADER_LEN];
+        file.read_exact(&mut header)
+            .map_err(|e| self.read_error(e))?;
+        let key = self.key_for(&header)?;
+        Ok(LogReader {
+            file,
+            link: key.anchor(&header).map_err(self.ctx(Operation::Load))?,
+            key,
+            index: 0,
+            done: false,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The key of a log with `header`, derived once per handle.
+    fn key_for(&self, header: &[u8; HEADER_LEN]) -> Result<LogKey, SerdeVaultError> {
+        let mut cached = self.key.lock().unwrap_or_else(|e| e.into_inner());
+        match cached.as_ref() {
+            Some(key) if header[..PARAMS_LEN] == key.params => Ok(key.clone()),
+            _ => {
+                let key = self.derive(header).map_err(self.ctx(Operation::Load))?;
+                *cached = Some(key.clone());
+                Ok(key)
+            }
+        }
+    }
+
+    /// Derive the key for `header` and check it against the key check.
+    fn derive(&self, header: &[u8; HEADER_LEN]) -> Result<LogKey, SerdeVaultError> {
+        let (params, check) = header.split_at(PARAMS_LEN);
+        if &params[..4] != LOG_MAGIC {
+            return Err(SerdeVaultError::InvalidFormat(
+                "not a serdevault log".into(),
+            ));
+        }
+        if params[4] != LOG_VERSION {
+            return Err(SerdeVaultError::UnsupportedVersion(params[4]));
+        }
+        let salt: &[u8; SALT_SIZE] = params[5..5 + SALT_SIZE].try_into().expect("salt slice");
+        let cost = |i: usize| {
+            let at = 5 + SALT_SIZE + 4 * i;
+            u32::from_le_bytes(params[at..at + 4].try_into().expect("u32 slice"))
+        };
+        let key = derive_key(&self.password, salt, cost(0), cost(1), cost(2))?;
+        open(check, &key, params)?;
+        Ok(LogKey {
+            params: params.try_into().expect("PARAMS_LEN bytes"),
+            key,
+        })
+    }
+
+    /// Write a new, empty log under a fresh salt and return its header.
+    fn create(&self) -> Result<[u8; HEADER_LEN], SerdeVaultError> {
+        let mut salt = [0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(LOG_MAGIC);
+        header.push(LOG_VERSION);
+        header.extend_from_slice(&salt);
+        for cost in [self.m_cost, self.t_cost, self.p_cost] {
+            header.extend_from_slice(&cost.to_le_bytes());
+        }
+        let key = LogKey {
+            params: header.try_into().expect("PARAMS_LEN bytes"),
+            key: derive_key(&self.password, &salt, self.m_cost, self.t_cost, self.p_cost)?,
+        };
+        let header = key.header(&[0; TAG_SIZE])?;
+        atomic_write(&self.path, &header, &WriteOptions::default())?;
+        Ok(header)
+    }
+
+    fn read_error(&self, e: std::io::Error) -> SerdeVaultError {
+        match e.kind() {
+            IoErrorKind::NotFound => SerdeVaultError::VaultNotFound(self.path.clone()),
+            IoErrorKind::UnexpectedEof => {
+                SerdeVaultError::InvalidFormat("log header truncated".into())
+                    .context(Operation::Load, &self.path)
+            }
+            _ => SerdeVaultError::from(e).context(Operation::Load, &self.path),
+        }
+    }
+
+    fn ctx<E: Into<SerdeVaultError>>(
+        &self,
+        operation: Operation,
+    ) -> impl Fn(E) -> SerdeVaultError + '_ {
+        move |e| e.into().context(operation, &self.path)
+    }
+}
+
+impl LogKey {
+    /// Associated data of the record following `link`.
+    fn aad(&self, link: &Link) -> Vec<u8> {
+        [&self.params[..], link].concat()
+    }
+
+    /// A header for this key starting the chain at `anchor`.
+    fn header(&self, anchor: &Link) -> Result<[u8; HEADER_LEN], SerdeVaultError> {
+        let check = seal(anchor, &self.key, &self.params)?;
+        Ok([&self.params[..], &check]
+            .concat()
+            .try_into()
+            .expect("HEADER_LEN bytes"))
+    }
+
+    /// The anchor sealed in `header`.
+    fn anchor(&self, header: &[u8; HEADER_LEN]) -> Result<Link, SerdeVaultError> {
+        let anchor = open(&header[PARAMS_LEN..], &self.key, &self.params)?;
+        anchor[..]
+            .try_into()
+            .map_err(|_| SerdeVaultError::InvalidFormat("log key check malformed".into()))
+    }
+}
+
+fn read_header(path: &Path) -> std::io::Result<[u8; HEADER_LEN]> {
+    let mut header = [0u8; HEADER_LEN];
+    File::open(path)?.read_exact(&mut header)?;
+    Ok(header)
+}
+
+fn frame_len(len: usize) -> Result<u32, SerdeVaultError> {
+    match u32::try_from(len) {
+        Ok(len) if len as usize <= MAX_RECORD => Ok(len),
+        _ => Err(SerdeVaultError::SerializationError(format!(
+            "log record of {len} bytes exceeds the {MAX_RECORD}-byte limit"
+        ))),
+    }
+}
+
+impl fmt::Debug for VaultLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultLog")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Sequential reader over the records of a log, from [`VaultLog::read`].
+pub struct LogReader<T> {
+    file: BufReader<File>,
+    key: LogKey,
+    link: Link,
+    index: usize,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> LogReader<T> {
+    /// The next sealed record, `None` at a clean end of the log.
+    fn next_frame(&mut self) -> Result<Option<Vec<u8>>, SerdeVaultError> {
+        let mut len = [0u8; 4];
+        match self.file.read(&mut len[..1])? {
+            0 => return Ok(None),
+            _ => self
+                .file
+                .read_exact(&mut len[1..])
+                .map_err(|e| self.truncated(e))?,
+        }
+        let len = u32::from_le_bytes(len) as usize;
+        if len > MAX_RECORD {
+            return Err(SerdeVaultError::InvalidFormat(format!(
+                "log record {} claims {len} bytes",
+                self.index
+            )));
+        }
+        let mut sealed = vec![0u8; len];
+        self.file
+            .read_exact(&mut sealed)
+            .map_err(|e| self.truncated(e))?;
+        Ok(Some(sealed))
+    }
+
+    fn truncated(&self, e: std::io::Error) -> SerdeVaultError {
+        match e.kind() {
+            IoErrorKind::UnexpectedEof => {
+                SerdeVaultError::InvalidFormat(format!("log truncated in record {}", self.index))
+            }
+            _ => e.into(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Iterator for LogReader<T> {
+    type Item = Result<T, SerdeVaultError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let sealed = match self.next_frame() {
+            Ok(Some(sealed)) => sealed,
+            Ok(None) => {
+                self.done = true;
+                return None;
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        self.index += 1;
+        let aad = self.key.aad(&self.link);
+        if let Some(tag) = sealed.last_chunk() {
+            self.link = *tag;
+        }
+        Some(
+            open(&sealed, &self.key.key, &aad)
+                .and_then(|plaintext| Format::Json.deserialize(&plaintext)),
+        )
+    }
+}
+
+impl<T> fmt::Debug for LogReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LogReader")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    pub(crate) struct Event {
+        pub(crate) id: u32,
+    }
+
+    pub(crate) fn log_in(dir: &tempfile::TempDir, password: &str) -> VaultLog {
+        VaultLog::open(dir.path().join("audit.log"), password).with_params(8, 1, 1)
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let dir = tempdir().unwrap();
+        let log = log_in(&dir, "pwd");
+        assert!(matches!(
+            log.read::<Event>(),
+            Err(SerdeVaultError::VaultNotFound(_))
+        ));
+
+        log.append(&Event { id: 1 }).unwrap();
+        let before = std::fs::read(log.path()).unwrap();
+        log.append(&Event { id: 2 }).unwrap();
+        // Appending leaves what was there untouched.
+        let raw = std::fs::read(log.path()).unwrap();
+        assert_eq!(raw[..before.len()], before);
+        assert!(!raw.windows(4).any(|w| w == b"\"id\""));
+
+        let events: Vec<Event> = log.read().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(events, [Event { id: 1 }, Event { id: 2 }]);
+
+        let other = log_in(&dir, "wrong");
+        assert!(matches!(
+            other.append(&Event { id: 3 }),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+        assert!(matches!(
+            other.read::<Event>(),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_truncated_record_ends_reading() {
+        let dir = tempdir().unwrap();
+        let log = log_in(&dir, "pwd");
+        log.append(&Event { id: 1 }).unwrap();
+        log.append(&Event { id: 2 }).unwrap();
+        let raw = std::fs::read(log.path()).unwrap();
+        std::fs::write(log.path(), &raw[..raw.len() - 3]).unwrap();
+
+        let results: Vec<_> = log.read::<Event>().unwrap().collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(*results[0].as_ref().unwrap(), Event { id: 1 });
+        assert!(matches!(&results[1], Err(SerdeVaultError::InvalidFormat(m)) if m.contains('1')));
+    }
+}
@@ -0,0 +1,96 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use serde::de::IgnoredAny;
+
+use crate::crypto::cipher::TAG_SIZE;
+use crate::error::SerdeVaultError;
+
+use super::{read_header, Link, LogKey, VaultLog, HEADER_LEN};
+
+impl VaultLog {
+    /// Check that every record still follows the one it was appended after,
+    /// across the archived [segments](Self::segments) and into the log.
+    ///
+    /// Returns the index of the first record that doesn't, counting from the
+    /// oldest record kept, or `None` if the chain is intact. A record that was
+    /// removed, reordered, altered or cut short breaks the chain where it was;
+    /// records removed from the end of a segment break it at the first record
+    /// after them. Records removed from the very end of the log leave no gap to
+    /// find: compare [`read`](Self::read)'s count with one kept elsewhere for
+    /// that.
+    pub fn verify_chain(&self) -> Result<Option<usize>, SerdeVaultError> {
+        let mut files = self.segments()?;
+        files.push(self.path.clone());
+        let mut index = 0;
+        let mut expected = None;
+        for path in files {
+            let log = VaultLog {
+                path,
+                ..self.clone()
+            };
+            let mut records = log.read::<IgnoredAny>()?;
+            if expected.is_some_and(|link| link != records.link) {
+                return Ok(Some(index));
+            }
+            for record in records.by_ref() {
+                if record.is_err() {
+                    return Ok(Some(index));
+                }
+                index += 1;
+            }
+            expected = Some(records.link);
+        }
+        Ok(None)
+    }
+
+    /// The link the next record appended to the log chains to.
+    pub(super) fn last_link(&self, key: &LogKey) -> Result<Link, SerdeVaultError> {
+        let mut file = File::open(&self.path)?;
+        if file.seek(SeekFrom::End(0))? <= HEADER_LEN as u64 {
+            return key.anchor(&read_header(&self.path)?);
+        }
+        let mut link = [0u8; TAG_SIZE];
+        file.seek(SeekFrom::End(-(TAG_SIZE as i64)))?;
+        file.read_exact(&mut link)?;
+        Ok(link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::tests::{log_in, Event};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_chain_detects_gaps_and_reordering() {
+        let dir = tempdir().unwrap();
+        let log = log_in(&dir, "pwd");
+        for id in 1..=4 {
+            log.append(&Event { id }).unwrap();
+        }
+        assert_eq!(log.verify_chain().unwrap(), None);
+        let raw = std::fs::read(log.path()).unwrap();
+        let (header, records) = raw.split_at(HEADER_LEN);
+        let frames: Vec<_> = records.chunks(records.len() / 4).collect();
+
+        let swapped = [header, frames[0], frames[2], frames[1], frames[3]].concat();
+        std::fs::write(log.path(), swapped).unwrap();
+        assert_eq!(log.verify_chain().unwrap(), Some(1));
+        assert!(log.read::<Event>().unwrap().nth(1).unwrap().is_err());
+
+        let dropped_first = [header, frames[1], frames[2], frames[3]].concat();
+        std::fs::write(log.path(), dropped_first).unwrap();
+        assert_eq!(log.verify_chain().unwrap(), Some(0));
+
+        let archived = log_in(&dir, "pwd").with_max_records(2);
+        std::fs::write(archived.path(), &raw).unwrap();
+        archived.append(&Event { id: 5 }).unwrap();
+        archived.append(&Event { id: 6 }).unwrap();
+        assert_eq!(archived.verify_chain().unwrap(), None);
+        let segment = &archived.segments().unwrap()[0];
+        std::fs::write(segment, [header, frames[0], frames[1], frames[2]].concat()).unwrap();
+        assert_eq!(archived.verify_chain().unwrap(), Some(3));
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::artifacts;
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{atomic_write, WriteOptions};
+use crate::time::utc_stamp;
+
+use crate::crypto::cipher::TAG_SIZE;
+
+use super::{LogKey, VaultLog, HEADER_LEN};
+
+/// What a [`VaultLog`] does when an append would exceed its size or record cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// Move the full log aside as a segment named `<name>.seg-<timestamp>` and
+    /// start an empty one. Only the `keep` most recent segments are kept; 0
+    /// keeps them all. Segments are complete logs, readable with
+    /// [`VaultLog::open`] and the same password.
+    Archive { keep: usize },
+    /// Drop the oldest records, ring-buffer style, until the new one fits.
+    DropOldest,
+}
+
+impl Default for Rotation {
+    fn default() -> Self {
+        Rotation::Archive { keep: 0 }
+    }
+}
+
+impl VaultLog {
+    /// Rotate the log before an append would make the file larger than `bytes`.
+    /// A single record larger than that is still written, alone.
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Rotate the log before an append would make it hold more than `records`
+    /// records. Counting walks the record lengths, so each append reads one
+    /// length per record already in the log.
+    pub fn with_max_records(mut self, records: usize) -> Self {
+        self.max_records = Some(records);
+        self
+    }
+
+    /// How to rotate once a cap is reached. Defaults to archiving every
+    /// segment. Without [`with_max_size`](Self::with_max_size) or
+    /// [`with_max_records`](Self::with_max_records) the log never rotates.
+    pub fn with_rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Archived segments of this log, oldest first.
+    pub fn segments(&self) -> Result<Vec<PathBuf>, SerdeVaultError> {
+        artifacts::segments(&self.path).map_err(self.ctx(Operation::Load))
+    }
+
+    /// Rotate the log if a record of `incoming` bytes (with its length)
+    /// wouldn't fit within the caps. What remains starts its chain where the
+    /// records rotated out ended. Runs under the append lock.
+    pub(super) fn make_room(&self, key: &LogKey, incoming: usize) -> Result<(), SerdeVaultError> {
+        if self.max_size.is_none() && self.max_records.is_none() {
+            return Ok(());
+        }
+        let frames = frame_sizes(&self.path)?;
+        let fits = |frames: &[u64]| {
+            let size = (HEADER_LEN + incoming) as u64 + frames.iter().sum::<u64>();
+            self.max_records.is_none_or(|max| frames.len() < max)
+                && self.max_size.is_none_or(|max| size <= max)
+        };
+        if frames.is_empty() || fits(&frames) {
+            return Ok(());
+        }
+
+        match self.rotation {
+            Rotation::Archive { keep } => {
+                let header = key.header(&self.last_link(key)?)?;
+                let segment = artifacts::segment_path(&self.path, &utc_stamp(SystemTime::now()));
+                fs::rename(&self.path, &segment)?;
+                atomic_write(&self.path, &header, &WriteOptions::default())?;
+                let segments = artifacts::segments(&self.path)?;
+                if keep > 0 && segments.len() > keep {
+                    for old in &segments[..segments.len() - keep] {
+                        fs::remove_file(old)?;
+                    }
+                }
+            }
+            Rotation::DropOldest => {
+                let mut dropped = 0;
+                while dropped < frames.len() && !fits(&frames[dropped..]) {
+                    dropped += 1;
+                }
+                let skip = HEADER_LEN + frames[..dropped].iter().sum::<u64>() as usize;
+                let raw = fs::read(&self.path)?;
+                let anchor = raw[skip - TAG_SIZE..skip]
+                    .try_into()
+                    .expect("TAG_SIZE bytes");
+                let mut kept = key.header(&anchor)?.to_vec();
+                kept.extend_from_slice(&raw[skip..]);
+                atomic_write(&self.path, &kept, &WriteOptions::default())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size of every record in the log at `path`, length prefix included.
+fn frame_sizes(path: &Path) -> Result<Vec<u64>, SerdeVaultError> {
+    let mut file = BufReader::new(fs::File::open(path)?);
+    let end = file.seek(SeekFrom::End(0))?;
+    let mut pos = file.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+    let mut sizes = Vec::new();
+    let mut len = [0u8; 4];
+    while pos + 4 <= end {
+        file.read_exact(&mut len)?;
+        let size = 4 + u64::from(u32::from_le_bytes(len));
+        if pos + size > end {
+            break;
+        }
+        file.seek_relative(size as i64 - 4)?;
+        pos += size;
+        sizes.push(size);
+    }
+    Ok(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::tests::{log_in, Event};
+    use tempfile::tempdir;
+
+    fn ids(log: &VaultLog) -> Vec<u32> {
+        log.read::<Event>()
+            .unwrap()
+            .map(|e| e.unwrap().id)
+            .collect()
+    }
+
+    #[test]
+    fn test_archive_rotation() {
+        let dir = tempdir().unwrap();
+        let log = log_in(&dir, "pwd")
+            .with_max_records(2)
+            .with_rotation(Rotation::Archive { keep: 1 });
+        for id in 1..=5 {
+            log.append(&Event { id }).unwrap();
+        }
+        assert_eq!(ids(&log), [5]);
+        let segments = log.segments().unwrap();
+        assert_eq!(segments.len(), 1);
+        let segment = VaultLog::open(&segments[0], "pwd").with_params(8, 1, 1);
+        assert_eq!(ids(&segment), [3, 4]);
+    }
+
+    #[test]
+    fn test_drop_oldest_rotation() {
+        let dir = tempdir().unwrap();
+        let log = log_in(&dir, "pwd")
+            .with_max_records(3)
+            .with_rotation(Rotation::DropOldest);
+        for id in 1..=5 {
+            log.append(&Event { id }).unwrap();
+        }
+        assert_eq!(ids(&log), [3, 4, 5]);
+        assert_eq!(log.verify_chain().unwrap(), None);
+
+        let record = std::fs::metadata(log.path()).unwrap().len() - HEADER_LEN as u64;
+        let capped = log
+            .with_max_records(100)
+            .with_max_size(HEADER_LEN as u64 + record / 3 * 2);
+        capped.append(&Event { id: 6 }).unwrap();
+        assert_eq!(ids(&capped), [5, 6]);
+        assert_eq!(capped.verify_chain().unwrap(), None);
+        assert!(capped.segments().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! Schema versioning for stored structs.
+//!
+//! Register one upgrade function per schema change and attach them to a vault
+//! with [`VaultFile::with_migrations`](crate::VaultFile::with_migrations):
+//!
+//! ```no_run
+//! use serde::{Deserialize, Serialize};
+//! use serdevault::{migration::Migrations, VaultFile};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct ConfigV1 { url: String }
+//! #[derive(Serialize, Deserialize)]
+//! struct ConfigV2 { server_url: String }
+//! #[derive(Serialize, Deserialize)]
+//! struct ConfigV3 { server_url: String, timeout_secs: u64 }
+//!
+//! let migrations = Migrations::new()
+//!     .step(|v1: ConfigV1| ConfigV2 { server_url: v1.url })
+//!     .step(|v2: ConfigV2| ConfigV3 { server_url: v2.server_url, timeout_secs: 30 });
+//!
+//! let vault = VaultFile::open("~/.my.vault", "my_password").with_migrations(migrations);
+//! let config: ConfigV3 = vault.load()?; // upgrades a v1 or v2 vault on the fly
+//! vault.save(&config)?; // stored as schema version 3
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+
+use std::fmt;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{ErrorKind, SerdeVaultError};
+use crate::serializer::Format;
+use crate::vault::VaultFile;
+
+/// Key of the schema version in the payload envelope.
+const VERSION_KEY: &str = "serdevault_schema";
+/// Key of the stored value in the payload envelope.
+const DATA_KEY: &str = "data";
+
+type Step = Arc<dyn Fn(Value) -> Result<Value, SerdeVaultError> + Send + Sync>;
+
+/// An ordered chain of upgrade functions between schema versions.
+///
+/// Versions are numbered from 1. The first step upgrades version 1 to 2, the
+/// second 2 to 3, and so on; the current version is one more than the number of
+/// steps. Payloads written without migrations count as version 1.
+#[derive(Clone, Default)]
+pub struct Migrations {
+    steps: Vec<Step>,
+}
+
+impl Migrations {
+    /// An empty chain: the current schema is version 1.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the upgrade from the current latest version to the next one.
+    pub fn step<A, B, F>(mut self, upgrade: F) -> Self
+    where
+        A: DeserializeOwned,
+        B: Serialize,
+        F: Fn(A) -> B + Send + Sync + 'static,
+    {
+        let from = self.current_version();
+        self.steps.push(Arc::new(move |value| {
+            let old: A = serde_json::from_value(value).map_err(|e| {
+                SerdeVaultError::MigrationError(format!("cannot read schema version {from}: {e}"))
+            })?;
+            serde_json::to_value(upgrade(old))
+                .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))
+        }));
+        self
+    }
+
+    /// The schema version that `save` writes.
+    pub fn current_version(&self) -> u32 {
+        self.steps.len() as u32 + 1
+    }
+
+    /// Wrap `data` in an envelope recording the current schema version.
+    pub(crate) fn wrap<'a, T: Serialize>(&self, data: &'a T) -> impl Serialize + 'a {
+        #[derive(Serialize)]
+        struct Envelope<'a, T> {
+            serdevault_schema: u32,
+            data: &'a T,
+        }
+        Envelope {
+            serdevault_schema: self.current_version(),
+            data,
+        }
+    }
+
+    /// Unwrap a stored payload and run it through the steps it is missing.
+    pub(crate) fn upgrade(&self, payload: Value) -> Result<Value, SerdeVaultError> {
+        let (version, mut value) = unwrap_envelope(payload);
+        let current = self.current_version();
+        if version == 0 || version > current {
+            return Err(SerdeVaultError::MigrationError(format!(
+                "vault holds schema version {version}, this build knows versions 1 to {current}"
+            )));
+        }
+        for step in &self.steps[version as usize - 1..] {
+            value = step(value)?;
+        }
+        Ok(value)
+    }
+}
+
+/// A newest-first tuple of representations a stored value may have, for
+/// [`VaultFile::load_any`]: `(V3, V2, V1)` tries `V3`, then `V2`, then `V1`,
+/// converting older ones with `From`.
+///
+/// Implemented for tuples of two to four types.
+pub trait Representations {
+    /// The newest representation, which `load_any` returns.
+    type Output;
+
+    #[doc(hidden)]
+    fn decode(
+        vault: &VaultFile,
+        format: Format,
+        plaintext: &[u8],
+    ) -> Result<Self::Output, SerdeVaultError>;
+}
+
+macro_rules! impl_representations {
+    ($newest:ident $(, $older:ident)+) => {
+        impl<$newest, $($older),+> Representations for ($newest, $($older),+)
+        where
+            $newest: DeserializeOwned $(+ From<$older>)+,
+            $($older: DeserializeOwned,)+
+        {
+            type Output = $newest;
+
+            fn decode(
+                vault: &VaultFile,
+                format: Format,
+                plaintext: &[u8],
+            ) -> Result<$newest, SerdeVaultError> {
+                let err = match vault.decode_payload::<$newest>(format, plaintext) {
+                    Ok(value) => return Ok(value),
+                    Err(e) if e.kind() == ErrorKind::Deserialization => e,
+                    Err(e) => return Err(e),
+                };
+                $(
+                    if let Ok(value) = vault.decode_payload::<$older>(format, plaintext) {
+                        return Ok(value.into());
+                    }
+                )+
+                Err(err)
+            }
+        }
+    };
+}
+
+impl_representations!(A, B);
+impl_representations!(A, B, C);
+impl_representations!(A, B, C, D);
+
+impl fmt::Debug for Migrations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Migrations")
+            .field("current_version", &self.current_version())
+            .finish()
+    }
+}
+
+/// Split an envelope into `(version, data)`; anything else is a version 1 payload.
+fn unwrap_envelope(payload: Value) -> (u32, Value) {
+    match payload {
+        Value::Object(mut map)
+            if map.len() == 2 && map.contains_key(DATA_KEY) && map.contains_key(VERSION_KEY) =>
+        {
+            let version = map[VERSION_KEY]
+                .as_u64()
+                .map_or(0, |v| v.try_into().unwrap_or(0));
+            let data = map.remove(DATA_KEY).unwrap_or_default();
+            (version, data)
+        }
+        other => (1, other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize)]
+    struct V1 {
+        url: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct V2 {
+        server_url: String,
+    }
+
+    fn chain() -> Migrations {
+        Migrations::new()
+            .step(|v1: V1| V2 { server_url: v1.url })
+            .step(|v2: V2| json!({ "server_url": v2.server_url, "retries": 3 }))
+    }
+
+    #[test]
+    fn test_upgrade_from_each_version() {
+        let expected = json!({ "server_url": "https://a", "retries": 3 });
+        let m = chain();
+        assert_eq!(m.current_version(), 3);
+
+        assert_eq!(m.upgrade(json!({ "url": "https://a" })).unwrap(), expected);
+        assert_eq!(
+            m.upgrade(json!({ "serdevault_schema": 2, "data": { "server_url": "https://a" } }))
+                .unwrap(),
+            expected
+        );
+        let current = serde_json::to_value(m.wrap(&expected)).unwrap();
+        assert_eq!(current["serdevault_schema"], 3);
+        assert_eq!(m.upgrade(current).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_upgrade_rejects_unknown_versions() {
+        let m = chain();
+        for version in [0, 4] {
+            let err = m
+                .upgrade(json!({ "serdevault_schema": version, "data": {} }))
+                .unwrap_err();
+            assert!(matches!(err, SerdeVaultError::MigrationError(_)));
+        }
+
+        let err = m.upgrade(json!({ "something": "else" })).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::MigrationError(m) if m.contains("version 1")));
+    }
+}
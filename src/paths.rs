@@ -0,0 +1,270 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that relocates relative and `~/` vault paths.
+pub const ROOT_ENV: &str = "SERDEVAULT_DIR";
+
+/// Expand the user-facing shorthands in a vault path:
+///
+/// - `${VAR}` is replaced with the environment variable `VAR`;
+/// - on Windows, so is `%VAR%`;
+/// - a leading `~/` becomes the user's home directory.
+///
+/// References to unset variables (and `~/` without `HOME`) are left as written.
+/// Bare `$VAR` is not expanded, since `$` is a legal file name character.
+///
+/// If [`ROOT_ENV`] is set, relative and `~/` paths resolve inside it instead.
+pub fn expand(path: &Path) -> PathBuf {
+    let root = env::var_os(ROOT_ENV)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from);
+    expand_in(path, root.as_deref())
+}
+
+/// [`expand`] with an explicit root for relative and `~/` paths (`None`: the
+/// working directory and home directory, as usual).
+pub fn expand_in(path: &Path, root: Option<&Path>) -> PathBuf {
+    let path = match path.to_str() {
+        Some(s) => PathBuf::from(expand_vars(s, cfg!(windows), |name| env::var(name).ok())),
+        None => path.to_path_buf(),
+    };
+    match root {
+        Some(root) => relocate(&path, root),
+        None => expand_tilde(&path),
+    }
+}
+
+fn relocate(path: &Path, root: &Path) -> PathBuf {
+    if let Some(rest) = tilde_rest(&path.to_string_lossy()) {
+        return root.join(rest);
+    }
+    if path.is_relative() {
+        root.join(path)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The conventional per-user config directory for an application:
+///
+/// - Linux and other Unix: `$XDG_CONFIG_HOME/<app>` (default `~/.config/<app>`),
+///   with `app` lowercased and spaces removed;
+/// - macOS: `~/Library/Application Support/<qualifier>.<org>.<app>`;
+/// - Windows: `%APPDATA%\<org>\<app>\config`.
+///
+/// `None` if the home directory (or `APPDATA`) isn't set.
+pub fn app_config_dir(qualifier: &str, organization: &str, application: &str) -> Option<PathBuf> {
+    let lookup = |name: &str| {
+        env::var_os(name)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+    };
+    if cfg!(windows) {
+        windows_config_dir(organization, application, lookup)
+    } else if cfg!(target_os = "macos") {
+        macos_config_dir(qualifier, organization, application, lookup)
+    } else {
+        xdg_config_dir(application, lookup)
+    }
+}
+
+fn xdg_config_dir(application: &str, lookup: impl Fn(&str) -> Option<PathBuf>) -> Option<PathBuf> {
+    let base = lookup("XDG_CONFIG_HOME")
+        .filter(|p| p.is_absolute())
+        .or_else(|| Some(lookup("HOME")?.join(".config")))?;
+    Some(base.join(application.to_lowercase().replace(' ', "")))
+}
+
+fn macos_config_dir(
+    qualifier: &str,
+    organization: &str,
+    application: &str,
+    lookup: impl Fn(&str) -> Option<PathBuf>,
+) -> Option<PathBuf> {
+    let bundle_id = [qualifier, organization, application]
+        .iter()
+        .filter(|part| !part.is_empty())
+        .map(|part| part.replace(' ', "-"))
+        .collect::<Vec<_>>()
+        .join(".");
+    Some(
+        lookup("HOME")?
+            .join("Library/Application Support")
+            .join(bundle_id),
+    )
+}
+
+fn windows_config_dir(
+    organization: &str,
+    application: &str,
+    lookup: impl Fn(&str) -> Option<PathBuf>,
+) -> Option<PathBuf> {
+    Some(
+        lookup("APPDATA")?
+            .join(organization)
+            .join(application)
+            .join("config"),
+    )
+}
+
+/// Expand a leading `~/` to the user's home directory (`HOME`, or `USERPROFILE`
+/// on Windows, where `~\` works too). Falls back to the literal path if neither is set.
+fn expand_tilde(path: &Path) -> PathBuf {
+    if let Some(rest) = tilde_rest(&path.to_string_lossy()) {
+        if let Some(home) = home_dir() {
+            return home.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// What follows a leading `~/` (or `~\` on Windows).
+fn tilde_rest(s: &str) -> Option<&str> {
+    s.strip_prefix("~/")
+        .or_else(|| s.strip_prefix("~\\").filter(|_| cfg!(windows)))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE").filter(|_| cfg!(windows)))
+        .map(PathBuf::from)
+}
+
+fn expand_vars(s: &str, percent: bool, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(i) = rest.find(|c| c == '$' || (percent && c == '%')) {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        let (open, close) = if rest.starts_with('%') {
+            ("%", '%')
+        } else {
+            ("${", '}')
+        };
+        let reference = rest.strip_prefix(open).and_then(|after| {
+            let end = after.find(close)?;
+            let name = &after[..end];
+            let value = is_var_name(name).then(|| lookup(name)).flatten()?;
+            Some((value, open.len() + end + 1))
+        });
+
+        match reference {
+            Some((value, len)) => {
+                out.push_str(&value);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Letters, digits, `_`, and the parentheses of Windows' `ProgramFiles(x86)`.
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '(' | ')'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "XDG_CONFIG_HOME" => Some("/home/me/.config".into()),
+            "APPDATA" => Some(r"C:\Users\me\AppData\Roaming".into()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_vars() {
+        assert_eq!(
+            expand_vars("${XDG_CONFIG_HOME}/myapp/vault.svlt", false, lookup),
+            "/home/me/.config/myapp/vault.svlt"
+        );
+        assert_eq!(
+            expand_vars(r"%APPDATA%\myapp\vault.svlt", true, lookup),
+            r"C:\Users\me\AppData\Roaming\myapp\vault.svlt"
+        );
+    }
+
+    #[test]
+    fn test_expand_in_root() {
+        let root = Path::new("/srv/vaults");
+        assert_eq!(
+            expand_in(Path::new("~/.app.vault"), Some(root)),
+            root.join(".app.vault")
+        );
+        assert_eq!(
+            expand_in(Path::new("data/app.vault"), Some(root)),
+            root.join("data/app.vault")
+        );
+        assert_eq!(
+            expand_in(Path::new("/etc/app.vault"), Some(root)),
+            PathBuf::from("/etc/app.vault")
+        );
+        assert_eq!(
+            expand_in(Path::new("app.vault"), None),
+            PathBuf::from("app.vault")
+        );
+    }
+
+    #[test]
+    fn test_app_config_dirs() {
+        let env = |name: &str| match name {
+            "HOME" => Some(PathBuf::from("/home/me")),
+            "APPDATA" => Some(PathBuf::from("/appdata")),
+            _ => None,
+        };
+        assert_eq!(
+            xdg_config_dir("My App", env),
+            Some(PathBuf::from("/home/me/.config/myapp"))
+        );
+        assert_eq!(
+            macos_config_dir("com", "Acme Corp", "My App", env),
+            Some(PathBuf::from(
+                "/home/me/Library/Application Support/com.Acme-Corp.My-App"
+            ))
+        );
+        assert_eq!(
+            windows_config_dir("Acme", "MyApp", env),
+            Some(PathBuf::from("/appdata/Acme/MyApp/config"))
+        );
+
+        let xdg = |name: &str| match name {
+            "XDG_CONFIG_HOME" => Some(PathBuf::from("/xdg")),
+            _ => None,
+        };
+        assert_eq!(
+            xdg_config_dir("myapp", xdg),
+            Some(PathBuf::from("/xdg/myapp"))
+        );
+        assert_eq!(xdg_config_dir("myapp", |_| None), None);
+    }
+
+    #[test]
+    fn test_expand_vars_leaves_other_text_alone() {
+        for s in [
+            "${UNSET}/vault.svlt",
+            "$XDG_CONFIG_HOME/vault.svlt",
+            "${XDG_CONFIG_HOME/vault.svlt",
+            "${}/a$b",
+            "100%/%APPDATA%",
+        ] {
+            assert_eq!(expand_vars(s, false, lookup), s);
+        }
+        assert_eq!(
+            expand_vars("50% off/%UNSET%", true, lookup),
+            "50% off/%UNSET%"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::io;
+use std::path::Path;
+
+use crate::error::SerdeVaultError;
+
+/// Mode given to every vault file we create: owner read/write, nothing else.
+#[cfg(unix)]
+pub const VAULT_MODE: u32 = 0o600;
+
+/// Restrict a file to its owner, regardless of the process umask.
+#[cfg(unix)]
+pub fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(VAULT_MODE))
+}
+
+/// Restrict a file to the current user by replacing its DACL.
+///
+/// The new DACL is protected (nothing inherited from the parent directory) and
+/// holds a single ACE granting full access to the user the process runs as —
+/// the Windows equivalent of mode `0600`. The user is named by SID rather than
+/// as `OWNER RIGHTS`, which would leave the owner free to grant itself more and
+/// follow ownership to whoever takes it.
+#[cfg(windows)]
+pub fn restrict(path: &Path) -> io::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::{null, null_mut};
+
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertStringSecurityDescriptorToSecurityDescriptorW, SetNamedSecurityInfoW,
+        SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{
+        GetSecurityDescriptorDacl, ACL, DACL_SECURITY_INFORMATION,
+        PROTECTED_DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR,
+    };
+
+    // P = protected, A;;FA;;;<sid> = allow FILE_ALL_ACCESS to the current user.
+    let sddl = format!("D:P(A;;FA;;;{})", current_user_sid()?);
+    let sddl: Vec<u16> = sddl.encode_utf16().chain(Some(0)).collect();
+    let mut name: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+
+    // SAFETY: all strings are NUL-terminated and outlive the calls; `sd` is
+    // allocated by the conversion function and released with LocalFree, and
+    // `dacl` points into it, so it is only used before that.
+    unsafe {
+        let mut sd: PSECURITY_DESCRIPTOR = null_mut();
+        if ConvertStringSecurityDescriptorToSecurityDescriptorW(
+            sddl.as_ptr(),
+            SDDL_REVISION_1,
+            &mut sd,
+            null_mut(),
+        ) == 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut present = 0;
+        let mut defaulted = 0;
+        let mut dacl: *mut ACL = null_mut();
+        let result = if GetSecurityDescriptorDacl(sd, &mut present, &mut dacl, &mut defaulted) == 0
+        {
+            Err(io::Error::last_os_error())
+        } else {
+            let status = SetNamedSecurityInfoW(
+                name.as_mut_ptr(),
+                SE_FILE_OBJECT,
+                DACL_SECURITY_INFORMATION | PROTECTED_DACL_SECURITY_INFORMATION,
+                null_mut(),
+                null_mut(),
+                dacl,
+                null(),
+            );
+            if status == ERROR_SUCCESS {
+                Ok(())
+            } else {
+                Err(io::Error::from_raw_os_error(status as i32))
+            }
+        };
+
+        LocalFree(sd as _);
+        result
+    }
+}
+
+/// The SID of the user the process runs as, in string form (`S-1-5-21-…`),
+/// read from the process token.
+#[cfg(windows)]
+fn current_user_sid() -> io::Result<String> {
+    use std::ptr::null_mut;
+
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, HANDLE};
+    use windows_sys::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows_sys::Win32::Security::{GetTokenInformation, TokenUser, TOKEN_QUERY, TOKEN_USER};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    // SAFETY: the token handle is closed on every path once opened; `buf` is
+    // sized by the first GetTokenInformation call and u64-aligned, which covers
+    // TOKEN_USER, and the SID it points to lives in `buf`, which outlives its
+    // use; the string SID is allocated by ConvertSidToStringSidW, NUL-terminated,
+    // and released with LocalFree after being copied.
+    unsafe {
+        let mut token: HANDLE = null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut len = 0u32;
+        GetTokenInformation(token, TokenUser, null_mut(), 0, &mut len);
+        let mut buf = vec![0u64; (len as usize).div_ceil(8)];
+        let queried = GetTokenInformation(token, TokenUser, buf.as_mut_ptr().cast(), len, &mut len);
+        let queried = if queried == 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        CloseHandle(token);
+        queried?;
+
+        let user = &*buf.as_ptr().cast::<TOKEN_USER>();
+        let mut string_sid = null_mut();
+        if ConvertSidToStringSidW(user.User.Sid, &mut string_sid) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = (0..).take_while(|&i| *string_sid.add(i) != 0).count();
+        let sid = String::from_utf16_lossy(std::slice::from_raw_parts(string_sid, len));
+        LocalFree(string_sid as _);
+        Ok(sid)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn restrict(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Fail with `InsecurePermissions` if the file is readable or writable by
+/// anyone other than its owner.
+#[cfg(unix)]
+pub fn ensure_private(path: &Path) -> Result<(), SerdeVaultError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)?.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(SerdeVaultError::InsecurePermissions(mode));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn ensure_private(_path: &Path) -> Result<(), SerdeVaultError> {
+    Ok(())
+}
@@ -0,0 +1,373 @@
+//! Encrypted save games in slots.
+//!
+//! A [`SaveManager`] keeps one vault per save slot in a directory, with the
+//! metadata a load screen needs next to each save:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use serde::{Deserialize, Serialize};
+//! use serdevault::save::{SaveInfo, SaveManager};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct World { level: u32, gold: u64 }
+//!
+//! let saves = SaveManager::open("~/.mygame/saves", "per-install secret");
+//! let info = SaveInfo::new(Duration::from_secs(3600));
+//! saves.save(1, &info, &World { level: 3, gold: 120 })?;
+//! saves.save("autosave", &info, &World { level: 3, gold: 125 })?;
+//!
+//! for (slot, info) in saves.list()? {
+//!     match info {
+//!         Ok(info) => println!("{slot}: {:?} played", info.playtime),
+//!         Err(_) => println!("{slot}: unreadable"),
+//!     }
+//! }
+//! let world = saves.load::<World>(1)?.data;
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+//!
+//! Each slot is a separate file, so a damaged save never affects the others,
+//! and the previous version of each save is kept as a backup to fall back on.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::kdf::{ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST};
+use crate::error::{ErrorKind, Operation, SerdeVaultError};
+use crate::paths;
+use crate::vault::VaultFile;
+
+const EXTENSION: &str = "sav";
+
+/// A save slot: numbered (`3.sav`) or named (`autosave.sav`).
+///
+/// Names start with a letter and contain only ASCII letters, digits, `_` and
+/// `-`, so that they can't collide with numbers or leave the save directory.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SaveSlot {
+    Numbered(u32),
+    Named(String),
+}
+
+/// What a load screen shows about a save.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SaveInfo {
+    /// Set by [`SaveManager::save`] to the time of the save.
+    pub saved_at: SystemTime,
+    pub playtime: Duration,
+    /// Encoded image bytes (PNG, …), empty if there is none.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", with = "crate::bytes")]
+    pub screenshot: Vec<u8>,
+}
+
+/// A slot's info in [`SaveManager::list`], or why it can't be read.
+pub type SlotInfo = Result<SaveInfo, SerdeVaultError>;
+
+/// A save read back by [`SaveManager::load`].
+#[derive(Debug)]
+pub struct LoadedSave<T> {
+    pub data: T,
+    pub info: SaveInfo,
+    /// The backup the save was recovered from, if the slot itself couldn't be
+    /// read. Saving to the slot again repairs it.
+    pub recovered_from: Option<PathBuf>,
+}
+
+/// A directory of encrypted save slots, all under one password.
+#[derive(Clone)]
+pub struct SaveManager {
+    dir: PathBuf,
+    password: Zeroizing<String>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    backups: usize,
+}
+
+/// What a slot's vault holds.
+#[derive(Serialize, Deserialize)]
+struct SaveFile<T> {
+    info: SaveInfo,
+    data: T,
+}
+
+impl SaveInfo {
+    pub fn new(playtime: Duration) -> Self {
+        Self {
+            saved_at: SystemTime::now(),
+            playtime,
+            screenshot: Vec::new(),
+        }
+    }
+
+    pub fn with_screenshot(mut self, screenshot: Vec<u8>) -> Self {
+        self.screenshot = screenshot;
+        self
+    }
+}
+
+impl SaveManager {
+    /// Saves in `dir` (`~` is expanded), which is created by the first save.
+    /// No I/O is performed.
+    pub fn open(dir: impl AsRef<Path>, password: &str) -> Self {
+        Self {
+            dir: paths::expand(dir.as_ref()),
+            password: Zeroizing::new(password.to_owned()),
+            m_cost: ARGON2_M_COST,
+            t_cost: ARGON2_T_COST,
+            p_cost: ARGON2_P_COST,
+            backups: 1,
+        }
+    }
+
+    /// See [`VaultFile::with_params`]. Every load and save derives a key, so
+    /// games that save often may want cheaper parameters than the default.
+    pub fn with_params(mut self, m_cost: u32, t_cost: u32, p_cost: u32) -> Self {
+        self.m_cost = m_cost;
+        self.t_cost = t_cost;
+        self.p_cost = p_cost;
+        self
+    }
+
+    /// How many previous versions of each save to keep to recover from
+    /// (1 by default, 0 disables recovery).
+    pub fn with_backups(mut self, keep: usize) -> Self {
+        self.backups = keep;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save `data` to `slot`, replacing what was there, with `info` stamped
+    /// with the current time.
+    pub fn save<T: Serialize>(
+        &self,
+        slot: impl Into<SaveSlot>,
+        info: &SaveInfo,
+        data: &T,
+    ) -> Result<(), SerdeVaultError> {
+        let vault = self.vault(&slot.into())?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| SerdeVaultError::from(e).context(Operation::Save, &self.dir))?;
+        let info = SaveInfo {
+            saved_at: SystemTime::now(),
+            ..info.clone()
+        };
+        vault.save(&SaveFile { info, data })
+    }
+
+    /// Load the save in `slot`. If the slot is damaged (or was tampered with),
+    /// its newest readable backup is returned instead; if none is, the slot's
+    /// own error.
+    pub fn load<T: DeserializeOwned>(
+        &self,
+        slot: impl Into<SaveSlot>,
+    ) -> Result<LoadedSave<T>, SerdeVaultError> {
+        let (file, recovered_from) = self.read::<T>(&slot.into())?;
+        Ok(LoadedSave {
+            data: file.data,
+            info: file.info,
+            recovered_from,
+        })
+    }
+
+    /// Every slot with its info, numbered slots first. A slot that can't be
+    /// read, even from a backup, is listed with its error.
+    pub fn list(&self) -> Result<Vec<(SaveSlot, SlotInfo)>, SerdeVaultError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(SerdeVaultError::from(e).context(Operation::Load, &self.dir)),
+        };
+        let mut slots = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                if let Some(slot) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(parse_slot)
+                {
+                    slots.push(slot);
+                }
+            }
+        }
+        slots.sort();
+        Ok(slots
+            .into_iter()
+            .map(|slot| {
+                let info = self.read::<IgnoredAny>(&slot).map(|(file, _)| file.info);
+                (slot, info)
+            })
+            .collect())
+    }
+
+    pub fn exists(&self, slot: impl Into<SaveSlot>) -> Result<bool, SerdeVaultError> {
+        Ok(self.vault(&slot.into())?.exists())
+    }
+
+    /// Delete the save in `slot` with its backups. Returns whether there was
+    /// one.
+    pub fn delete(&self, slot: impl Into<SaveSlot>) -> Result<bool, SerdeVaultError> {
+        let vault = self.vault(&slot.into())?;
+        let existed = vault.exists();
+        vault.delete()?;
+        Ok(existed)
+    }
+
+    /// The slot's save, or its newest backup that can be read.
+    fn read<T: DeserializeOwned>(
+        &self,
+        slot: &SaveSlot,
+    ) -> Result<(SaveFile<T>, Option<PathBuf>), SerdeVaultError> {
+        let vault = self.vault(slot)?;
+        let error = match vault.load() {
+            Ok(file) => return Ok((file, None)),
+            Err(e) if is_damage(&e) => e,
+            Err(e) => return Err(e),
+        };
+        for backup in vault.backups()?.into_iter().rev() {
+            if let Ok(file) = self.vault_at(&backup).load() {
+                return Ok((file, Some(backup)));
+            }
+        }
+        Err(error)
+    }
+
+    fn vault(&self, slot: &SaveSlot) -> Result<VaultFile, SerdeVaultError> {
+        let name = match slot {
+            SaveSlot::Numbered(n) => n.to_string(),
+            SaveSlot::Named(name) if parse_slot(name).as_ref() == Some(slot) => name.clone(),
+            SaveSlot::Named(name) => {
+                return Err(SerdeVaultError::InvalidConfig(format!(
+                    "invalid save slot name {name:?}"
+                )))
+            }
+        };
+        let path = self.dir.join(format!("{name}.{EXTENSION}"));
+        Ok(self.vault_at(&path).with_backups(self.backups))
+    }
+
+    fn vault_at(&self, path: &Path) -> VaultFile {
+        VaultFile::open(path, &self.password).with_params(self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+/// Whether `error` means the file is there but damaged, rather than missing or
+/// inaccessible.
+fn is_damage(error: &SerdeVaultError) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::DecryptionFailed
+            | ErrorKind::InvalidFormat
+            | ErrorKind::Deserialization
+            | ErrorKind::UnsupportedVersion
+            | ErrorKind::UnsupportedFormat
+    )
+}
+
+/// The slot stored as `<stem>.sav`, if `stem` is a valid slot.
+fn parse_slot(stem: &str) -> Option<SaveSlot> {
+    if !stem.is_empty() && stem.bytes().all(|b| b.is_ascii_digit()) {
+        return stem.parse().ok().map(SaveSlot::Numbered);
+    }
+    let mut chars = stem.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    valid.then(|| SaveSlot::Named(stem.to_string()))
+}
+
+impl From<u32> for SaveSlot {
+    fn from(n: u32) -> Self {
+        SaveSlot::Numbered(n)
+    }
+}
+
+impl From<&str> for SaveSlot {
+    fn from(name: &str) -> Self {
+        SaveSlot::Named(name.to_string())
+    }
+}
+
+impl From<String> for SaveSlot {
+    fn from(name: String) -> Self {
+        SaveSlot::Named(name)
+    }
+}
+
+impl fmt::Display for SaveSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveSlot::Numbered(n) => write!(f, "slot {n}"),
+            SaveSlot::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+impl fmt::Debug for SaveManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SaveManager")
+            .field("dir", &self.dir)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_slots_and_recovery() {
+        let dir = tempdir().unwrap();
+        let saves = SaveManager::open(dir.path().join("saves"), "pwd").with_params(8, 1, 1);
+        assert!(saves.list().unwrap().is_empty());
+
+        let info = SaveInfo::new(Duration::from_secs(90)).with_screenshot(vec![0x89, b'P']);
+        saves.save(2, &info, &"level 1").unwrap();
+        saves.save(2, &info, &"level 2").unwrap();
+        saves
+            .save("autosave", &SaveInfo::new(Duration::ZERO), &"level 3")
+            .unwrap();
+        assert!(saves.save("../escape", &info, &"x").is_err());
+
+        let listed = saves.list().unwrap();
+        let slots: Vec<_> = listed.iter().map(|(slot, _)| slot.clone()).collect();
+        assert_eq!(
+            slots,
+            [SaveSlot::Numbered(2), SaveSlot::Named("autosave".into())]
+        );
+        let first = listed[0].1.as_ref().unwrap();
+        assert_eq!(
+            (first.playtime, &first.screenshot[..]),
+            (info.playtime, &[0x89, b'P'][..])
+        );
+
+        let loaded = saves.load::<String>(2).unwrap();
+        assert_eq!(
+            (loaded.data.as_str(), loaded.recovered_from),
+            ("level 2", None)
+        );
+
+        // A damaged slot falls back to the save before it.
+        let path = dir.path().join("saves/2.sav");
+        let mut raw = fs::read(&path).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        fs::write(&path, raw).unwrap();
+        let loaded = saves.load::<String>(2).unwrap();
+        assert_eq!(loaded.data, "level 1");
+        assert!(loaded.recovered_from.is_some());
+
+        assert!(saves.delete("autosave").unwrap());
+        assert!(!saves.exists("autosave").unwrap());
+    }
+}
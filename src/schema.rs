@@ -0,0 +1,214 @@
+//! Several independently loadable sections in one vault file.
+//!
+//! [`vault_schema!`](crate::vault_schema) declares a wrapper around a
+//! [`VaultFile`] with one accessor per section. The vault stores a JSON object
+//! with a key per section, so each can be read and written without touching
+//! (or even knowing the types of) the others:
+//!
+//! ```no_run
+//! use serde::{Deserialize, Serialize};
+//! use serdevault::{vault_schema, VaultFile};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Credentials { token: String }
+//! #[derive(Serialize, Deserialize, Default)]
+//! struct Settings { theme: String }
+//!
+//! vault_schema! {
+//!     /// Everything the app keeps encrypted.
+//!     pub struct AppVault {
+//!         credentials: Credentials,
+//!         settings: Settings,
+//!     }
+//! }
+//!
+//! let vault = AppVault::new(VaultFile::open("~/.app.vault", "my_password"));
+//! vault.credentials().save(&Credentials { token: "s3cr3t".into() })?;
+//! let settings = vault.settings().load_opt()?.unwrap_or_default();
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{ErrorKind, SerdeVaultError};
+use crate::vault::VaultFile;
+
+/// One section of a [`vault_schema!`](crate::vault_schema) vault, typed as `T`.
+pub struct Section<'a, T> {
+    vault: &'a VaultFile,
+    name: &'static str,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: Serialize + DeserializeOwned> Section<'a, T> {
+    #[doc(hidden)]
+    pub fn new(vault: &'a VaultFile, name: &'static str) -> Self {
+        Self {
+            vault,
+            name,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Load this section. Fails with `PointerNotFound` if it was never saved.
+    pub fn load(&self) -> Result<T, SerdeVaultError> {
+        self.vault.load_path(&self.pointer())
+    }
+
+    /// Load this section, or `None` if neither it nor the vault exists yet.
+    pub fn load_opt(&self) -> Result<Option<T>, SerdeVaultError> {
+        match self.load() {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replace this section, leaving the others as they are. Creates the vault
+    /// if needed.
+    pub fn save(&self, data: &T) -> Result<(), SerdeVaultError> {
+        let value = serde_json::to_value(data)
+            .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?;
+        self.vault.update_document(|document| {
+            document.insert(self.name.to_string(), value);
+        })
+    }
+
+    /// Delete this section from the vault.
+    pub fn remove(&self) -> Result<(), SerdeVaultError> {
+        self.vault.update_document(|document| {
+            document.remove(self.name);
+        })
+    }
+
+    /// Name of the section, which is its key in the stored object.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn pointer(&self) -> String {
+        format!("/{}", self.name)
+    }
+}
+
+impl<T> fmt::Debug for Section<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Section")
+            .field("path", &self.vault.path())
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl VaultFile {
+    /// Modify the stored JSON object under the update lock, starting from an
+    /// empty object if the vault doesn't exist yet.
+    pub(crate) fn update_document(
+        &self,
+        f: impl FnOnce(&mut serde_json::Map<String, Value>),
+    ) -> Result<(), SerdeVaultError> {
+        let _lock = self.lock_for_update()?;
+        let mut document = match self.load_opt::<Value>()? {
+            None => serde_json::Map::new(),
+            Some(Value::Object(map)) => map,
+            Some(_) => {
+                return Err(SerdeVaultError::DeserializationError(
+                    "vault does not hold a JSON object".to_string(),
+                ))
+            }
+        };
+        f(&mut document);
+        self.save(&document)
+    }
+}
+
+/// Declare a vault made of named, independently typed sections.
+///
+/// Generates a struct wrapping a [`VaultFile`](crate::VaultFile) with a
+/// constructor `new(vault)`, a `vault()` accessor, and for every field a method
+/// of the same name returning a [`Section`] with `load`, `load_opt`, `save`
+/// and `remove`. See the [module docs](crate::schema) for an example.
+#[macro_export]
+macro_rules! vault_schema {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$field_meta:meta])* $field:ident : $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            vault: $crate::VaultFile,
+        }
+
+        impl $name {
+            /// Wrap `vault`, which stores one entry per section.
+            $vis fn new(vault: $crate::VaultFile) -> Self {
+                Self { vault }
+            }
+
+            /// The underlying vault handle.
+            $vis fn vault(&self) -> &$crate::VaultFile {
+                &self.vault
+            }
+
+            $(
+                $(#[$field_meta])*
+                $vis fn $field(&self) -> $crate::schema::Section<'_, $ty> {
+                    $crate::schema::Section::new(&self.vault, stringify!($field))
+                }
+            )*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Credentials {
+        token: String,
+    }
+
+    vault_schema! {
+        struct TestVault {
+            credentials: Credentials,
+            /// Retry count.
+            retries: u32,
+        }
+    }
+
+    #[test]
+    fn test_sections_are_independent() {
+        let dir = tempdir().unwrap();
+        let vault = TestVault::new(
+            VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1),
+        );
+
+        assert_eq!(vault.retries().load_opt().unwrap(), None);
+        vault.retries().save(&3).unwrap();
+        assert_eq!(vault.credentials().load_opt().unwrap(), None);
+
+        let creds = Credentials {
+            token: "s3cr3t".into(),
+        };
+        vault.credentials().save(&creds).unwrap();
+        assert_eq!(vault.credentials().load().unwrap(), creds);
+        assert_eq!(vault.retries().load().unwrap(), 3);
+
+        vault.retries().remove().unwrap();
+        assert_eq!(vault.retries().load_opt().unwrap(), None);
+        assert_eq!(vault.credentials().load().unwrap(), creds);
+        assert_eq!(vault.retries().name(), "retries");
+        let stored = vault.vault().load_value().unwrap();
+        assert_eq!(stored, serde_json::json!({ "credentials": creds }));
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::error::SerdeVaultError;
+use crate::wipe::{SecretBuf, SecretValue};
+
+/// Encoding used for the plaintext before encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Format {
+    /// Compact JSON via `serde_json`.
+    #[default]
+    Json,
+    /// JSON with object keys sorted at every level and no whitespace, so equal
+    /// data always encodes to the same bytes. Pair it with
+    /// [`VaultFile::with_deterministic_encryption`](crate::VaultFile::with_deterministic_encryption)
+    /// for reproducible files.
+    CanonicalJson,
+    /// Newline-delimited JSON: one compact record per line. The stored value
+    /// must be a sequence. Lets records be appended and read one at a time with
+    /// [`VaultFile::append_record`](crate::VaultFile::append_record) and
+    /// [`VaultFile::records`](crate::VaultFile::records).
+    NdJson,
+    /// A [`Codec`] registered under this id with [`Format::register`].
+    Custom(u8),
+}
+
+/// Lowest id available to [`Format::register`]; those below are reserved for
+/// formats built into the crate.
+pub const FIRST_CUSTOM_ID: u8 = 128;
+
+/// A user-provided payload encoding, for [`Format::register`].
+///
+/// Codecs see the data as a `serde_json::Value`, so any serde type can be
+/// stored with them. Return `SerializationError`/`DeserializationError` on failure.
+///
+/// The crate wipes the values it passes in and gets back, and the buffer
+/// `encode` returns, but not whatever the codec allocates in between: reserve
+/// the output up front rather than letting it grow, where possible.
+pub trait Codec: Send + Sync + 'static {
+    /// Encode `value` into the bytes that get encrypted.
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, SerdeVaultError>;
+
+    /// Inverse of [`encode`](Self::encode).
+    fn decode(&self, bytes: &[u8]) -> Result<Value, SerdeVaultError>;
+}
+
+fn codecs() -> &'static RwLock<HashMap<u8, Arc<dyn Codec>>> {
+    static CODECS: OnceLock<RwLock<HashMap<u8, Arc<dyn Codec>>>> = OnceLock::new();
+    CODECS.get_or_init(Default::default)
+}
+
+impl Format {
+    /// Register `codec` process-wide under `id` and return the format to pass to
+    /// [`VaultFile::with_format`](crate::VaultFile::with_format).
+    ///
+    /// The id is recorded in every vault saved with it, so `load` picks the codec
+    /// automatically — register it before loading such vaults. Ids must be at
+    /// least [`FIRST_CUSTOM_ID`] and not already taken, else `InvalidConfig`.
+    pub fn register(id: u8, codec: impl Codec) -> Result<Format, SerdeVaultError> {
+        if id < FIRST_CUSTOM_ID {
+            return Err(SerdeVaultError::InvalidConfig(format!(
+                "format id {id} is reserved; custom ids start at {FIRST_CUSTOM_ID}"
+            )));
+        }
+        let mut codecs = codecs().write().unwrap_or_else(|e| e.into_inner());
+        if codecs.contains_key(&id) {
+            return Err(SerdeVaultError::InvalidConfig(format!(
+                "format id {id} is already registered"
+            )));
+        }
+        codecs.insert(id, Arc::new(codec));
+        Ok(Format::Custom(id))
+    }
+
+    /// Identifier recorded in the vault header.
+    pub fn id(self) -> u8 {
+        match self {
+            Format::Json => 1,
+            Format::CanonicalJson => 2,
+            Format::NdJson => 3,
+            Format::Custom(id) => id,
+        }
+    }
+
+    /// The format recorded as `id`, if this build supports it (or a codec is
+    /// registered under it).
+    pub fn from_id(id: u8) -> Option<Format> {
+        match id {
+            1 => Some(Format::Json),
+            2 => Some(Format::CanonicalJson),
+            3 => Some(Format::NdJson),
+            id if id >= FIRST_CUSTOM_ID && codec(id).is_ok() => Some(Format::Custom(id)),
+            _ => None,
+        }
+    }
+
+    /// Encode `data` into a buffer that is wiped on drop, and that leaves no
+    /// copies behind as it grows.
+    pub(crate) fn serialize<T: Serialize>(
+        self,
+        data: &T,
+    ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+        match self {
+            Format::Custom(id) => codec(id)?
+                .encode(&SecretValue(to_value(data)?))
+                .map(Zeroizing::new),
+            _ => {
+                let mut out = SecretBuf::new();
+                self.serialize_into(data, &mut out)?;
+                Ok(out.into_inner())
+            }
+        }
+    }
+
+    /// Encode `data` straight into `out`, without an intermediate buffer.
+    pub(crate) fn serialize_into<T: Serialize, W: Write>(
+        self,
+        data: &T,
+        mut out: W,
+    ) -> Result<(), SerdeVaultError> {
+        match self {
+            Format::Json => serde_json::to_writer(out, data).map_err(|e| {
+                if e.is_io() {
+                    SerdeVaultError::IoError(e.into())
+                } else {
+                    SerdeVaultError::SerializationError(e.to_string())
+                }
+            }),
+            Format::CanonicalJson => write_canonical(&SecretValue(to_value(data)?), &mut out),
+            Format::NdJson => write_ndjson(&SecretValue(to_value(data)?), &mut out),
+            Format::Custom(_) => Ok(out.write_all(&self.serialize(data)?)?),
+        }
+    }
+
+    /// Decode a plaintext buffer produced by [`Format::serialize`].
+    pub(crate) fn deserialize<T: DeserializeOwned>(
+        self,
+        bytes: &[u8],
+    ) -> Result<T, SerdeVaultError> {
+        match self {
+            Format::Json | Format::CanonicalJson => {
+                serde_json::from_slice(bytes).map_err(|e| json_deserialize_error(bytes, e))
+            }
+            Format::NdJson => {
+                let records = ndjson_lines(bytes)
+                    .map(|(line, record)| parse_record(line, record))
+                    .collect::<Result<Vec<SecretValue>, _>>()?;
+                let records = records.into_iter().map(SecretValue::expose).collect();
+                from_secret_value(SecretValue(Value::Array(records)))
+            }
+            Format::Custom(id) => from_secret_value(SecretValue(codec(id)?.decode(bytes)?)),
+        }
+    }
+}
+
+/// Write one NDJSON line for `record`.
+pub(crate) fn write_record<T: Serialize + ?Sized>(
+    record: &T,
+    out: &mut impl Write,
+) -> Result<(), SerdeVaultError> {
+    write_scalar(record, out)?;
+    Ok(out.write_all(b"\n")?)
+}
+
+/// The non-blank lines of an NDJSON payload, with their 1-based line numbers.
+pub(crate) fn ndjson_lines(bytes: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    bytes
+        .split(|&b| b == b'\n')
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+}
+
+/// Parse one NDJSON line, naming it in the error.
+pub(crate) fn parse_record<T: DeserializeOwned>(
+    line: usize,
+    record: &[u8],
+) -> Result<T, SerdeVaultError> {
+    serde_json::from_slice(record)
+        .map_err(|e| SerdeVaultError::DeserializationError(format!("record on line {line}: {e}")))
+}
+
+fn write_ndjson(value: &Value, out: &mut impl Write) -> Result<(), SerdeVaultError> {
+    let Value::Array(records) = value else {
+        return Err(SerdeVaultError::SerializationError(
+            "NDJSON payloads must be sequences of records".to_string(),
+        ));
+    };
+    records
+        .iter()
+        .try_for_each(|record| write_record(record, out))
+}
+
+/// The codec registered under `id`.
+fn codec(id: u8) -> Result<Arc<dyn Codec>, SerdeVaultError> {
+    let codecs = codecs().read().unwrap_or_else(|e| e.into_inner());
+    codecs
+        .get(&id)
+        .cloned()
+        .ok_or(SerdeVaultError::UnsupportedFormat(id))
+}
+
+/// Compact JSON with object keys in byte order, whatever order the map keeps
+/// them in (serde_json's `preserve_order` feature may be on elsewhere in the
+/// build). Numbers use serde_json's shortest round-trip formatting, which is
+/// already fixed for a given value.
+fn write_canonical(value: &Value, out: &mut impl Write) -> Result<(), SerdeVaultError> {
+    match value {
+        Value::Array(items) => {
+            out.write_all(b"[")?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                write_canonical(item, out)?;
+            }
+            out.write_all(b"]")?;
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            out.write_all(b"{")?;
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                write_scalar(key, out)?;
+                out.write_all(b":")?;
+                write_canonical(item, out)?;
+            }
+            out.write_all(b"}")?;
+        }
+        scalar => write_scalar(scalar, out)?,
+    }
+    Ok(())
+}
+
+fn write_scalar<T: Serialize + ?Sized>(
+    value: &T,
+    out: &mut impl Write,
+) -> Result<(), SerdeVaultError> {
+    serde_json::to_writer(out, value)
+        .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))
+}
+
+/// Deserialize `T` from a copy of `value`'s contents, wiping `value` afterwards.
+fn from_secret_value<T: DeserializeOwned>(value: SecretValue) -> Result<T, SerdeVaultError> {
+    T::deserialize(&*value).map_err(|e| SerdeVaultError::DeserializationError(e.to_string()))
+}
+
+fn to_value<T: Serialize>(data: &T) -> Result<Value, SerdeVaultError> {
+    serde_json::to_value(data).map_err(|e| SerdeVaultError::SerializationError(e.to_string()))
+}
+
+#[cfg(not(feature = "path-errors"))]
+fn json_deserialize_error(_json: &[u8], e: serde_json::Error) -> SerdeVaultError {
+    SerdeVaultError::DeserializationError(e.to_string())
+}
+
+/// Prefix the serde_json message with the path of the offending field, e.g.
+/// `features[2].name: invalid type: integer `5`, expected a string`.
+#[cfg(feature = "path-errors")]
+fn json_deserialize_error(json: &[u8], e: serde_json::Error) -> SerdeVaultError {
+    let path = json_path_at(json, e.line(), e.column());
+    if path.is_empty() {
+        SerdeVaultError::DeserializationError(e.to_string())
+    } else {
+        SerdeVaultError::DeserializationError(format!("{path}: {e}"))
+    }
+}
+
+/// Path of the JSON value whose last byte is at `line`/`column` (both 1-based,
+/// as reported by `serde_json::Error`), in `a.b[2].c` notation.
+///
+/// serde_json reports the position just after the value it rejected, or just
+/// after the closing brace of an object missing a field, so replaying the
+/// document up to that point yields exactly the containers that enclose it.
+#[cfg(feature = "path-errors")]
+fn json_path_at(json: &[u8], line: usize, column: usize) -> String {
+    enum Frame {
+        Object {
+            key: Option<String>,
+            expecting_key: bool,
+        },
+        Array {
+            index: usize,
+        },
+    }
+
+    let line_start = json
+        .split_inclusive(|&b| b == b'\n')
+        .take(line.saturating_sub(1))
+        .map(<[u8]>::len)
+        .sum::<usize>();
+    let end = (line_start + column).min(json.len());
+
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut i = 0;
+    while i < end {
+        match json[i] {
+            b'{' => stack.push(Frame::Object {
+                key: None,
+                expecting_key: true,
+            }),
+            b'[' => stack.push(Frame::Array { index: 0 }),
+            b'}' | b']' => {
+                stack.pop();
+            }
+            b',' => match stack.last_mut() {
+                Some(Frame::Object { expecting_key, .. }) => *expecting_key = true,
+                Some(Frame::Array { index }) => *index += 1,
+                None => {}
+            },
+            b'"' => {
+                let start = i + 1;
+                i = start;
+                while i < end && json[i] != b'"' {
+                    i += if json[i] == b'\\' { 2 } else { 1 };
+                }
+                if let Some(Frame::Object { key, expecting_key }) = stack.last_mut() {
+                    if *expecting_key {
+                        *key = Some(String::from_utf8_lossy(&json[start..i.min(end)]).into_owned());
+                        *expecting_key = false;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut path = String::new();
+    for frame in &stack {
+        match frame {
+            Frame::Object {
+                key: Some(key),
+                expecting_key: false,
+            } => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Frame::Object { .. } => {}
+            Frame::Array { index } => path.push_str(&format!("[{index}]")),
+        }
+    }
+    path
+}
+
+#[cfg(all(test, feature = "path-errors"))]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Feature {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Config {
+        features: Vec<Feature>,
+        retries: u32,
+    }
+
+    fn error_for(json: &str) -> String {
+        match Format::Json.deserialize::<Config>(json.as_bytes()) {
+            Err(SerdeVaultError::DeserializationError(msg)) => msg,
+            other => panic!("expected DeserializationError, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_path_of_wrong_type() {
+        let msg = error_for(r#"{"features":[{"name":"a"},{"name":"b"},{"name":5}],"retries":1}"#);
+        assert!(msg.starts_with("features[2].name: invalid type"), "{msg}");
+    }
+
+    #[test]
+    fn test_path_of_missing_field() {
+        let msg = error_for(r#"{"features":[{"nam":"a"}],"retries":1}"#);
+        assert!(
+            msg.starts_with("features[0]: missing field `name`"),
+            "{msg}"
+        );
+    }
+
+    #[test]
+    fn test_path_in_pretty_json() {
+        let msg = error_for("{\n  \"features\": [],\n  \"retries\": \"x\"\n}");
+        assert!(msg.starts_with("retries: invalid type"), "{msg}");
+    }
+}
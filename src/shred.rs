@@ -0,0 +1,30 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use rand::{rngs::OsRng, RngCore};
+
+/// Overwrite buffer size.
+const CHUNK: usize = 64 * 1024;
+
+/// Overwrite a file in place with random bytes, sync it, then unlink it.
+///
+/// Best-effort only: SSDs (wear levelling), copy-on-write filesystems (btrfs,
+/// ZFS, APFS), snapshots and backups may all keep the old blocks around. The
+/// overwrite only raises the bar against casual recovery from the same device.
+pub fn shred(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let mut buf = vec![0u8; CHUNK];
+
+    while remaining > 0 {
+        let n = remaining.min(CHUNK as u64) as usize;
+        OsRng.fill_bytes(&mut buf[..n]);
+        file.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+
+    fs::remove_file(path)
+}
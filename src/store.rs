@@ -0,0 +1,491 @@
+//! Many named entries in one vault file.
+//!
+//! A [`VaultStore`] keeps any number of independently typed values under
+//! string names, password-manager style, instead of one struct holding
+//! everything:
+//!
+//! ```no_run
+//! use serde::{Deserialize, Serialize};
+//! use serdevault::VaultStore;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Login { user: String, password: String }
+//!
+//! let store = VaultStore::open("~/.passwords.vault", "master_password");
+//! store.put("github", &Login { user: "me".into(), password: "hunter2".into() })?;
+//! let github: Login = store.get("github")?;
+//! for name in store.list()? {
+//!     println!("{name}");
+//! }
+//! # Ok::<(), serdevault::SerdeVaultError>(())
+//! ```
+//!
+//! Each entry is encrypted under a data key of its own, inside the vault's own
+//! encryption, and removing an entry discards its key along with it. Every
+//! call still decrypts and parses the whole vault to reach the store's
+//! document; only the entry being read is then unsealed and deserialized, so
+//! other entries' values stay sealed in memory. Reaching one entry therefore
+//! costs as much I/O and parsing as the whole store does; entry access does
+//! not avoid deserializing the store's document. Extra-sensitive entries can
+//! require a password of their own on top of the vault's, see
+//! [`VaultStore::put_protected`].
+
+use std::collections::{btree_map, BTreeMap};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+use crate::vault::VaultFile;
+
+mod blind;
+mod capacity;
+mod entry;
+mod expiry;
+mod import;
+mod index;
+mod iter;
+mod merge;
+mod merkle;
+mod metadata;
+mod query;
+mod share;
+mod transaction;
+
+pub use capacity::Eviction;
+use entry::{EntryPassword, SealedEntry, StoreKey};
+pub use import::{ImportPolicy, ImportReport, OnConflict};
+use index::Indexes;
+pub use iter::Entries;
+pub use merge::MergeStrategy;
+pub use merkle::MerkleProof;
+use metadata::Entry;
+pub use metadata::EntryMetadata;
+pub use query::Query;
+pub use transaction::Transaction;
+
+/// A vault holding named entries, each with its own type.
+///
+/// Every call reads the file; writes run under the vault's update lock, so
+/// concurrent `put`s from several processes don't lose each other's entries.
+/// All settings (Argon2 parameters, backups, permissions, …) come from the
+/// wrapped [`VaultFile`].
+#[derive(Clone)]
+pub struct VaultStore {
+    vault: VaultFile,
+    blind_names: bool,
+    purge_expired: bool,
+    size_cap: Option<(usize, Eviction)>,
+    /// Entries read since this handle last wrote, see [`Eviction::LeastRecentlyUsed`].
+    reads: Arc<Mutex<Vec<String>>>,
+}
+
+/// What the store keeps in its vault.
+#[derive(Default, Serialize, Deserialize)]
+struct StoreDocument {
+    /// Created with the first entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<StoreKey>,
+    /// Entries are keyed by blind index, see [`VaultStore::with_blind_names`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    blind: bool,
+    /// Counts entry uses, to order entries by last use.
+    #[serde(default)]
+    clock: u64,
+    entries: BTreeMap<String, Entry>,
+    /// See [`VaultStore::create_index`].
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    indexes: Indexes,
+}
+
+impl StoreDocument {
+    fn key(&self) -> Result<&StoreKey, SerdeVaultError> {
+        self.key
+            .as_ref()
+            .ok_or_else(|| SerdeVaultError::InvalidFormat("store has entries but no key".into()))
+    }
+
+    /// The key `name` is stored under in `entries`.
+    fn slot(&self, name: &str) -> String {
+        match &self.key {
+            Some(key) if self.blind => key.blind_index(name),
+            _ => name.to_string(),
+        }
+    }
+
+    fn entry(&self, name: &str) -> Result<&Entry, SerdeVaultError> {
+        self.entries
+            .get(&self.slot(name))
+            .ok_or_else(|| SerdeVaultError::EntryNotFound(name.to_string()))
+    }
+
+    fn entry_mut(&mut self, name: &str) -> Result<&mut Entry, SerdeVaultError> {
+        let slot = self.slot(name);
+        self.entries
+            .get_mut(&slot)
+            .ok_or_else(|| SerdeVaultError::EntryNotFound(name.to_string()))
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Entry> {
+        let slot = self.slot(name);
+        self.unindex(Some(&slot));
+        self.entries.remove(&slot)
+    }
+
+    /// All entries with their names, sorted by name.
+    fn named_entries(&self) -> Result<Vec<(String, &Entry)>, SerdeVaultError> {
+        let mut named = self
+            .entries
+            .iter()
+            .map(|(slot, entry)| match &entry.name {
+                Some(sealed) if self.blind => Ok((self.key()?.open_name(sealed, slot)?, entry)),
+                _ => Ok((slot.clone(), entry)),
+            })
+            .collect::<Result<Vec<_>, SerdeVaultError>>()?;
+        if self.blind {
+            named.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        Ok(named)
+    }
+
+    /// Decrypt the entry `name`, unless it has expired.
+    fn open(
+        &self,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+        let entry = self.entry(name)?;
+        if entry.is_expired() {
+            return Err(SerdeVaultError::EntryExpired(name.to_string()));
+        }
+        entry.sealed.open(self.key()?, name, password)
+    }
+
+    /// Seal `plaintext` as the entry `name`, replacing the value of any previous
+    /// one but keeping its metadata.
+    fn insert(
+        &mut self,
+        name: &str,
+        plaintext: &[u8],
+        password: Option<EntryPassword<'_>>,
+    ) -> Result<&mut Entry, SerdeVaultError> {
+        let key = self.key.get_or_insert_with(StoreKey::generate);
+        let sealed = SealedEntry::seal(key, name, plaintext, password)?;
+        if !sealed.is_protected() {
+            if let Some(previous) = self.entries.get(&self.slot(name)) {
+                if previous.sealed.is_protected() {
+                    return Err(SerdeVaultError::EntryProtected(name.to_string()));
+                }
+            }
+        }
+        let previous = self.remove(name);
+        self.place(name, Entry::replacing(previous, sealed))
+    }
+
+    /// Advance the use counter and return its new value.
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Store `entry` as `name`, which must be sealed under this document's key.
+    fn place(&mut self, name: &str, mut entry: Entry) -> Result<&mut Entry, SerdeVaultError> {
+        let slot = self.slot(name);
+        entry.name = None;
+        entry.accessed = self.tick();
+        if self.blind {
+            entry.name = Some(self.key()?.seal_name(name, &slot)?);
+        }
+        let keys = self.index_keys(name, &entry)?;
+        self.index(&slot, keys);
+        Ok(match self.entries.entry(slot) {
+            btree_map::Entry::Occupied(mut occupied) => {
+                occupied.insert(entry);
+                occupied.into_mut()
+            }
+            btree_map::Entry::Vacant(vacant) => vacant.insert(entry),
+        })
+    }
+}
+
+impl VaultStore {
+    /// A store at `path` with default vault settings. No I/O is performed.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Self {
+        VaultFile::open(path, password).into()
+    }
+
+    /// The vault the store is kept in.
+    pub fn vault(&self) -> &VaultFile {
+        &self.vault
+    }
+
+    /// Store `value` under `name`, replacing the value of any previous entry of
+    /// that name (its [metadata](Self::metadata) is kept). Creates the vault if
+    /// needed. Like every write, this decrypts, parses and re-encrypts the whole
+    /// store, not just the entry.
+    ///
+    /// Fails with [`SerdeVaultError::EntryProtected`] if the previous entry has
+    /// a password of its own, rather than dropping it: overwrite it with
+    /// [`put_protected`](Self::put_protected), or remove it first.
+    pub fn put<T: Serialize>(&self, name: &str, value: &T) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(value)?;
+        self.modify(|document| document.insert(name, &plaintext, None).map(drop))
+    }
+
+    /// Store `value` under `name`, readable only with `entry_password` on top
+    /// of the vault password, e.g. for recovery codes.
+    ///
+    /// The entry password's key is derived with the vault's Argon2 parameters
+    /// and a salt of its own, so each protected `put` and `get` costs one extra
+    /// key derivation.
+    pub fn put_protected<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+        entry_password: &str,
+    ) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(value)?;
+        let password = EntryPassword {
+            password: entry_password,
+            params: self.vault.argon2_params(),
+        };
+        self.modify(|document| document.insert(name, &plaintext, Some(password)).map(drop))
+    }
+
+    /// The entry stored under `name`. Fails with
+    /// [`SerdeVaultError::EntryNotFound`] if there is none, with
+    /// [`SerdeVaultError::EntryExpired`] if its TTL has passed, and with
+    /// [`SerdeVaultError::EntryProtected`] if it has a password of its own.
+    ///
+    /// The whole vault is decrypted and its document parsed to reach the entry;
+    /// only the entry itself is unsealed and deserialized.
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, SerdeVaultError> {
+        self.get_inner(name, None)
+    }
+
+    /// The entry stored under `name` by [`put_protected`](Self::put_protected).
+    /// A wrong entry password fails with `DecryptionFailed`. Entries without a
+    /// password of their own are returned as by [`get`](Self::get).
+    pub fn get_protected<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        entry_password: &str,
+    ) -> Result<T, SerdeVaultError> {
+        self.get_inner(name, Some(entry_password))
+    }
+
+    /// Whether the entry `name` has a password of its own.
+    pub fn is_protected(&self, name: &str) -> Result<bool, SerdeVaultError> {
+        Ok(self.read()?.entry(name)?.sealed.is_protected())
+    }
+
+    fn get_inner<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<T, SerdeVaultError> {
+        let plaintext = self.read()?.open(name, password)?;
+        if let Some((_, Eviction::LeastRecentlyUsed)) = self.size_cap {
+            self.record_read(name);
+        }
+        decode_entry(name, &plaintext)
+    }
+
+    /// Delete the entry stored under `name`. Returns whether there was one.
+    pub fn remove(&self, name: &str) -> Result<bool, SerdeVaultError> {
+        self.modify(|document| Ok(document.remove(name).is_some()))
+    }
+
+    /// Names of all entries, in sorted order, including expired ones that were
+    /// not purged yet (see [`expired`](Self::expired)). Empty if the vault
+    /// doesn't exist.
+    pub fn list(&self) -> Result<Vec<String>, SerdeVaultError> {
+        let document = self.read()?;
+        Ok(document
+            .named_entries()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// The stored document, or an empty one if the vault doesn't exist yet.
+    fn read(&self) -> Result<StoreDocument, SerdeVaultError> {
+        let mut document: StoreDocument = self.vault.load_opt()?.unwrap_or_default();
+        if document.key.is_none() {
+            // No entries yet: the handle decides how they will be keyed.
+            document.blind = self.blind_names;
+        }
+        Ok(document)
+    }
+
+    /// Change the stored document under the update lock and save it, dropping
+    /// expired entries first if [`with_purge_expired`](Self::with_purge_expired)
+    /// is on and keeping within the [size cap](Self::with_size_cap), then
+    /// dropping removed entries from the indexes. Nothing is written if `f`
+    /// fails.
+    fn modify<R>(
+        &self,
+        f: impl FnOnce(&mut StoreDocument) -> Result<R, SerdeVaultError>,
+    ) -> Result<R, SerdeVaultError> {
+        let _lock = self.vault.lock_for_update()?;
+        let mut document = self.read()?;
+        self.apply_reads(&mut document);
+        let baseline = match self.size_cap {
+            Some(_) => (document.clock, capacity::document_size(&document)?),
+            None => (document.clock, 0),
+        };
+        let result = f(&mut document)?;
+        if self.purge_expired {
+            document.entries.retain(|_, entry| !entry.is_expired());
+        }
+        if let Some((max, eviction)) = self.size_cap {
+            capacity::enforce(&mut document, baseline, max, eviction)?;
+        }
+        document.unindex(None);
+        self.vault.save(&document)?;
+        Ok(result)
+    }
+}
+
+/// Parse an entry's value, naming the entry in errors.
+fn decode_entry<T: DeserializeOwned>(name: &str, plaintext: &[u8]) -> Result<T, SerdeVaultError> {
+    Format::Json.deserialize(plaintext).map_err(|e| match e {
+        SerdeVaultError::DeserializationError(m) => {
+            SerdeVaultError::DeserializationError(format!("{name}: {m}"))
+        }
+        other => other,
+    })
+}
+
+impl From<VaultFile> for VaultStore {
+    fn from(vault: VaultFile) -> Self {
+        Self {
+            vault,
+            blind_names: false,
+            purge_expired: false,
+            size_cap: None,
+            reads: Arc::default(),
+        }
+    }
+}
+
+impl fmt::Debug for VaultStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultStore")
+            .field("path", &self.vault.path())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Login {
+        user: String,
+        password: String,
+    }
+
+    fn store_in(dir: &tempfile::TempDir) -> VaultStore {
+        VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into()
+    }
+
+    #[test]
+    fn test_put_get_remove_list() {
+        let dir = tempdir().unwrap();
+        let store = store_in(&dir);
+        assert!(store.list().unwrap().is_empty());
+
+        let login = Login {
+            user: "me".into(),
+            password: "hunter2".into(),
+        };
+        store.put("github", &login).unwrap();
+        store.put("pin", &1234u32).unwrap();
+        assert_eq!(store.get::<Login>("github").unwrap(), login);
+        assert_eq!(store.get::<u32>("pin").unwrap(), 1234);
+        assert_eq!(store.list().unwrap(), ["github", "pin"]);
+
+        assert!(store.remove("pin").unwrap());
+        assert!(!store.remove("pin").unwrap());
+        let err = store.get::<u32>("pin").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryNotFound(ref n) if n == "pin"));
+        assert_eq!(err.kind(), crate::ErrorKind::NotFound);
+
+        let err = store.get::<u32>("github").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DeserializationError(m) if m.starts_with("github")));
+    }
+
+    #[test]
+    fn test_entries_are_sealed_separately() {
+        let dir = tempdir().unwrap();
+        let store = store_in(&dir);
+        store.put("a", &"alpha").unwrap();
+        store.put("b", &"bravo").unwrap();
+
+        // Inside the vault's plaintext, bodies are opaque and bound to their name.
+        let mut document: StoreDocument = store.vault().load().unwrap();
+        let text = serde_json::to_string(&document).unwrap();
+        assert!(!text.contains("alpha") && !text.contains("bravo"));
+
+        let a = document.entries.remove("a").unwrap();
+        document.entries.insert("b".into(), a);
+        store.vault().save(&document).unwrap();
+        assert!(matches!(
+            store.get::<String>("b"),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+
+        // Re-encrypting the vault under a new password keeps entries readable.
+        store.put("b", &"bravo").unwrap();
+        let copy = dir.path().join("copy.svlt");
+        store.vault().copy_to(&copy, "new").unwrap();
+        let copy = VaultStore::from(VaultFile::open(&copy, "new").with_params(8, 1, 1));
+        assert_eq!(copy.get::<String>("b").unwrap(), "bravo");
+    }
+
+    #[test]
+    fn test_protected_entries_need_their_password() {
+        let dir = tempdir().unwrap();
+        let store = store_in(&dir);
+        let codes = vec!["1111-2222".to_string(), "3333-4444".to_string()];
+        store
+            .put_protected("recovery", &codes, "entry-pwd")
+            .unwrap();
+        store.put("plain", &1u8).unwrap();
+
+        assert!(store.is_protected("recovery").unwrap());
+        assert!(!store.is_protected("plain").unwrap());
+        let err = store.get::<Vec<String>>("recovery").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryProtected(ref n) if n == "recovery"));
+        assert_eq!(err.kind(), crate::ErrorKind::Locked);
+        assert!(matches!(
+            store.get_protected::<Vec<String>>("recovery", "wrong"),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+        assert_eq!(
+            store
+                .get_protected::<Vec<String>>("recovery", "entry-pwd")
+                .unwrap(),
+            codes
+        );
+        assert_eq!(store.get_protected::<u8>("plain", "anything").unwrap(), 1);
+
+        // put doesn't silently drop the entry password.
+        let err = store.put("recovery", &codes).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryProtected(ref n) if n == "recovery"));
+        assert!(store.is_protected("recovery").unwrap());
+        assert!(store.remove("recovery").unwrap());
+        store.put("recovery", &codes).unwrap();
+        assert_eq!(store.get::<Vec<String>>("recovery").unwrap(), codes);
+    }
+}
@@ -0,0 +1,53 @@
+use super::VaultStore;
+
+impl VaultStore {
+    /// Key entries by a blind index of their name instead of the name itself.
+    ///
+    /// The vault is encrypted as a whole, so names never reach the disk in the
+    /// clear either way. With blind names they are also absent from the
+    /// decrypted document (as seen by [`VaultFile::load_value`] or
+    /// [`export_plaintext_json`]): each entry is keyed by a BLAKE2b MAC of its
+    /// name under the store key, and the name itself is stored encrypted for
+    /// [`list`](Self::list). `get` recomputes the index; it never scans.
+    ///
+    /// Only takes effect when the store's first entry is written. A store keeps
+    /// the layout it was created with, whatever later handles ask for.
+    ///
+    /// [`VaultFile::load_value`]: crate::VaultFile::load_value
+    /// [`export_plaintext_json`]: crate::VaultFile::export_plaintext_json
+    pub fn with_blind_names(mut self, blind: bool) -> Self {
+        self.blind_names = blind;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_blind_names_hide_names_in_document() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("store.svlt"), "pwd").with_params(8, 1, 1);
+        let store = VaultStore::from(vault.clone()).with_blind_names(true);
+        store.put("zeta-bank", &1).unwrap();
+        store.put("alpha-mail", &2).unwrap();
+        store.set_tags("alpha-mail", ["mail"]).unwrap();
+
+        let document = vault.load_value().unwrap().to_string();
+        assert!(!document.contains("bank") && !document.contains("alpha"));
+
+        // A plain handle on the same vault follows the stored layout.
+        let plain = VaultStore::from(vault);
+        assert_eq!(plain.list().unwrap(), ["alpha-mail", "zeta-bank"]);
+        assert_eq!(plain.get::<u8>("zeta-bank").unwrap(), 1);
+        assert_eq!(
+            plain.find(&crate::store::Query::new().tag("mail")).unwrap(),
+            ["alpha-mail"]
+        );
+        assert!(plain.remove("zeta-bank").unwrap());
+        assert_eq!(store.list().unwrap(), ["alpha-mail"]);
+    }
+}
@@ -0,0 +1,252 @@
+use std::sync::PoisonError;
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+
+use super::{StoreDocument, VaultStore};
+
+/// What a size-capped store does when a write would take it over its cap, see
+/// [`VaultStore::with_size_cap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    /// Remove the least recently used entries until the store fits. Entries
+    /// count as used when they are put or read with `get`; reads are recorded
+    /// by the next write from the same handle (or its clones). Entries with a
+    /// password of their own are evicted like any other.
+    LeastRecentlyUsed,
+    /// Fail the write with [`SerdeVaultError::StoreFull`].
+    RejectWrites,
+}
+
+impl VaultStore {
+    /// Keep the store within `max_bytes`, e.g. for a cache of API responses,
+    /// and make room per `eviction` when a write would exceed it.
+    ///
+    /// The size counted is that of the store's document before encryption, see
+    /// [`size`](Self::size); the vault file adds a fixed header on top. An
+    /// entry that doesn't fit on its own is rejected either way.
+    ///
+    /// Under [`Eviction::LeastRecentlyUsed`], reads don't write: `get` and
+    /// `get_protected` note the access in memory, and the handle's next write
+    /// records it in the store before evicting anything. Reads through other
+    /// handles, or not followed by a write, don't count.
+    pub fn with_size_cap(mut self, max_bytes: usize, eviction: Eviction) -> Self {
+        self.size_cap = Some((max_bytes, eviction));
+        self
+    }
+
+    /// The size of the store as counted by [`with_size_cap`](Self::with_size_cap),
+    /// in bytes.
+    pub fn size(&self) -> Result<usize, SerdeVaultError> {
+        document_size(&self.read()?)
+    }
+
+    /// Note that the entry `name` was read, for [`apply_reads`](Self::apply_reads).
+    pub(super) fn record_read(&self, name: &str) {
+        let mut reads = self.reads.lock().unwrap_or_else(PoisonError::into_inner);
+        reads.retain(|read| read != name);
+        reads.push(name.to_string());
+    }
+
+    /// Mark the entries read since this handle last wrote as used, in the order
+    /// they were read.
+    pub(super) fn apply_reads(&self, document: &mut StoreDocument) {
+        let reads = std::mem::take(&mut *self.reads.lock().unwrap_or_else(PoisonError::into_inner));
+        for name in reads {
+            let now = document.tick();
+            if let Ok(entry) = document.entry_mut(&name) {
+                entry.accessed = now;
+            }
+        }
+    }
+}
+
+/// Bring `document` within `max` bytes after a change that grew it from
+/// `before` bytes: entries used by the change itself (since `clock`) are kept.
+/// Changes that don't grow the document pass even over the cap, so entries can
+/// still be removed from a store whose cap was lowered.
+pub(super) fn enforce(
+    document: &mut StoreDocument,
+    (clock, before): (u64, usize),
+    max: usize,
+    eviction: Eviction,
+) -> Result<(), SerdeVaultError> {
+    let mut size = document_size(document)?;
+    if size <= max || size <= before {
+        return Ok(());
+    }
+    if eviction == Eviction::RejectWrites {
+        return Err(SerdeVaultError::StoreFull { size, max });
+    }
+
+    let mut unused: Vec<_> = document
+        .entries
+        .iter()
+        .filter(|(_, entry)| entry.accessed <= clock)
+        .map(|(slot, entry)| (entry.accessed, slot.clone()))
+        .collect();
+    unused.sort();
+    let mut unused = unused.into_iter();
+    while size > max {
+        let Some((_, slot)) = unused.next() else {
+            return Err(SerdeVaultError::StoreFull { size, max });
+        };
+        document.entries.remove(&slot);
+        size = document_size(document)?;
+    }
+    Ok(())
+}
+
+pub(super) fn document_size(document: &StoreDocument) -> Result<usize, SerdeVaultError> {
+    Ok(Format::Json.serialize(document)?.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_size_cap_evicts_or_rejects() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("cache.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        let body = "x".repeat(200);
+        store.put("a", &body).unwrap();
+        let one = store.size().unwrap();
+        store.put("b", &body).unwrap();
+        let two = store.size().unwrap();
+
+        let lru = store
+            .clone()
+            .with_size_cap(two + (two - one) / 2, Eviction::LeastRecentlyUsed);
+        lru.get::<String>("a").unwrap();
+        assert_eq!(store.size().unwrap(), two);
+        let reader = VaultFile::open_read_only(dir.path().join("cache.svlt"), "pwd");
+        let reader = VaultStore::from(reader).with_size_cap(two, Eviction::LeastRecentlyUsed);
+        reader.get::<String>("b").unwrap();
+        lru.put("c", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "c"]);
+        assert!(matches!(
+            lru.put("huge", &"x".repeat(2 * two)),
+            Err(SerdeVaultError::StoreFull { .. })
+        ));
+
+        let strict = store.clone().with_size_cap(two, Eviction::RejectWrites);
+        let err = strict.put("d", &body).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::StoreFull { max, .. } if max == two));
+        assert_eq!(store.list().unwrap(), ["a", "c"]);
+        assert!(strict.remove("a").unwrap());
+        strict.put("d", &body).unwrap();
+
+        // Changes that store no entry count too.
+        let tight = store
+            .clone()
+            .with_size_cap(store.size().unwrap(), Eviction::RejectWrites);
+        let err = tight.set_label("c", Some(&body)).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::StoreFull { .. }));
+        assert!(matches!(
+            tight.create_index("x"),
+            Err(SerdeVaultError::StoreFull { .. })
+        ));
+        tight.set_label("c", None).unwrap();
+    }
+
+    fn store(dir: &tempfile::TempDir) -> VaultStore {
+        VaultFile::open(dir.path().join("cache.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into()
+    }
+
+    #[test]
+    fn test_lru_evicts_in_read_order() {
+        let dir = tempdir().unwrap();
+        let store = store(&dir);
+        let body = "x".repeat(200);
+        store.put("a", &body).unwrap();
+        let one = store.size().unwrap();
+        store.put("b", &body).unwrap();
+        store.put("c", &body).unwrap();
+        let three = store.size().unwrap();
+
+        let lru = store
+            .clone()
+            .with_size_cap(three + one / 2, Eviction::LeastRecentlyUsed);
+        lru.get::<String>("b").unwrap();
+        lru.get::<String>("a").unwrap();
+        lru.put("d", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "b", "d"]);
+        lru.put("e", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "d", "e"]);
+        // Reading again moves an entry back to the end of the queue.
+        lru.get::<String>("a").unwrap();
+        lru.put("f", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "e", "f"]);
+    }
+
+    #[test]
+    fn test_lru_counts_protected_entries() {
+        let dir = tempdir().unwrap();
+        let store = store(&dir);
+        let body = "x".repeat(200);
+        store.put_protected("p", &body, "entry").unwrap();
+        let one = store.size().unwrap();
+        store.put("a", &body).unwrap();
+        let two = store.size().unwrap();
+
+        let lru = store
+            .clone()
+            .with_size_cap(two + (two - one) / 2, Eviction::LeastRecentlyUsed);
+        lru.get_protected::<String>("p", "entry").unwrap();
+        lru.put("b", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["b", "p"]);
+        // Once least recently used, a protected entry is evicted like any other.
+        lru.put("c", &body).unwrap();
+        assert_eq!(store.list().unwrap(), ["b", "c"]);
+    }
+
+    #[test]
+    fn test_oversized_entry_is_rejected() {
+        let dir = tempdir().unwrap();
+        let store = store(&dir);
+        store.put("a", &"x".repeat(200)).unwrap();
+        let max = 2 * store.size().unwrap();
+        let huge = "x".repeat(max);
+
+        for eviction in [Eviction::LeastRecentlyUsed, Eviction::RejectWrites] {
+            let capped = store.clone().with_size_cap(max, eviction);
+            let err = capped.put("huge", &huge).unwrap_err();
+            assert!(
+                matches!(err, SerdeVaultError::StoreFull { size, max: m } if size > m && m == max)
+            );
+            // Nothing was evicted to make room for it.
+            assert_eq!(store.list().unwrap(), ["a"]);
+        }
+    }
+
+    #[test]
+    fn test_size_cap_enforced_on_put() {
+        let dir = tempdir().unwrap();
+        let store = store(&dir);
+        let max = 1024;
+        let capped = store.clone().with_size_cap(max, Eviction::RejectWrites);
+        let body = "x".repeat(100);
+
+        let mut stored = 0;
+        while capped.put(&format!("k{stored}"), &body).is_ok() {
+            assert!(store.size().unwrap() <= max);
+            stored += 1;
+        }
+        assert!(stored > 0);
+        assert_eq!(store.list().unwrap().len(), stored);
+
+        // Overwrites are capped too, but may shrink an entry.
+        let err = capped.put("k0", &"x".repeat(300)).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::StoreFull { .. }));
+        capped.put("k0", &"x").unwrap();
+        assert_eq!(capped.get::<String>("k0").unwrap(), "x");
+        assert!(store.size().unwrap() <= max);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+use base64ct::{Base64, Encoding};
+use blake2::digest::consts::U32;
+use blake2::digest::Mac;
+use blake2::Blake2bMac;
+use rand::{rngs::OsRng, RngCore};
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::crypto::cipher::{open, seal};
+use crate::crypto::kdf::{derive_key, KEY_SIZE, SALT_SIZE};
+use crate::error::SerdeVaultError;
+
+use super::merkle::ValueDigest;
+
+/// The random key that wraps every entry's data key. Kept in the store's
+/// (encrypted) document, so changing the vault password doesn't touch entries.
+pub(super) struct StoreKey(Zeroizing<[u8; KEY_SIZE]>);
+
+/// An entry as stored: a data key of its own, wrapped by the [`StoreKey`], and
+/// the serialized value sealed under that data key. Both authenticate the
+/// entry's name, so entries can't be swapped around in the document.
+///
+/// A protected entry's data key is wrapped twice: first under a key derived
+/// from the entry password, then under the store key.
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct SealedEntry {
+    #[serde(with = "base64")]
+    key: Vec<u8>,
+    #[serde(with = "base64")]
+    body: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guard: Option<EntryGuard>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    digest: Option<ValueDigest>,
+}
+
+/// How the key of an entry password is derived.
+#[derive(Clone, Serialize, Deserialize)]
+struct EntryGuard {
+    #[serde(with = "base64")]
+    salt: Vec<u8>,
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl EntryGuard {
+    fn new((m_cost, t_cost, p_cost): (u32, u32, u32)) -> Self {
+        let mut salt = vec![0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            salt,
+            m_cost,
+            t_cost,
+            p_cost,
+        }
+    }
+
+    fn derive(&self, password: &str) -> Result<Zeroizing<[u8; KEY_SIZE]>, SerdeVaultError> {
+        let salt: &[u8; SALT_SIZE] = self
+            .salt
+            .as_slice()
+            .try_into()
+            .map_err(|_| SerdeVaultError::InvalidFormat("bad entry password salt".into()))?;
+        derive_key(password, salt, self.m_cost, self.t_cost, self.p_cost)
+    }
+}
+
+/// An entry password and the Argon2 parameters to derive its key with.
+pub(super) struct EntryPassword<'a> {
+    pub(super) password: &'a str,
+    pub(super) params: (u32, u32, u32),
+}
+
+impl StoreKey {
+    pub(super) fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+        OsRng.fill_bytes(key.as_mut());
+        Self(key)
+    }
+
+    /// The blind index of `name`: a keyed BLAKE2b hash, hex-encoded.
+    pub(super) fn blind_index(&self, name: &str) -> String {
+        self.hash(b"serdevault-name", &[name.as_bytes()])
+    }
+
+    /// The key of `value` in the index on `attribute`, hashed like a blind name.
+    pub(super) fn value_index(&self, attribute: &str, value: &str) -> String {
+        self.hash(
+            b"serdevault-index",
+            &[attribute.as_bytes(), &[0], value.as_bytes()],
+        )
+    }
+
+    fn hash(&self, persona: &[u8], parts: &[&[u8]]) -> String {
+        let mut mac = Blake2bMac::<U32>::new_with_salt_and_personal(&self.0[..], &[], persona)
+            .expect("32-byte key and short persona are within BLAKE2b limits");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    }
+
+    /// Encrypt the name of the entry stored under blind index `index`.
+    pub(super) fn seal_name(&self, name: &str, index: &str) -> Result<Vec<u8>, SerdeVaultError> {
+        seal(name.as_bytes(), &self.0, index.as_bytes())
+    }
+
+    /// Decrypt a name sealed by [`seal_name`](Self::seal_name).
+    pub(super) fn open_name(&self, sealed: &[u8], index: &str) -> Result<String, SerdeVaultError> {
+        let name = open(sealed, &self.0, index.as_bytes())?;
+        String::from_utf8(name.to_vec())
+            .map_err(|_| SerdeVaultError::InvalidFormat("entry name is not UTF-8".into()))
+    }
+}
+
+impl SealedEntry {
+    /// Seal `plaintext` as the entry `name` under a fresh data key, protected
+    /// by `password` if given.
+    pub(super) fn seal(
+        store_key: &StoreKey,
+        name: &str,
+        plaintext: &[u8],
+        password: Option<EntryPassword<'_>>,
+    ) -> Result<Self, SerdeVaultError> {
+        let data_key = StoreKey::generate();
+        let aad = name.as_bytes();
+        let (wrapped, guard) = match password {
+            Some(entry) => {
+                let guard = EntryGuard::new(entry.params);
+                let password_key = guard.derive(entry.password)?;
+                (
+                    Zeroizing::new(seal(&data_key.0[..], &password_key, aad)?),
+                    Some(guard),
+                )
+            }
+            None => (Zeroizing::new(data_key.0.to_vec()), None),
+        };
+        let digest_key = guard.as_ref().map(|_| &data_key.0);
+        Ok(Self {
+            key: seal(&wrapped, &store_key.0, aad)?,
+            body: seal(plaintext, &data_key.0, aad)?,
+            digest: Some(ValueDigest::of(plaintext, name, digest_key)?),
+            guard,
+        })
+    }
+
+    /// The same entry with its data key rewrapped from store key `from` to `to`.
+    /// The value is never decrypted, so this works for protected entries too.
+    pub(super) fn rewrap(
+        &self,
+        from: &StoreKey,
+        to: &StoreKey,
+        name: &str,
+    ) -> Result<Self, SerdeVaultError> {
+        let wrapped = open(&self.key, &from.0, name.as_bytes())?;
+        Ok(Self {
+            key: seal(&wrapped, &to.0, name.as_bytes())?,
+            body: self.body.clone(),
+            guard: self.guard.clone(),
+            digest: self.digest.clone(),
+        })
+    }
+
+    /// Whether `other` holds the very value this entry was sealed with, rather
+    /// than an equal one put separately.
+    pub(super) fn same_value(&self, other: &SealedEntry) -> bool {
+        self.body == other.body
+    }
+
+    /// The digest of the value, for Merkle proofs.
+    pub(super) fn digest(&self) -> Option<&ValueDigest> {
+        self.digest.as_ref()
+    }
+
+    /// Whether the entry has a password of its own.
+    pub(super) fn is_protected(&self) -> bool {
+        self.guard.is_some()
+    }
+
+    /// Unwrap the data key and decrypt the value stored as `name`. The entry
+    /// password is only used if the entry has one.
+    pub(super) fn open(
+        &self,
+        store_key: &StoreKey,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+        let data_key = self.data_key(store_key, name, password)?;
+        open(&self.body, &data_key, name.as_bytes())
+    }
+
+    /// Unwrap the data key of the entry stored as `name`.
+    pub(super) fn data_key(
+        &self,
+        store_key: &StoreKey,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<Zeroizing<[u8; KEY_SIZE]>, SerdeVaultError> {
+        let mut unwrapped = open(&self.key, &store_key.0, name.as_bytes())?;
+        if let Some(guard) = &self.guard {
+            let password =
+                password.ok_or_else(|| SerdeVaultError::EntryProtected(name.to_string()))?;
+            unwrapped = open(&unwrapped, &guard.derive(password)?, name.as_bytes())?;
+        }
+        if unwrapped.len() != KEY_SIZE {
+            return Err(SerdeVaultError::DecryptionFailed);
+        }
+        let mut data_key = Zeroizing::new([0u8; KEY_SIZE]);
+        data_key.copy_from_slice(&unwrapped);
+        Ok(data_key)
+    }
+}
+
+impl Serialize for StoreKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut buf = Zeroizing::new([0u8; 4 * KEY_SIZE.div_ceil(3)]);
+        let encoded =
+            Base64::encode(&self.0[..], buf.as_mut()).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(encoded)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoreKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+
+        impl Visitor<'_> for KeyVisitor {
+            type Value = StoreKey;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a base64-encoded 256-bit key")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<StoreKey, E> {
+                let mut key = Zeroizing::new([0u8; KEY_SIZE]);
+                match Base64::decode(v, key.as_mut()) {
+                    Ok(decoded) if decoded.len() == KEY_SIZE => Ok(StoreKey(key)),
+                    _ => Err(E::custom("invalid store key")),
+                }
+            }
+
+            fn visit_string<E: de::Error>(self, mut v: String) -> Result<StoreKey, E> {
+                let key = self.visit_str(&v);
+                v.zeroize();
+                key
+            }
+        }
+
+        deserializer.deserialize_str(KeyVisitor)
+    }
+}
+
+/// Byte strings as base64 text, a third larger rather than JSON's integer arrays.
+pub(super) mod base64 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&Base64::encode_string(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Base64::decode_vec(&text).map_err(de::Error::custom)
+    }
+}
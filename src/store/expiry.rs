@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+
+use super::{StoreDocument, VaultStore};
+
+impl VaultStore {
+    /// Store `value` under `name` for `ttl`, e.g. a session token or a
+    /// short-lived API key. Once it has passed, `get` fails with
+    /// [`SerdeVaultError::EntryExpired`] and [`expired`](Self::expired) reports
+    /// the entry, until it is purged or put again.
+    ///
+    /// Expiry has a resolution of one second.
+    pub fn put_with_ttl<T: Serialize>(
+        &self,
+        name: &str,
+        value: &T,
+        ttl: Duration,
+    ) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(value)?;
+        self.modify(|document| {
+            document.insert(name, &plaintext, None)?.expire_after(ttl);
+            Ok(())
+        })
+    }
+
+    /// Drop expired entries whenever the store is written. Off by default, so
+    /// that expired entries stay visible until [`purge_expired`](Self::purge_expired)
+    /// is called.
+    pub fn with_purge_expired(mut self, purge: bool) -> Self {
+        self.purge_expired = purge;
+        self
+    }
+
+    /// Names of the entries whose TTL has passed, in sorted order.
+    pub fn expired(&self) -> Result<Vec<String>, SerdeVaultError> {
+        expired_names(&self.read()?)
+    }
+
+    /// Remove the entries whose TTL has passed and return their names.
+    pub fn purge_expired(&self) -> Result<Vec<String>, SerdeVaultError> {
+        self.modify(|document| {
+            let expired = expired_names(document)?;
+            for name in &expired {
+                document.remove(name);
+            }
+            Ok(expired)
+        })
+    }
+}
+
+fn expired_names(document: &StoreDocument) -> Result<Vec<String>, SerdeVaultError> {
+    Ok(document
+        .named_entries()?
+        .into_iter()
+        .filter(|(_, entry)| entry.is_expired())
+        .map(|(name, _)| name)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_expired_entries_are_reported_and_purged() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        store
+            .put_with_ttl("session", &"abc", Duration::ZERO)
+            .unwrap();
+        store
+            .put_with_ttl("api-key", &"def", Duration::from_secs(3600))
+            .unwrap();
+        store.put("keep", &1).unwrap();
+
+        assert_eq!(store.list().unwrap(), ["api-key", "keep", "session"]);
+        assert_eq!(store.expired().unwrap(), ["session"]);
+        assert!(store.metadata("session").unwrap().is_expired());
+        assert!(!store.metadata("api-key").unwrap().is_expired());
+        let err = store.get::<String>("session").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryExpired(ref n) if n == "session"));
+        assert_eq!(store.get::<String>("api-key").unwrap(), "def");
+
+        // Any write purges when asked to.
+        let purging = store.clone().with_purge_expired(true);
+        purging.put("other", &2).unwrap();
+        assert_eq!(store.list().unwrap(), ["api-key", "keep", "other"]);
+
+        store
+            .put_with_ttl("session", &"abc", Duration::ZERO)
+            .unwrap();
+        assert_eq!(store.purge_expired().unwrap(), ["session"]);
+        // A plain put clears the TTL.
+        store.put_with_ttl("keep", &1, Duration::ZERO).unwrap();
+        store.put("keep", &1).unwrap();
+        assert!(store.expired().unwrap().is_empty());
+    }
+}
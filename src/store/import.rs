@@ -0,0 +1,330 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::error::SerdeVaultError;
+use crate::vault::VaultFile;
+
+use super::entry::StoreKey;
+use super::{Entry, StoreDocument, VaultStore};
+
+/// What to do when an imported entry has the name of an existing one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Keep the existing entry.
+    #[default]
+    Skip,
+    /// Replace the existing entry.
+    Overwrite,
+    /// Keep whichever value was put more recently.
+    PreferNewer,
+    /// Import nothing and fail with [`SerdeVaultError::EntryExists`].
+    Fail,
+}
+
+/// Which entries [`VaultStore::import_from`] takes, and how it resolves name
+/// collisions. The default imports everything and skips collisions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportPolicy {
+    only: Option<BTreeSet<String>>,
+    on_conflict: OnConflict,
+}
+
+impl ImportPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Import only the entries named here. Fails with `EntryNotFound` if the
+    /// other store lacks one of them.
+    pub fn only<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.only = Some(names.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.on_conflict = on_conflict;
+        self
+    }
+}
+
+/// What [`VaultStore::import_from`] did, by entry name.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Entries added or replaced.
+    pub imported: Vec<String>,
+    /// Entries left out because of a name collision.
+    pub skipped: Vec<String>,
+}
+
+impl VaultStore {
+    /// Copy entries from the store at `path` into this one, as selected by
+    /// `policy`.
+    ///
+    /// Entries keep their metadata, TTL and entry password: only their data
+    /// keys are rewrapped for this store, their values are never decrypted. The
+    /// other store is left untouched.
+    pub fn import_from(
+        &self,
+        path: impl AsRef<Path>,
+        password: &str,
+        policy: &ImportPolicy,
+    ) -> Result<ImportReport, SerdeVaultError> {
+        let source = VaultStore::from(VaultFile::open(path, password)).read()?;
+        self.modify(|document| merge_into(document, &source, policy))
+    }
+}
+
+/// Add the entries of `source` selected by `policy` to `document`. An entry
+/// whose value is the very one `document` already has (as in two copies of the
+/// same store) is skipped rather than treated as a conflict.
+pub(super) fn merge_into(
+    document: &mut StoreDocument,
+    source: &StoreDocument,
+    policy: &ImportPolicy,
+) -> Result<ImportReport, SerdeVaultError> {
+    let mut selected = source.named_entries()?;
+    if let Some(only) = &policy.only {
+        if let Some(missing) = only.iter().find(|n| !selected.iter().any(|(s, _)| s == *n)) {
+            return Err(SerdeVaultError::EntryNotFound(missing.clone()));
+        }
+        selected.retain(|(name, _)| only.contains(name));
+    }
+
+    let mut report = ImportReport::default();
+    let mut accepted = Vec::new();
+    for (name, entry) in selected {
+        let take = match document.entry(&name) {
+            Err(_) => true,
+            Ok(existing) if existing.sealed.same_value(&entry.sealed) => false,
+            Ok(existing) => match policy.on_conflict {
+                OnConflict::Skip => false,
+                OnConflict::Overwrite => true,
+                OnConflict::PreferNewer => entry.modified > existing.modified,
+                OnConflict::Fail => return Err(SerdeVaultError::EntryExists(name)),
+            },
+        };
+        if take {
+            accepted.push((name, entry));
+        } else {
+            report.skipped.push(name);
+        }
+    }
+    if !accepted.is_empty() {
+        let from = source.key()?;
+        for (name, entry) in accepted {
+            transplant(document, from, &name, entry)?;
+            report.imported.push(name);
+        }
+    }
+    Ok(report)
+}
+
+/// Add `entry`, sealed under `from`, to `document` as `name`.
+fn transplant(
+    document: &mut StoreDocument,
+    from: &StoreKey,
+    name: &str,
+    entry: &Entry,
+) -> Result<(), SerdeVaultError> {
+    let to = document.key.get_or_insert_with(StoreKey::generate);
+    let sealed = entry.sealed.rewrap(from, to, name)?;
+    document.remove(name);
+    document.place(name, entry.with_sealed(sealed))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::decode_entry;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_import_with_policies() {
+        let dir = tempdir().unwrap();
+        let store_at = |file: &str| -> VaultStore {
+            VaultFile::open(dir.path().join(file), "pwd")
+                .with_params(8, 1, 1)
+                .into()
+        };
+        let other = store_at("other.svlt");
+        other.put("shared", &"theirs").unwrap();
+        other.put("new", &1).unwrap();
+        other.put_protected("codes", &[1, 2], "entry-pwd").unwrap();
+        other.set_tags("new", ["imported"]).unwrap();
+        let other_path = other.vault().path().to_path_buf();
+
+        let store = store_at("store.svlt").with_blind_names(true);
+        store.put("shared", &"mine").unwrap();
+        let fail = ImportPolicy::new().on_conflict(OnConflict::Fail);
+        let err = store.import_from(&other_path, "pwd", &fail).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryExists(ref n) if n == "shared"));
+        assert_eq!(store.list().unwrap(), ["shared"]);
+
+        let report = store
+            .import_from(&other_path, "pwd", &ImportPolicy::new())
+            .unwrap();
+        assert_eq!(report.imported, ["codes", "new"]);
+        assert_eq!(report.skipped, ["shared"]);
+        assert_eq!(store.get::<String>("shared").unwrap(), "mine");
+        assert_eq!(
+            store
+                .get_protected::<Vec<u8>>("codes", "entry-pwd")
+                .unwrap(),
+            [1, 2]
+        );
+        assert!(store.metadata("new").unwrap().tags.contains("imported"));
+
+        let only = ImportPolicy::new()
+            .only(["shared"])
+            .on_conflict(OnConflict::Overwrite);
+        store.import_from(&other_path, "pwd", &only).unwrap();
+        assert_eq!(store.get::<String>("shared").unwrap(), "theirs");
+        let missing = ImportPolicy::new().only(["nope"]);
+        assert!(store.import_from(&other_path, "pwd", &missing).is_err());
+    }
+
+    /// Two store documents sharing the entry `shared`, with `theirs` put one
+    /// second after `mine` by the entries' own clocks.
+    fn conflicting(dir: &tempfile::TempDir) -> (StoreDocument, StoreDocument) {
+        let store_at = |file: &str, value: &str, extra: &str| {
+            let store: VaultStore = VaultFile::open(dir.path().join(file), "pwd")
+                .with_params(8, 1, 1)
+                .into();
+            store.put("shared", &value).unwrap();
+            store.put(extra, &value).unwrap();
+            store.read().unwrap()
+        };
+        let mine = store_at("mine.svlt", "mine", "local");
+        let mut theirs = store_at("theirs.svlt", "theirs", "new");
+        let modified = mine.entry("shared").unwrap().modified;
+        theirs.entry_mut("shared").unwrap().modified = modified + 1;
+        (mine, theirs)
+    }
+
+    fn value(document: &StoreDocument, name: &str) -> String {
+        decode_entry(name, &document.open(name, None).unwrap()).unwrap()
+    }
+
+    fn merge(
+        mine: &mut StoreDocument,
+        theirs: &StoreDocument,
+        on_conflict: OnConflict,
+    ) -> Result<ImportReport, SerdeVaultError> {
+        merge_into(mine, theirs, &ImportPolicy::new().on_conflict(on_conflict))
+    }
+
+    #[test]
+    fn test_on_conflict_skip_and_overwrite() {
+        let dir = tempdir().unwrap();
+        let (mut mine, theirs) = conflicting(&dir);
+        let report = merge(&mut mine, &theirs, OnConflict::Skip).unwrap();
+        assert_eq!(report.imported, ["new"]);
+        assert_eq!(report.skipped, ["shared"]);
+        assert_eq!(value(&mine, "shared"), "mine");
+        assert_eq!(value(&mine, "new"), "theirs");
+
+        let (mut mine, theirs) = conflicting(&dir);
+        let report = merge(&mut mine, &theirs, OnConflict::Overwrite).unwrap();
+        assert_eq!(report.imported, ["new", "shared"]);
+        assert!(report.skipped.is_empty());
+        assert_eq!(value(&mine, "shared"), "theirs");
+        assert_eq!(value(&mine, "local"), "mine");
+    }
+
+    #[test]
+    fn test_on_conflict_prefer_newer() {
+        let dir = tempdir().unwrap();
+        let (mut mine, theirs) = conflicting(&dir);
+        let report = merge(&mut mine, &theirs, OnConflict::PreferNewer).unwrap();
+        assert_eq!(report.imported, ["new", "shared"]);
+        assert_eq!(value(&mine, "shared"), "theirs");
+
+        // The other way round, ours is newer and stays. Ties keep ours too.
+        let (mut theirs, mut mine) = conflicting(&dir);
+        let report = merge(&mut mine, &theirs, OnConflict::PreferNewer).unwrap();
+        assert_eq!(report.imported, ["local"]);
+        assert_eq!(report.skipped, ["shared"]);
+        assert_eq!(value(&mine, "shared"), "theirs");
+        theirs.entry_mut("shared").unwrap().modified += 1;
+        let report = merge(&mut mine, &theirs, OnConflict::PreferNewer).unwrap();
+        assert!(report.imported.is_empty());
+        assert_eq!(report.skipped, ["local", "shared"]);
+        assert_eq!(value(&mine, "shared"), "theirs");
+    }
+
+    #[test]
+    fn test_on_conflict_fail_imports_nothing() {
+        let dir = tempdir().unwrap();
+        let (mut mine, theirs) = conflicting(&dir);
+        let err = merge(&mut mine, &theirs, OnConflict::Fail).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryExists(ref n) if n == "shared"));
+        let names: Vec<_> = mine
+            .named_entries()
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, ["local", "shared"]);
+        assert_eq!(value(&mine, "shared"), "mine");
+
+        // Without a conflict, `Fail` imports like any other policy.
+        let only = ImportPolicy::new()
+            .only(["new"])
+            .on_conflict(OnConflict::Fail);
+        let report = merge_into(&mut mine, &theirs, &only).unwrap();
+        assert_eq!(report.imported, ["new"]);
+    }
+
+    #[test]
+    fn test_import_keeps_entry_passwords() {
+        let dir = tempdir().unwrap();
+        let store_at = |file: &str| -> VaultStore {
+            VaultFile::open(dir.path().join(file), "pwd")
+                .with_params(8, 1, 1)
+                .into()
+        };
+        let other = store_at("other.svlt");
+        other
+            .put_protected("codes", &"theirs", "their-pwd")
+            .unwrap();
+        other.put_protected("pin", &"1234", "pin-pwd").unwrap();
+        let other_path = other.vault().path().to_path_buf();
+
+        let store = store_at("store.svlt");
+        store.put_protected("codes", &"mine", "my-pwd").unwrap();
+        let report = store
+            .import_from(&other_path, "pwd", &ImportPolicy::new())
+            .unwrap();
+        assert_eq!(report.imported, ["pin"]);
+        assert!(matches!(
+            store.get::<String>("pin"),
+            Err(SerdeVaultError::EntryProtected(_))
+        ));
+        assert!(matches!(
+            store.get_protected::<String>("pin", "pwd"),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+        assert_eq!(
+            store.get_protected::<String>("pin", "pin-pwd").unwrap(),
+            "1234"
+        );
+        assert_eq!(
+            store.get_protected::<String>("codes", "my-pwd").unwrap(),
+            "mine"
+        );
+
+        // Overwriting brings the imported entry's own password along.
+        let overwrite = ImportPolicy::new().on_conflict(OnConflict::Overwrite);
+        store.import_from(&other_path, "pwd", &overwrite).unwrap();
+        assert_eq!(
+            store.get_protected::<String>("codes", "their-pwd").unwrap(),
+            "theirs"
+        );
+        assert!(store.get_protected::<String>("codes", "my-pwd").is_err());
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+use crate::wipe::SecretValue;
+
+use super::metadata::Entry;
+use super::{StoreDocument, VaultStore};
+
+/// The indexes of a store, by attribute: each maps the keyed hash of a value
+/// (see `StoreKey::value_index`) to the slots of the entries holding it.
+pub(super) type Indexes = BTreeMap<String, BTreeMap<String, BTreeSet<String>>>;
+
+impl StoreDocument {
+    /// The index keys of `entry`, stored as `name`, one per indexed attribute
+    /// value. Protected entries aren't indexed.
+    pub(super) fn index_keys(
+        &self,
+        name: &str,
+        entry: &Entry,
+    ) -> Result<Vec<(String, String)>, SerdeVaultError> {
+        if self.indexes.is_empty() || entry.sealed.is_protected() {
+            return Ok(Vec::new());
+        }
+        let key = self.key()?;
+        let value: SecretValue = Format::Json.deserialize(&entry.sealed.open(key, name, None)?)?;
+        let mut keys = Vec::new();
+        for attribute in self.indexes.keys() {
+            let values = match value.get(attribute) {
+                Some(Value::Array(items)) => items.iter().collect(),
+                Some(item) => vec![item],
+                None => Vec::new(),
+            };
+            for item in values {
+                let hash = match item {
+                    Value::String(s) => key.value_index(attribute, s),
+                    Value::Bool(_) | Value::Number(_) => {
+                        key.value_index(attribute, &item.to_string())
+                    }
+                    _ => continue,
+                };
+                keys.push((attribute.clone(), hash));
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Add the entry in `slot` under `keys`, from [`index_keys`](Self::index_keys).
+    pub(super) fn index(&mut self, slot: &str, keys: Vec<(String, String)>) {
+        for (attribute, key) in keys {
+            if let Some(index) = self.indexes.get_mut(&attribute) {
+                index.entry(key).or_default().insert(slot.to_string());
+            }
+        }
+    }
+
+    /// Drop the entry in `slot`, or with `None` every entry that is gone, from
+    /// all indexes.
+    pub(super) fn unindex(&mut self, slot: Option<&str>) {
+        let entries = &self.entries;
+        for index in self.indexes.values_mut() {
+            for slots in index.values_mut() {
+                slots.retain(|s| slot.map_or(entries.contains_key(s), |slot| s != slot));
+            }
+            index.retain(|_, slots| !slots.is_empty());
+        }
+    }
+}
+
+impl VaultStore {
+    /// Index entries on the top-level field `attribute` of their value, e.g.
+    /// `"url"` for a struct with a `url` field, for [`find_by`](Self::find_by).
+    ///
+    /// Every entry is decrypted once to build the index, which is then kept up
+    /// to date by every write, from any handle. String, number and boolean
+    /// fields are indexed, as are arrays of them, one index key per item.
+    /// Entries with a password of their own are left out.
+    ///
+    /// Like the entries, the index is encrypted with the vault, and values are
+    /// kept only as keyed hashes (like [blind names](Self::with_blind_names)),
+    /// so they don't appear even in the decrypted document. Lookups are by
+    /// exact value. Does nothing if the index exists.
+    pub fn create_index(&self, attribute: &str) -> Result<(), SerdeVaultError> {
+        self.modify(|document| {
+            if document.indexes.contains_key(attribute) {
+                return Ok(());
+            }
+            document
+                .indexes
+                .insert(attribute.to_string(), BTreeMap::new());
+            let mut keys = Vec::new();
+            for (name, entry) in document.named_entries()? {
+                keys.push((document.slot(&name), document.index_keys(&name, entry)?));
+            }
+            for (slot, entry_keys) in keys {
+                document.index(&slot, entry_keys);
+            }
+            Ok(())
+        })
+    }
+
+    /// Delete the index on `attribute`. Returns whether there was one.
+    pub fn drop_index(&self, attribute: &str) -> Result<bool, SerdeVaultError> {
+        self.modify(|document| Ok(document.indexes.remove(attribute).is_some()))
+    }
+
+    /// The indexed attributes, in sorted order.
+    pub fn indexes(&self) -> Result<Vec<String>, SerdeVaultError> {
+        Ok(self.read()?.indexes.into_keys().collect())
+    }
+
+    /// Names of the entries whose `attribute` is `value` (or, for an array,
+    /// holds it), in sorted order, using the index created by
+    /// [`create_index`](Self::create_index). No entry is decrypted. Numbers and
+    /// booleans are matched by their JSON text, e.g. `"443"` or `"true"`.
+    ///
+    /// Fails with `InvalidConfig` if `attribute` isn't indexed.
+    pub fn find_by(&self, attribute: &str, value: &str) -> Result<Vec<String>, SerdeVaultError> {
+        let document = self.read()?;
+        let index = document.indexes.get(attribute).ok_or_else(|| {
+            SerdeVaultError::InvalidConfig(format!(
+                "no index on {attribute:?}; create one with create_index()"
+            ))
+        })?;
+        let Some(key) = &document.key else {
+            return Ok(Vec::new());
+        };
+        let Some(slots) = index.get(&key.value_index(attribute, value)) else {
+            return Ok(Vec::new());
+        };
+        let mut names = Vec::with_capacity(slots.len());
+        for slot in slots {
+            let Some(entry) = document.entries.get(slot) else {
+                continue;
+            };
+            names.push(match &entry.name {
+                Some(sealed) if document.blind => key.open_name(sealed, slot)?,
+                _ => slot.clone(),
+            });
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use serde::Serialize;
+    use std::time::Duration;
+    use tempfile::tempdir;
+
+    #[derive(Serialize)]
+    struct Login<'a> {
+        url: &'a str,
+        username: &'a str,
+        tags: &'a [&'a str],
+    }
+
+    #[test]
+    fn test_find_by_indexed_attribute() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("store.svlt"), "pwd").with_params(8, 1, 1);
+        let store = VaultStore::from(vault.clone()).with_blind_names(true);
+        let login = |url, username| Login {
+            url,
+            username,
+            tags: &["web"],
+        };
+        store
+            .put("gh-work", &login("github.com", "me@work"))
+            .unwrap();
+        store.put("gh-home", &login("github.com", "me")).unwrap();
+        store
+            .put_protected("bank", &login("bank.example", "me"), "entry-pwd")
+            .unwrap();
+
+        store.create_index("url").unwrap();
+        store.create_index("tags").unwrap();
+        store.put("gitlab", &login("gitlab.com", "me")).unwrap();
+        assert_eq!(store.indexes().unwrap(), ["tags", "url"]);
+        assert_eq!(
+            store.find_by("url", "github.com").unwrap(),
+            ["gh-home", "gh-work"]
+        );
+        assert_eq!(
+            store.find_by("tags", "web").unwrap(),
+            ["gh-home", "gh-work", "gitlab"]
+        );
+        assert!(store.find_by("url", "bank.example").unwrap().is_empty());
+        assert!(store.find_by("username", "me").is_err());
+
+        // Values are hashed in the document, and writes keep the index current.
+        assert!(!vault.load_value().unwrap().to_string().contains("github"));
+        store
+            .put("gh-work", &login("ghe.example", "me@work"))
+            .unwrap();
+        assert!(store.remove("gh-home").unwrap());
+        assert!(store.find_by("url", "github.com").unwrap().is_empty());
+        assert_eq!(store.find_by("url", "ghe.example").unwrap(), ["gh-work"]);
+
+        assert!(store.drop_index("url").unwrap());
+        assert!(store.find_by("url", "ghe.example").is_err());
+    }
+
+    /// Check the stored indexes against ones rebuilt from the entries.
+    fn assert_consistent(store: &VaultStore) {
+        let document = store.read().unwrap();
+        let mut rebuilt: Indexes = document
+            .indexes
+            .keys()
+            .map(|attribute| (attribute.clone(), BTreeMap::new()))
+            .collect();
+        for (name, entry) in document.named_entries().unwrap() {
+            let slot = document.slot(&name);
+            for (attribute, key) in document.index_keys(&name, entry).unwrap() {
+                let index = rebuilt.get_mut(&attribute).unwrap();
+                index.entry(key).or_default().insert(slot.clone());
+            }
+        }
+        assert_eq!(document.indexes, rebuilt);
+    }
+
+    fn indexed_store(dir: &tempfile::TempDir) -> VaultStore {
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        store.create_index("url").unwrap();
+        store.create_index("tags").unwrap();
+        store
+    }
+
+    #[test]
+    fn test_index_consistent_after_remove_and_overwrite() {
+        let dir = tempdir().unwrap();
+        let store = indexed_store(&dir);
+        let login = |url| Login {
+            url,
+            username: "me",
+            tags: &["web", "work"],
+        };
+        store.put("a", &login("github.com")).unwrap();
+        store.put("b", &login("github.com")).unwrap();
+        assert_consistent(&store);
+
+        assert!(store.remove("a").unwrap());
+        assert_consistent(&store);
+        assert_eq!(store.find_by("url", "github.com").unwrap(), ["b"]);
+        assert_eq!(store.find_by("tags", "work").unwrap(), ["b"]);
+
+        store.put("b", &login("gitlab.com")).unwrap();
+        assert_consistent(&store);
+        assert!(store.find_by("url", "github.com").unwrap().is_empty());
+        assert_eq!(store.find_by("url", "gitlab.com").unwrap(), ["b"]);
+
+        // A value without the attribute, or under a password of its own,
+        // leaves the index.
+        store.put("b", &"plain").unwrap();
+        assert_consistent(&store);
+        assert!(store.find_by("url", "gitlab.com").unwrap().is_empty());
+        assert!(store.find_by("tags", "web").unwrap().is_empty());
+        store.put("c", &login("gitlab.com")).unwrap();
+        store
+            .put_protected("c", &login("gitlab.com"), "entry-pwd")
+            .unwrap();
+        assert_consistent(&store);
+        assert!(store.find_by("url", "gitlab.com").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_consistent_after_expiry() {
+        let dir = tempdir().unwrap();
+        let store = indexed_store(&dir);
+        let login = |url| Login {
+            url,
+            username: "me",
+            tags: &["web"],
+        };
+        store
+            .put_with_ttl("token", &login("api.example"), Duration::ZERO)
+            .unwrap();
+        store
+            .put_with_ttl("other", &login("api.example"), Duration::ZERO)
+            .unwrap();
+        store.put("keep", &login("api.example")).unwrap();
+
+        // Like `list`, lookups report expired entries until they are purged.
+        assert_eq!(
+            store.find_by("url", "api.example").unwrap(),
+            ["keep", "other", "token"]
+        );
+        assert_eq!(store.purge_expired().unwrap(), ["other", "token"]);
+        assert_consistent(&store);
+        assert_eq!(store.find_by("url", "api.example").unwrap(), ["keep"]);
+
+        store
+            .put_with_ttl("token", &login("api.example"), Duration::ZERO)
+            .unwrap();
+        let purging = store.clone().with_purge_expired(true);
+        purging.put("new", &login("new.example")).unwrap();
+        assert_consistent(&store);
+        assert_eq!(store.find_by("url", "api.example").unwrap(), ["keep"]);
+        assert_eq!(store.find_by("tags", "web").unwrap(), ["keep", "new"]);
+    }
+}
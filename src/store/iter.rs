@@ -0,0 +1,99 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use crate::error::SerdeVaultError;
+
+use super::{decode_entry, StoreDocument, VaultStore};
+
+impl VaultStore {
+    /// Iterate over all entries as `(name, value)`, in sorted order.
+    ///
+    /// This is not streaming, and memory is not bounded: the store is read
+    /// once, the whole vault is decrypted and its document, with every entry
+    /// still sealed, is held by the iterator until it is dropped, so memory
+    /// grows with the store, not with one entry. What the iterator defers is
+    /// the per-entry work: each entry is unsealed and parsed only when reached,
+    /// and its plaintext wiped before the next one, so at most one entry's
+    /// value is in the clear at a time. Changes made to the store meanwhile aren't seen. An entry that
+    /// can't be read as `T` (or is expired, or has a password of its own) yields
+    /// an error and iteration continues past it.
+    pub fn iter<T: DeserializeOwned>(&self) -> Result<Entries<T>, SerdeVaultError> {
+        let document = self.read()?;
+        let names: Vec<String> = document
+            .named_entries()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        Ok(Entries {
+            document,
+            names: names.into_iter(),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Iterator over the entries of a store, from [`VaultStore::iter`]. Holds the
+/// whole sealed store document; see there.
+pub struct Entries<T> {
+    document: StoreDocument,
+    names: std::vec::IntoIter<String>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Iterator for Entries<T> {
+    type Item = (String, Result<T, SerdeVaultError>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        let value = self
+            .document
+            .open(&name, None)
+            .and_then(|plaintext| decode_entry(&name, &plaintext));
+        Some((name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+impl<T: DeserializeOwned> ExactSizeIterator for Entries<T> {}
+
+impl<T> fmt::Debug for Entries<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entries")
+            .field("remaining", &self.names.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_iter_yields_each_entry() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        store.put("b", &2u32).unwrap();
+        store.put("a", &1u32).unwrap();
+        store.put("c", &"three").unwrap();
+
+        let mut entries = store.iter::<u32>().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries.next().unwrap().1.unwrap(), 1);
+        assert_eq!(entries.next().unwrap().1.unwrap(), 2);
+        let (name, value) = entries.next().unwrap();
+        assert_eq!(name, "c");
+        assert!(
+            matches!(value, Err(SerdeVaultError::DeserializationError(m)) if m.starts_with("c:"))
+        );
+        assert!(entries.next().is_none());
+    }
+}
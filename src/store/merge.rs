@@ -0,0 +1,107 @@
+use std::path::Path;
+
+use crate::error::SerdeVaultError;
+use crate::vault::VaultFile;
+
+use super::import::{merge_into, ImportPolicy, ImportReport, OnConflict};
+use super::VaultStore;
+
+/// How [`VaultStore::merge`] resolves an entry both stores hold with
+/// different values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Always take the value of `b`, the second store. No times are compared;
+    /// see [`PreferNewer`](Self::PreferNewer) for that.
+    #[default]
+    PreferTheirs,
+    /// Take whichever value was put more recently.
+    PreferNewer,
+    /// Merge nothing and fail with [`SerdeVaultError::EntryExists`].
+    ErrorOnConflict,
+}
+
+impl VaultStore {
+    /// Write the union of the stores at `a` and `b`, both opened with
+    /// `password`, into this store: for when a store was edited as two copies
+    /// on two machines.
+    ///
+    /// This store must be empty (or not exist yet), so that nothing it holds is
+    /// replaced unreported; otherwise the merge fails with `InvalidConfig`. An
+    /// entry left untouched in both copies is not a conflict. Everything is
+    /// written in one save, so a failed merge writes nothing. Returns what was
+    /// taken from `b`.
+    pub fn merge(
+        &self,
+        a: impl AsRef<Path>,
+        b: impl AsRef<Path>,
+        password: &str,
+        strategy: MergeStrategy,
+    ) -> Result<ImportReport, SerdeVaultError> {
+        let a = VaultStore::from(VaultFile::open(a, password)).read()?;
+        let b = VaultStore::from(VaultFile::open(b, password)).read()?;
+        let on_conflict = match strategy {
+            MergeStrategy::PreferTheirs => OnConflict::Overwrite,
+            MergeStrategy::PreferNewer => OnConflict::PreferNewer,
+            MergeStrategy::ErrorOnConflict => OnConflict::Fail,
+        };
+        self.modify(|document| {
+            if !document.entries.is_empty() {
+                return Err(SerdeVaultError::InvalidConfig(format!(
+                    "merge() writes into an empty store, and {} holds {} entries",
+                    self.vault.path().display(),
+                    document.entries.len()
+                )));
+            }
+            let overwrite = ImportPolicy::new().on_conflict(OnConflict::Overwrite);
+            merge_into(document, &a, &overwrite)?;
+            merge_into(document, &b, &ImportPolicy::new().on_conflict(on_conflict))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_merge_two_copies() {
+        let dir = tempdir().unwrap();
+        let store_at = |file: &str| -> VaultStore {
+            VaultFile::open(dir.path().join(file), "pwd")
+                .with_params(8, 1, 1)
+                .into()
+        };
+        let laptop = store_at("laptop.svlt");
+        laptop.put("github", &"token").unwrap();
+        laptop.put("wifi", &"old").unwrap();
+        laptop
+            .vault()
+            .copy_to(dir.path().join("desktop.svlt"), "pwd")
+            .unwrap();
+        let desktop = store_at("desktop.svlt");
+        laptop.put("bank", &"pin").unwrap();
+        desktop.put("wifi", &"new").unwrap();
+        desktop.put("mail", &"imap").unwrap();
+        let paths = (laptop.vault().path(), desktop.vault().path());
+
+        let strict = store_at("strict.svlt");
+        let err = strict.merge(paths.0, paths.1, "pwd", MergeStrategy::ErrorOnConflict);
+        assert!(matches!(err, Err(SerdeVaultError::EntryExists(n)) if n == "wifi"));
+        assert!(!strict.vault().exists());
+
+        let merged = store_at("merged.svlt");
+        let report = merged
+            .merge(paths.0, paths.1, "pwd", MergeStrategy::default())
+            .unwrap();
+        assert_eq!(report.imported, ["mail", "wifi"]);
+        assert_eq!(report.skipped, ["github"]);
+        assert_eq!(merged.list().unwrap(), ["bank", "github", "mail", "wifi"]);
+        assert_eq!(merged.get::<String>("wifi").unwrap(), "new");
+        assert_eq!(merged.get::<String>("github").unwrap(), "token");
+
+        let err = merged.merge(paths.0, paths.1, "pwd", MergeStrategy::PreferTheirs);
+        assert!(matches!(err, Err(SerdeVaultError::InvalidConfig(_))));
+        assert_eq!(merged.get::<String>("wifi").unwrap(), "new");
+    }
+}
@@ -0,0 +1,339 @@
+use blake2::digest::consts::U32;
+use blake2::{Blake2b, Digest};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::cipher::{open, seal};
+use crate::crypto::kdf::KEY_SIZE;
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+use crate::wipe::SecretValue;
+
+use super::entry::base64;
+use super::{Entry, VaultStore};
+
+const SALT_LEN: usize = 16;
+
+/// A salted digest of an entry's value, taken when the value is put so that
+/// roots and proofs don't need to decrypt anything. The salt keeps the root
+/// from revealing guessable values.
+///
+/// The salt of a protected entry is sealed under the entry's data key: with it
+/// in the clear, anyone holding the vault password could test guesses of the
+/// value against the digest without the entry password.
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct ValueDigest {
+    #[serde(with = "base64")]
+    salt: Vec<u8>,
+    #[serde(with = "base64")]
+    digest: Vec<u8>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    sealed_salt: bool,
+}
+
+impl ValueDigest {
+    /// The digest of the JSON-encoded value `plaintext` of the entry `name`
+    /// under a fresh salt, sealed under `data_key` if given.
+    pub(super) fn of(
+        plaintext: &[u8],
+        name: &str,
+        data_key: Option<&Zeroizing<[u8; KEY_SIZE]>>,
+    ) -> Result<Self, SerdeVaultError> {
+        let value: SecretValue = Format::Json.deserialize(plaintext)?;
+        let mut salt = vec![0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let digest = value_digest(&salt, &*value)?.to_vec();
+        match data_key {
+            Some(key) => Ok(Self {
+                salt: seal(&salt, key, name.as_bytes())?,
+                digest,
+                sealed_salt: true,
+            }),
+            None => Ok(Self {
+                salt,
+                digest,
+                sealed_salt: false,
+            }),
+        }
+    }
+
+    /// Whether the salt can only be read with the entry's data key.
+    pub(super) fn is_sealed(&self) -> bool {
+        self.sealed_salt
+    }
+
+    /// The salt of the entry `name`, opened with `data_key` if it is sealed.
+    pub(super) fn salt(
+        &self,
+        name: &str,
+        data_key: Option<&Zeroizing<[u8; KEY_SIZE]>>,
+    ) -> Result<Vec<u8>, SerdeVaultError> {
+        match (self.sealed_salt, data_key) {
+            (false, _) => Ok(self.salt.clone()),
+            (true, Some(key)) => Ok(open(&self.salt, key, name.as_bytes())?.to_vec()),
+            (true, None) => Err(SerdeVaultError::EntryProtected(name.to_string())),
+        }
+    }
+}
+
+/// Evidence that an entry belongs to the store whose
+/// [`merkle_root`](VaultStore::merkle_root) it was made against, from
+/// [`VaultStore::prove`].
+///
+/// A proof reveals nothing about the other entries, so it can travel alongside
+/// a single entry exported with [`export_entry`](VaultStore::export_entry).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof {
+    #[serde(with = "base64")]
+    salt: Vec<u8>,
+    path: Vec<ProofStep>,
+}
+
+/// The hash next to ours on the way up to the root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct ProofStep {
+    #[serde(with = "base64")]
+    sibling: Vec<u8>,
+    /// Whether the sibling comes first.
+    left: bool,
+}
+
+impl MerkleProof {
+    /// Whether the entry `name` holding `value` was in the store when it had
+    /// `root`.
+    ///
+    /// Values are compared as JSON, regardless of field order, so `value` only
+    /// has to serialize to what was put, e.g. as the `serde_json::Value` an
+    /// imported entry reads back as.
+    pub fn verify<T: Serialize>(
+        &self,
+        root: &[u8; 32],
+        name: &str,
+        value: &T,
+    ) -> Result<bool, SerdeVaultError> {
+        let digest = value_digest(&self.salt, value)?;
+        let mut hash = leaf(name, &digest);
+        for step in &self.path {
+            let sibling: [u8; 32] = match step.sibling.as_slice().try_into() {
+                Ok(sibling) => sibling,
+                Err(_) => return Ok(false),
+            };
+            hash = match step.left {
+                true => node(&sibling, &hash),
+                false => node(&hash, &sibling),
+            };
+        }
+        Ok(hash == *root)
+    }
+}
+
+impl VaultStore {
+    /// The root of a Merkle tree over every entry's name and value, which
+    /// changes whenever an entry is put or removed. Publish or keep it to later
+    /// check [proofs](Self::prove) against this state of the store. Doesn't
+    /// decrypt any entry.
+    ///
+    /// The root of an empty store is all zeroes.
+    pub fn merkle_root(&self) -> Result<[u8; 32], SerdeVaultError> {
+        let leaves = leaves(&self.read()?.named_entries()?)?;
+        Ok(levels(leaves).last().map_or([0; 32], |top| top[0]))
+    }
+
+    /// A proof that the entry `name`, with its current value, is part of the
+    /// store's current [`merkle_root`](Self::merkle_root). Fails with
+    /// [`SerdeVaultError::EntryProtected`] for entries with a password of their
+    /// own, see [`prove_protected`](Self::prove_protected).
+    pub fn prove(&self, name: &str) -> Result<MerkleProof, SerdeVaultError> {
+        self.prove_inner(name, None)
+    }
+
+    /// [`prove`](Self::prove) for an entry stored by
+    /// [`put_protected`](Self::put_protected), whose digest salt is only
+    /// readable with its entry password.
+    pub fn prove_protected(
+        &self,
+        name: &str,
+        entry_password: &str,
+    ) -> Result<MerkleProof, SerdeVaultError> {
+        self.prove_inner(name, Some(entry_password))
+    }
+
+    fn prove_inner(
+        &self,
+        name: &str,
+        password: Option<&str>,
+    ) -> Result<MerkleProof, SerdeVaultError> {
+        let document = self.read()?;
+        let entries = document.named_entries()?;
+        let Some(mut index) = entries.iter().position(|(n, _)| n == name) else {
+            return Err(SerdeVaultError::EntryNotFound(name.to_string()));
+        };
+        let digest = digest_of(name, entries[index].1)?;
+        let salt = match digest.is_sealed() {
+            true => {
+                let data_key = entries[index]
+                    .1
+                    .sealed
+                    .data_key(document.key()?, name, password)?;
+                digest.salt(name, Some(&data_key))?
+            }
+            false => digest.salt(name, None)?,
+        };
+        let mut path = Vec::new();
+        for level in levels(leaves(&entries)?) {
+            if let Some(sibling) = level.get(index ^ 1) {
+                path.push(ProofStep {
+                    sibling: sibling.to_vec(),
+                    left: index % 2 == 1,
+                });
+            }
+            index /= 2;
+        }
+        Ok(MerkleProof { salt, path })
+    }
+}
+
+fn digest_of<'a>(name: &str, entry: &'a Entry) -> Result<&'a ValueDigest, SerdeVaultError> {
+    entry.sealed.digest().ok_or_else(|| {
+        SerdeVaultError::InvalidFormat(format!("{name} has no digest; put it again"))
+    })
+}
+
+fn leaves(entries: &[(String, &Entry)]) -> Result<Vec<[u8; 32]>, SerdeVaultError> {
+    entries
+        .iter()
+        .map(|(name, entry)| {
+            let digest = digest_of(name, entry)?;
+            Ok(leaf(name, &digest.digest))
+        })
+        .collect()
+}
+
+/// Every level of the tree over `leaves`, from the leaves up to the root. A
+/// hash without a sibling moves up a level unchanged.
+fn levels(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = Vec::new();
+    let mut level = leaves;
+    while level.len() > 1 {
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node(left, right),
+                [single] => *single,
+                _ => unreachable!("chunks of two"),
+            })
+            .collect();
+        levels.push(std::mem::replace(&mut level, next));
+    }
+    if !level.is_empty() {
+        levels.push(level);
+    }
+    levels
+}
+
+/// Distinct prefixes keep values, leaves and nodes from being passed off as
+/// one another.
+fn hash(prefix: u8, parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Blake2b::<U32>::new();
+    hasher.update([prefix]);
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+fn value_digest<T: Serialize>(salt: &[u8], value: &T) -> Result<[u8; 32], SerdeVaultError> {
+    let canonical = Format::CanonicalJson.serialize(value)?;
+    Ok(hash(0, &[salt, &canonical]))
+}
+
+fn leaf(name: &str, digest: &[u8]) -> [u8; 32] {
+    hash(
+        1,
+        &[&(name.len() as u64).to_le_bytes(), name.as_bytes(), digest],
+    )
+}
+
+fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash(2, &[left, right])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use base64ct::{Base64, Encoding};
+    use serde_json::{json, Value};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_protected_digest_needs_entry_password() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("store.svlt"), "pwd").with_params(8, 1, 1);
+        let store = VaultStore::from(vault.clone());
+        store.put_protected("pin", &1234, "entry-pwd").unwrap();
+
+        // The document alone doesn't allow testing guesses against the digest.
+        let document = vault.load_value().unwrap();
+        let digest = &document["entries"]["pin"]["digest"];
+        let decode = |v: &Value| Base64::decode_vec(v.as_str().unwrap()).unwrap();
+        let (salt, stored) = (decode(&digest["salt"]), decode(&digest["digest"]));
+        assert_ne!(value_digest(&salt, &1234).unwrap().to_vec(), stored);
+
+        assert!(matches!(
+            store.prove("pin"),
+            Err(SerdeVaultError::EntryProtected(_))
+        ));
+        assert!(store.prove_protected("pin", "wrong").is_err());
+        let proof = store.prove_protected("pin", "entry-pwd").unwrap();
+        assert!(proof
+            .verify(&store.merkle_root().unwrap(), "pin", &1234)
+            .unwrap());
+    }
+
+    #[test]
+    fn test_proofs_verify_against_root() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        assert_eq!(store.merkle_root().unwrap(), [0; 32]);
+        for (name, value) in [("a", 1), ("b", 2), ("c", 3), ("d", 4), ("e", 5)] {
+            store
+                .put(name, &json!({ "id": value, "name": name }))
+                .unwrap();
+        }
+        let root = store.merkle_root().unwrap();
+        for name in store.list().unwrap() {
+            let value: Value = store.get(&name).unwrap();
+            assert!(store
+                .prove(&name)
+                .unwrap()
+                .verify(&root, &name, &value)
+                .unwrap());
+        }
+
+        // Shared with the entry, the proof checks out on the other side.
+        let proof = store.prove("c").unwrap();
+        let shared = store.export_entry("c", "for-bob").unwrap();
+        let bob: VaultStore = VaultFile::open(dir.path().join("bob.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        bob.import_entry(&shared, "for-bob").unwrap();
+        let value: Value = bob.get("c").unwrap();
+        assert!(proof.verify(&root, "c", &value).unwrap());
+        assert!(!proof
+            .verify(&root, "c", &json!({ "id": 4, "name": "c" }))
+            .unwrap());
+        assert!(!proof.verify(&root, "d", &value).unwrap());
+
+        store.put("e", &6).unwrap();
+        assert_ne!(store.merkle_root().unwrap(), root);
+        assert!(!store
+            .prove("c")
+            .unwrap()
+            .verify(&root, "c", &value)
+            .unwrap());
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeSet;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SerdeVaultError;
+
+use super::entry::SealedEntry;
+use super::VaultStore;
+
+/// Everything known about an entry without decrypting it, as returned by
+/// [`VaultStore::metadata`] and [`VaultStore::list_metadata`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    /// When the entry was first put.
+    pub created: SystemTime,
+    /// When the entry's value was last put.
+    pub modified: SystemTime,
+    /// A display name, set with [`VaultStore::set_label`].
+    pub label: Option<String>,
+    /// Set with [`VaultStore::set_tags`].
+    pub tags: BTreeSet<String>,
+    /// Whether the entry has a password of its own.
+    pub protected: bool,
+    /// When the entry expires, if it was put with a TTL.
+    pub expires: Option<SystemTime>,
+}
+
+impl EntryMetadata {
+    /// Whether the entry's TTL has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|t| t <= SystemTime::now())
+    }
+}
+
+/// An entry in the store's document: the sealed value and its metadata.
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct Entry {
+    #[serde(flatten)]
+    pub(super) sealed: SealedEntry,
+    /// The entry's name, encrypted, when the document is keyed by blind index.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "option_base64"
+    )]
+    pub(super) name: Option<Vec<u8>>,
+    /// Seconds since the Unix epoch.
+    #[serde(default)]
+    created: u64,
+    #[serde(default)]
+    pub(super) modified: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    tags: BTreeSet<String>,
+    /// Seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) expires: Option<u64>,
+    /// The document's use counter when the entry was last put or read.
+    #[serde(default)]
+    pub(super) accessed: u64,
+}
+
+impl Entry {
+    /// `sealed` as a new value for `previous`, keeping its creation time, label
+    /// and tags. The new value doesn't expire.
+    pub(super) fn replacing(previous: Option<Entry>, sealed: SealedEntry) -> Self {
+        let now = now_secs();
+        match previous {
+            Some(previous) => Self {
+                sealed,
+                modified: now,
+                expires: None,
+                ..previous
+            },
+            None => Self {
+                sealed,
+                name: None,
+                created: now,
+                modified: now,
+                label: None,
+                tags: BTreeSet::new(),
+                expires: None,
+                accessed: 0,
+            },
+        }
+    }
+
+    /// This entry's metadata around another sealed value.
+    pub(super) fn with_sealed(&self, sealed: SealedEntry) -> Self {
+        Self {
+            sealed,
+            name: None,
+            ..self.clone()
+        }
+    }
+
+    pub(super) fn metadata(&self) -> EntryMetadata {
+        EntryMetadata {
+            created: UNIX_EPOCH + Duration::from_secs(self.created),
+            modified: UNIX_EPOCH + Duration::from_secs(self.modified),
+            label: self.label.clone(),
+            tags: self.tags.clone(),
+            protected: self.sealed.is_protected(),
+            expires: self.expires.map(|t| UNIX_EPOCH + Duration::from_secs(t)),
+        }
+    }
+
+    /// Make the entry expire `ttl` from now.
+    pub(super) fn expire_after(&mut self, ttl: Duration) {
+        self.expires = Some(now_secs().saturating_add(ttl.as_secs()));
+    }
+
+    pub(super) fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|t| t <= now_secs())
+    }
+}
+
+impl VaultStore {
+    /// Timestamps, label and tags of the entry `name`. Doesn't decrypt the entry.
+    pub fn metadata(&self, name: &str) -> Result<EntryMetadata, SerdeVaultError> {
+        Ok(self.read()?.entry(name)?.metadata())
+    }
+
+    /// Every entry's name and metadata, sorted by name, for rendering lists
+    /// without decrypting any entry.
+    pub fn list_metadata(&self) -> Result<Vec<(String, EntryMetadata)>, SerdeVaultError> {
+        let document = self.read()?;
+        Ok(document
+            .named_entries()?
+            .into_iter()
+            .map(|(name, entry)| (name, entry.metadata()))
+            .collect())
+    }
+
+    /// Replace the tags of the entry `name`. Doesn't change its `modified` time.
+    pub fn set_tags<I, S>(&self, name: &str, tags: I) -> Result<(), SerdeVaultError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let tags = tags.into_iter().map(Into::into).collect();
+        self.modify(|document| {
+            document.entry_mut(name)?.tags = tags;
+            Ok(())
+        })
+    }
+
+    /// Set or clear the display label of the entry `name`. Doesn't change its
+    /// `modified` time.
+    pub fn set_label(&self, name: &str, label: Option<&str>) -> Result<(), SerdeVaultError> {
+        self.modify(|document| {
+            document.entry_mut(name)?.label = label.map(str::to_string);
+            Ok(())
+        })
+    }
+}
+
+mod option_base64 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use super::super::entry::base64;
+
+    pub fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match bytes {
+            Some(bytes) => base64::serialize(bytes, s),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        #[derive(Deserialize)]
+        struct Wrapped(#[serde(with = "base64")] Vec<u8>);
+        Ok(Option::<Wrapped>::deserialize(d)?.map(|Wrapped(bytes)| bytes))
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_metadata_survives_puts() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        let before = SystemTime::now() - Duration::from_secs(1);
+        store.put("github", &"token").unwrap();
+        store.set_tags("github", ["work", "dev"]).unwrap();
+        store.set_label("github", Some("GitHub")).unwrap();
+        store.put_protected("codes", &[1, 2], "entry-pwd").unwrap();
+
+        let first = store.metadata("github").unwrap();
+        assert!(first.created >= before && first.modified >= first.created);
+        assert_eq!(first.label.as_deref(), Some("GitHub"));
+        assert!(!first.protected);
+
+        store.put("github", &"new token").unwrap();
+        let second = store.metadata("github").unwrap();
+        assert_eq!(second.created, first.created);
+        assert_eq!(second.tags, first.tags);
+
+        let listed = store.list_metadata().unwrap();
+        let names: Vec<_> = listed.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["codes", "github"]);
+        assert!(listed[0].1.protected && listed[0].1.tags.is_empty());
+        assert!(matches!(
+            store.set_tags("missing", ["x"]),
+            Err(SerdeVaultError::EntryNotFound(_))
+        ));
+    }
+}
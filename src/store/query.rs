@@ -0,0 +1,107 @@
+use std::collections::BTreeSet;
+use std::time::SystemTime;
+
+use crate::error::SerdeVaultError;
+
+use super::metadata::EntryMetadata;
+use super::VaultStore;
+
+/// Conditions on entry names and metadata, for [`VaultStore::find`]. An entry
+/// matches if it meets all of them; an empty query matches every entry.
+///
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use serdevault::store::Query;
+///
+/// let recent_work = Query::new()
+///     .tag("work")
+///     .prefix("aws/")
+///     .modified_since(SystemTime::now() - Duration::from_secs(7 * 86_400));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    tags: BTreeSet<String>,
+    prefix: Option<String>,
+    modified_since: Option<SystemTime>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only entries tagged `tag`. Repeat to require several tags.
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.insert(tag.into());
+        self
+    }
+
+    /// Only entries whose name starts with `prefix`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Only entries whose value was put at or after `time`.
+    pub fn modified_since(mut self, time: SystemTime) -> Self {
+        self.modified_since = Some(time);
+        self
+    }
+
+    fn matches(&self, name: &str, metadata: &EntryMetadata) -> bool {
+        self.prefix
+            .as_ref()
+            .is_none_or(|p| name.starts_with(p.as_str()))
+            && self.modified_since.is_none_or(|t| metadata.modified >= t)
+            && self.tags.is_subset(&metadata.tags)
+    }
+}
+
+impl VaultStore {
+    /// Names of the entries matching `query`, in sorted order. Only metadata is
+    /// looked at; no entry is decrypted.
+    pub fn find(&self, query: &Query) -> Result<Vec<String>, SerdeVaultError> {
+        Ok(self
+            .list_metadata()?
+            .into_iter()
+            .filter(|(name, metadata)| query.matches(name, metadata))
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_find_by_tag_prefix_and_time() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        for name in ["aws/prod", "aws/dev", "github"] {
+            store.put(name, &name).unwrap();
+        }
+        store.set_tags("aws/prod", ["work", "critical"]).unwrap();
+        store.set_tags("aws/dev", ["work"]).unwrap();
+        store.set_tags("github", ["work"]).unwrap();
+
+        assert_eq!(store.find(&Query::new()).unwrap().len(), 3);
+        assert_eq!(
+            store.find(&Query::new().prefix("aws/")).unwrap(),
+            ["aws/dev", "aws/prod"]
+        );
+        let critical = Query::new().tag("work").tag("critical");
+        assert_eq!(store.find(&critical).unwrap(), ["aws/prod"]);
+        let future = SystemTime::now() + Duration::from_secs(60);
+        assert!(store
+            .find(&Query::new().modified_since(future))
+            .unwrap()
+            .is_empty());
+    }
+}
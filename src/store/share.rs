@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+use crate::wipe::SecretValue;
+
+use super::VaultStore;
+
+/// The payload of an exported entry.
+#[derive(Serialize, Deserialize)]
+struct SharedEntry {
+    name: String,
+    value: SecretValue,
+}
+
+impl VaultStore {
+    /// Export the entry `name` as a standalone vault encrypted under
+    /// `recipient_password`, for sharing exactly one secret.
+    ///
+    /// The result is a complete vault file (with this store's Argon2
+    /// parameters) that holds only the entry's name and value; nothing else
+    /// about the store is revealed. Read it back with
+    /// [`import_entry`](Self::import_entry). Entries with a password of their
+    /// own can't be exported.
+    pub fn export_entry(
+        &self,
+        name: &str,
+        recipient_password: &str,
+    ) -> Result<Vec<u8>, SerdeVaultError> {
+        let plaintext = self.read()?.open(name, None)?;
+        let value: SecretValue = serde_json::from_slice(&plaintext)
+            .map_err(|e| SerdeVaultError::DeserializationError(format!("{name}: {e}")))?;
+        let shared = SharedEntry {
+            name: name.to_string(),
+            value,
+        };
+        let payload = Format::Json.serialize(&shared)?;
+        self.vault
+            .seal_blob(recipient_password, Format::Json, &payload)
+    }
+
+    /// Store the entry exported by [`export_entry`](Self::export_entry) as
+    /// `shared`, under its original name, and return that name. An entry of the
+    /// same name is replaced, as by [`put`](Self::put).
+    ///
+    /// A wrong password fails with `DecryptionFailed`.
+    pub fn import_entry(&self, shared: &[u8], password: &str) -> Result<String, SerdeVaultError> {
+        let (format, payload) = self.vault.open_blob_with(password, shared)?;
+        let shared: SharedEntry = format.deserialize(&payload)?;
+        self.put(&shared.name, &*shared.value)?;
+        Ok(shared.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_and_import_one_entry() {
+        let dir = tempdir().unwrap();
+        let store_at = |file: &str| -> VaultStore {
+            VaultFile::open(dir.path().join(file), "pwd")
+                .with_params(8, 1, 1)
+                .into()
+        };
+        let alice = store_at("alice.svlt");
+        alice.put("github", &("me", "hunter2")).unwrap();
+        alice.put("bank", &"secret").unwrap();
+
+        let shared = alice.export_entry("github", "for-bob").unwrap();
+        assert!(!shared
+            .windows(7)
+            .any(|w| w == b"hunter2" || w == b"github\""));
+
+        let bob = store_at("bob.svlt");
+        assert!(matches!(
+            bob.import_entry(&shared, "wrong"),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+        assert_eq!(bob.import_entry(&shared, "for-bob").unwrap(), "github");
+        assert_eq!(
+            bob.get::<(String, String)>("github").unwrap(),
+            ("me".into(), "hunter2".into())
+        );
+        assert_eq!(bob.list().unwrap(), ["github"]);
+    }
+}
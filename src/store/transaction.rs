@@ -0,0 +1,197 @@
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+
+use super::entry::EntryPassword;
+use super::{decode_entry, StoreDocument, VaultStore};
+
+/// Changes to a store that are written together, see
+/// [`VaultStore::transaction`].
+///
+/// Reads see the transaction's own changes.
+pub struct Transaction<'a> {
+    document: &'a mut StoreDocument,
+    params: (u32, u32, u32),
+}
+
+impl Transaction<'_> {
+    /// As [`VaultStore::put`].
+    pub fn put<T: Serialize>(&mut self, name: &str, value: &T) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(value)?;
+        self.document.insert(name, &plaintext, None).map(drop)
+    }
+
+    /// As [`VaultStore::put_protected`].
+    pub fn put_protected<T: Serialize>(
+        &mut self,
+        name: &str,
+        value: &T,
+        entry_password: &str,
+    ) -> Result<(), SerdeVaultError> {
+        let plaintext = Format::Json.serialize(value)?;
+        let password = EntryPassword {
+            password: entry_password,
+            params: self.params,
+        };
+        self.document
+            .insert(name, &plaintext, Some(password))
+            .map(drop)
+    }
+
+    /// As [`VaultStore::get`].
+    pub fn get<T: DeserializeOwned>(&self, name: &str) -> Result<T, SerdeVaultError> {
+        decode_entry(name, &self.document.open(name, None)?)
+    }
+
+    /// As [`VaultStore::get_protected`].
+    pub fn get_protected<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        entry_password: &str,
+    ) -> Result<T, SerdeVaultError> {
+        decode_entry(name, &self.document.open(name, Some(entry_password))?)
+    }
+
+    /// As [`VaultStore::remove`].
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.document.remove(name).is_some()
+    }
+
+    /// Whether there is an entry `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.document.entry(name).is_ok()
+    }
+
+    /// As [`VaultStore::list`].
+    pub fn list(&self) -> Result<Vec<String>, SerdeVaultError> {
+        Ok(self
+            .document
+            .named_entries()?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+}
+
+impl fmt::Debug for Transaction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transaction").finish_non_exhaustive()
+    }
+}
+
+impl VaultStore {
+    /// Run `f` on the store and write all its changes in one atomic write, or,
+    /// if it fails, none of them:
+    ///
+    /// ```no_run
+    /// # let store = serdevault::VaultStore::open("store.vault", "pwd");
+    /// store.transaction(|tx| {
+    ///     let balance: u64 = tx.get("checking")?;
+    ///     tx.put("checking", &(balance - 100))?;
+    ///     tx.put("savings", &100u64)?;
+    ///     tx.remove("pending-transfer");
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), serdevault::SerdeVaultError>(())
+    /// ```
+    ///
+    /// The update lock is held throughout, so other writers wait for `f`; keep
+    /// it short.
+    pub fn transaction<R>(
+        &self,
+        f: impl FnOnce(&mut Transaction<'_>) -> Result<R, SerdeVaultError>,
+    ) -> Result<R, SerdeVaultError> {
+        let params = self.vault.argon2_params();
+        self.modify(|document| f(&mut Transaction { document, params }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Eviction;
+    use crate::VaultFile;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_transaction_is_all_or_nothing() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        store.put("a", &1u32).unwrap();
+        store.put("b", &2u32).unwrap();
+
+        let err = store
+            .transaction(|tx| {
+                tx.put("c", &3u32)?;
+                assert!(tx.remove("a"));
+                assert_eq!(tx.list()?, ["b", "c"]);
+                tx.get::<u32>("a")
+            })
+            .unwrap_err();
+        assert!(matches!(err, SerdeVaultError::EntryNotFound(ref n) if n == "a"));
+        assert_eq!(store.list().unwrap(), ["a", "b"]);
+
+        let sum = store
+            .transaction(|tx| {
+                let sum = tx.get::<u32>("a")? + tx.get::<u32>("b")?;
+                tx.put("sum", &sum)?;
+                tx.remove("a");
+                Ok(sum)
+            })
+            .unwrap();
+        assert_eq!(sum, 3);
+        assert_eq!(store.list().unwrap(), ["b", "sum"]);
+        assert_eq!(store.get::<u32>("sum").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_panic() {
+        let dir = tempdir().unwrap();
+        let store: VaultStore = VaultFile::open(dir.path().join("store.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .into();
+        store.put("a", &1u32).unwrap();
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            store.transaction(|tx| -> Result<(), SerdeVaultError> {
+                tx.put("b", &2u32)?;
+                tx.remove("a");
+                panic!("inside the transaction")
+            })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(store.list().unwrap(), ["a"]);
+        // The update lock was released on the way out.
+        store.put("b", &2u32).unwrap();
+        assert_eq!(store.list().unwrap(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_transaction_writes_nothing_when_commit_fails() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("store.svlt");
+        let store: VaultStore = VaultFile::open(&path, "pwd").with_params(8, 1, 1).into();
+        store.put("a", &1u32).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let capped = store
+            .clone()
+            .with_size_cap(store.size().unwrap(), Eviction::RejectWrites);
+        let err = capped
+            .transaction(|tx| {
+                tx.remove("a");
+                tx.put("b", &2u32)?;
+                tx.put("c", &"x".repeat(100))
+            })
+            .unwrap_err();
+        assert!(matches!(err, SerdeVaultError::StoreFull { .. }));
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        assert_eq!(store.list().unwrap(), ["a"]);
+    }
+}
@@ -0,0 +1,136 @@
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::SerdeVaultError;
+use crate::vault::{EditGuard, VaultFile};
+
+/// A [`VaultFile`] bound to a single payload type.
+///
+/// `load` needs no turbofish, and `save` only accepts `&T`, so a vault can't
+/// accidentally be written with one type and read back as another.
+///
+/// # Example
+///
+/// ```no_run
+/// use serdevault::TypedVaultFile;
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct MyData { value: String }
+///
+/// let vault: TypedVaultFile<MyData> = TypedVaultFile::open("~/.my.vault", "my_password");
+/// vault.save(&MyData { value: "hello".into() }).unwrap();
+/// let loaded = vault.load().unwrap();
+/// ```
+pub struct TypedVaultFile<T> {
+    inner: VaultFile,
+    // fn() -> T: the handle never owns a T, so it stays Send/Sync regardless of T.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> TypedVaultFile<T> {
+    /// Open (or prepare to create) a typed vault at the given path.
+    ///
+    /// Use [`VaultFile::typed`] instead to keep non-default settings.
+    pub fn open(path: impl AsRef<Path>, password: &str) -> Self {
+        VaultFile::open(path, password).typed()
+    }
+
+    /// Encrypt `data` and write it to the vault file atomically.
+    pub fn save(&self, data: &T) -> Result<(), SerdeVaultError> {
+        self.inner.save(data)
+    }
+
+    /// See [`VaultFile::save_if_changed`].
+    pub fn save_if_changed(&self, data: &T) -> Result<bool, SerdeVaultError> {
+        self.inner.save_if_changed(data)
+    }
+
+    /// Read the vault file, decrypt it, and deserialize it as `T`.
+    pub fn load(&self) -> Result<T, SerdeVaultError> {
+        self.inner.load()
+    }
+
+    /// See [`VaultFile::load_opt`].
+    pub fn load_opt(&self) -> Result<Option<T>, SerdeVaultError> {
+        self.inner.load_opt()
+    }
+
+    /// See [`VaultFile::load_or_init`].
+    pub fn load_or_init(&self, init: impl FnOnce() -> T) -> Result<T, SerdeVaultError> {
+        self.inner.load_or_init(init)
+    }
+
+    /// See [`VaultFile::update`].
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, SerdeVaultError> {
+        self.inner.update(f)
+    }
+
+    /// See [`VaultFile::edit`].
+    pub fn edit(&self) -> Result<EditGuard<'_, T>, SerdeVaultError> {
+        self.inner.edit()
+    }
+
+    /// Whether the vault file exists on disk.
+    pub fn exists(&self) -> bool {
+        self.inner.exists()
+    }
+
+    /// The underlying untyped handle.
+    pub fn inner(&self) -> &VaultFile {
+        &self.inner
+    }
+
+    /// Drop the type binding and return the untyped handle.
+    pub fn into_inner(self) -> VaultFile {
+        self.inner
+    }
+}
+
+impl<T> From<VaultFile> for TypedVaultFile<T> {
+    fn from(inner: VaultFile) -> Self {
+        Self {
+            inner,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl VaultFile {
+    /// Bind this handle to the payload type `T`.
+    pub fn typed<T: Serialize + DeserializeOwned>(self) -> TypedVaultFile<T> {
+        self.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        url: String,
+        retries: u8,
+    }
+
+    #[test]
+    fn test_typed_roundtrip() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .typed::<Config>();
+        let data = Config {
+            url: "https://example.com".into(),
+            retries: 3,
+        };
+
+        assert!(!vault.exists());
+        vault.save(&data).unwrap();
+        assert_eq!(vault.load().unwrap(), data);
+    }
+}
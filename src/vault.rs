@@ -1,14 +1,60 @@
-use std::env;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use rand::{rngs::OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use zeroize::Zeroizing;
 
-use crate::crypto::cipher::{decrypt, encrypt};
-use crate::crypto::kdf::{derive_key, ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST, SALT_SIZE};
-use crate::error::SerdeVaultError;
-use crate::format::{atomic_write, decode, encode, VaultHeader};
+use crate::artifacts;
+use crate::backup;
+use crate::crypto::cipher::{decrypt, encrypt, random_nonce};
+use crate::crypto::kdf::{
+    derive_key, ARGON2_M_COST, ARGON2_P_COST, ARGON2_T_COST, KEY_SIZE, SALT_SIZE,
+};
+use crate::crypto::stream::decrypt_chunked;
+use crate::error::{ErrorKind, Operation, SerdeVaultError};
+use crate::format::{
+    atomic_write, decode, encode, VaultHeader, WriteOptions, FORMAT_VERSION, LEGACY_VERSION,
+};
+use crate::lock::VaultLock;
+use crate::migration::{Migrations, Representations};
+use crate::paths;
+use crate::permissions;
+use crate::serializer::Format;
+use crate::shred::shred;
+use crate::wipe::SecretValue;
+
+mod cache;
+mod convert;
+mod deterministic;
+mod edit;
+mod env;
+mod export;
+mod generation;
+mod history;
+mod lint;
+mod locking;
+mod merge;
+mod records;
+mod rename;
+mod secret;
+mod session;
+mod shape;
+mod size;
+mod snapshot;
+mod stream;
+mod symlink;
+mod value;
+
+pub use cache::VaultCache;
+pub use edit::EditGuard;
+pub use env::EnvGuard;
+pub use history::VersionInfo;
+pub use merge::MergeConflict;
+pub use records::Records;
+pub use secret::VaultSecret;
+pub use session::{LockedVault, UnlockedVault};
+pub use symlink::SymlinkPolicy;
 
 /// A handle to an encrypted vault file.
 ///
@@ -28,6 +74,7 @@ use crate::format::{atomic_write, decode, encode, VaultHeader};
 /// vault.save(&MyData { value: "hello".into() }).unwrap();
 /// let loaded: MyData = vault.load().unwrap();
 /// ```
+#[derive(Clone)]
 pub struct VaultFile {
     path: PathBuf,
     password: Zeroizing<String>,
@@ -35,19 +82,68 @@ pub struct VaultFile {
     m_cost: u32,
     t_cost: u32,
     p_cost: u32,
+    write_opts: WriteOptions,
+    strict_permissions: bool,
+    format: Format,
+    /// Number of timestamped backups kept by `save`; 0 disables them.
+    backups: usize,
+    /// Number of revisions kept in the history sidecar; 0 disables it.
+    history: usize,
+    read_only: bool,
+    streaming: bool,
+    deterministic: bool,
+    schema_hash: bool,
+    symlinks: SymlinkPolicy,
+    migrations: Option<Migrations>,
+    cache: Option<VaultCache>,
+    binary_lint: Option<lint::BinaryLint>,
+    /// How long to wait for the advisory lock; `None` waits indefinitely.
+    lock_timeout: Option<Duration>,
+    locked_loads: bool,
 }
 
 impl VaultFile {
     /// Open (or prepare to create) a vault at the given path.
     ///
+    /// A leading `~/` and `${VAR}` references (plus `%VAR%` on Windows) are
+    /// expanded, e.g. `${XDG_CONFIG_HOME}/myapp/vault.svlt`. If the
+    /// `SERDEVAULT_DIR` environment variable is set, relative and `~/` paths
+    /// resolve inside that directory instead — handy for test suites and containers.
+    ///
     /// No I/O is performed — the file is only read on `load` and written on `save`.
     pub fn open(path: impl AsRef<Path>, password: &str) -> Self {
         Self {
-            path: expand_tilde(path.as_ref()),
+            path: paths::expand(path.as_ref()),
             password: Zeroizing::new(password.to_owned()),
             m_cost: ARGON2_M_COST,
             t_cost: ARGON2_T_COST,
             p_cost: ARGON2_P_COST,
+            write_opts: WriteOptions::default(),
+            strict_permissions: false,
+            format: Format::default(),
+            backups: 0,
+            history: 0,
+            read_only: false,
+            streaming: false,
+            deterministic: false,
+            schema_hash: false,
+            symlinks: SymlinkPolicy::default(),
+            migrations: None,
+            cache: None,
+            binary_lint: None,
+            lock_timeout: None,
+            locked_loads: false,
+        }
+    }
+
+    /// Open a vault that this handle must never modify.
+    ///
+    /// `save`, `update`, `restore`, `rename`, `delete` and every other call that
+    /// would change the vault or its files return [`SerdeVaultError::ReadOnly`].
+    pub fn open_read_only(path: impl AsRef<Path>, password: &str) -> Self {
+        Self {
+            read_only: true,
+            ..Self::open(path, password)
         }
     }
 
@@ -61,71 +157,567 @@ impl VaultFile {
         self
     }
 
+    /// Enable or disable fsyncing the parent directory after each save (on by default).
+    ///
+    /// Without it, a crash right after `save` may roll the file back to its previous
+    /// contents. Disabling trades that guarantee for fewer syncs, e.g. in tests.
+    pub fn with_dir_sync(mut self, enabled: bool) -> Self {
+        self.write_opts.sync_dir = enabled;
+        self
+    }
+
+    /// Encoding used for the plaintext when saving (JSON by default).
+    ///
+    /// The format is recorded in the header, and `load` decodes with whichever
+    /// format the vault was saved in, so changing this doesn't strand existing files.
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Keep the `keep` most recent versions of the file as backups.
+    ///
+    /// Before every write, the current vault is preserved as
+    /// `<name>.bak-<UTC timestamp>` next to it and older backups beyond `keep`
+    /// are deleted. Backups are the encrypted files as they were, so each one
+    /// opens with the password it was saved under. 0 (the default) disables this.
+    pub fn with_backups(mut self, keep: usize) -> Self {
+        self.backups = keep;
+        self
+    }
+
+    /// Upgrade older payloads on `load` and record the schema version on `save`.
+    ///
+    /// Payloads are stored as `{"serdevault_schema": N, "data": ...}` from then
+    /// on; vaults saved before migrations were configured count as version 1.
+    /// See [`Migrations`].
+    pub fn with_migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = Some(migrations);
+        self
+    }
+
+    /// Refuse to `load` a vault that is group- or world-accessible.
+    ///
+    /// Vaults written by this crate are always created with mode `0600`; this
+    /// catches files that were copied or `chmod`ed afterwards. No-op on non-Unix.
+    pub fn with_strict_permissions(mut self, strict: bool) -> Self {
+        self.strict_permissions = strict;
+        self
+    }
+
+    /// Restrict the existing vault file to its owner.
+    ///
+    /// Sets mode `0600` on Unix and an owner-only DACL on Windows. `save` already
+    /// does this for every file it writes; use this to fix up vaults that were
+    /// copied in from elsewhere.
+    pub fn harden_permissions(&self) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        permissions::restrict(&self.path).map_err(self.ctx(Operation::HardenPermissions))?;
+        Ok(())
+    }
+
+    /// Paths of the existing backups of this vault, oldest first.
+    ///
+    /// Each one is a complete vault: open it with `VaultFile::open` to read it,
+    /// or copy it over the vault to roll back.
+    pub fn backups(&self) -> Result<Vec<PathBuf>, SerdeVaultError> {
+        artifacts::backups(&self.path).map_err(self.ctx(Operation::Load))
+    }
+
+    /// Location of the vault file, with `~/` and environment variables already expanded.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     /// Whether the vault file exists on disk.
     pub fn exists(&self) -> bool {
         self.path.exists()
     }
 
-    /// Serialize `data` to JSON, encrypt it, and write it to the vault file atomically.
+    /// Delete the vault together with its backups, history, snapshots, lock file
+    /// and any temp files the crate left behind.
+    ///
+    /// Files are simply unlinked; use [`destroy`](Self::destroy) to overwrite them
+    /// first. Succeeds if the vault doesn't exist.
+    pub fn delete(&self) -> Result<(), SerdeVaultError> {
+        self.remove_all(false)
+    }
+
+    /// Like [`delete`](Self::delete), but overwrite every file with random bytes
+    /// before unlinking it.
+    ///
+    /// This is best-effort: on SSDs, copy-on-write filesystems (btrfs, ZFS, APFS)
+    /// and anything with snapshots or backups, the old ciphertext may survive on
+    /// the device. The vault is encrypted regardless; treat the overwrite as
+    /// defence in depth, not a guarantee. Succeeds if the vault doesn't exist.
+    pub fn destroy(&self) -> Result<(), SerdeVaultError> {
+        self.remove_all(true)
+    }
+
+    fn remove_all(&self, secure: bool) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let remove = |path: &Path| {
+            if secure {
+                shred(path)
+            } else {
+                std::fs::remove_file(path)
+            }
+        };
+        let ctx = self.ctx(Operation::Destroy);
+
+        for artifact in artifacts::all(&self.path).map_err(&ctx)? {
+            remove(&artifact).map_err(&ctx)?;
+        }
+        match std::fs::remove_dir(artifacts::snapshot_dir(&self.path)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(ctx(e)),
+            _ => {}
+        }
+        match remove(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(ctx(e)),
+        }
+    }
+
+    /// Serialize `data`, encrypt it, and write it to the vault file atomically.
     pub fn save<T: Serialize>(&self, data: &T) -> Result<(), SerdeVaultError> {
-        let plaintext = Zeroizing::new(
-            serde_json::to_vec(data)
-                .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?,
-        );
+        self.save_as(self.format, data)
+    }
 
+    /// Like [`save`](Self::save), but serialize this one write as `format`
+    /// instead of the handle's [`with_format`](Self::with_format).
+    ///
+    /// The format is recorded in the header, so any handle loads the result, and
+    /// later saves go back to the handle's own format. Handy for a vault that
+    /// usually holds compact data but is sometimes written in a debug-friendly one.
+    pub fn save_as<T: Serialize>(&self, format: Format, data: &T) -> Result<(), SerdeVaultError> {
+        self.lint_binary(format, data)?;
+        if self.streaming && !self.deterministic {
+            return self.save_streamed(format, data);
+        }
+        let plaintext = self.encode_payload(format, data)?;
+        self.write_plaintext(format, &plaintext, self.extensions_for(data)?)
+    }
+
+    /// Like [`save`](Self::save), but skip the write when the vault already holds
+    /// exactly this data. Returns whether the file was written.
+    ///
+    /// Skipping keeps the file's mtime, salt and nonce unchanged, which avoids
+    /// spurious sync conflicts and backup churn. The check costs one key
+    /// derivation and decryption. Comparison is on the serialized bytes, so types
+    /// with unstable ordering (e.g. `HashMap`) may be rewritten even when equal.
+    /// A vault that can't be decrypted with this handle's password counts as
+    /// changed and is overwritten, exactly as `save` would.
+    pub fn save_if_changed<T: Serialize>(&self, data: &T) -> Result<bool, SerdeVaultError> {
+        let _lock = self.lock_for_update()?;
+        let plaintext = self.encode_payload(self.format, data)?;
+
+        match self.read_plaintext() {
+            Ok((format, current)) if format == self.format && *current == *plaintext => {
+                return Ok(false)
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::Io | ErrorKind::PermissionDenied) => {
+                return Err(e)
+            }
+            _ => {}
+        }
+
+        // Through `save`, so streaming and the binary lint apply as usual.
+        drop(plaintext);
+        self.save(data)?;
+        Ok(true)
+    }
+
+    /// Encrypt `plaintext` (encoded as `format`) under a fresh salt and nonce, or
+    /// [deterministically](Self::with_deterministic_encryption), and write it
+    /// atomically.
+    fn write_plaintext(
+        &self,
+        format: Format,
+        plaintext: &[u8],
+        extensions: Vec<u8>,
+    ) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        self.seal_and_write(format, plaintext, extensions, &self.write_key()?)
+    }
+
+    /// Derive a key under a fresh random salt and this handle's parameters.
+    fn fresh_key(&self) -> Result<VaultKey, SerdeVaultError> {
         let mut salt = [0u8; SALT_SIZE];
         OsRng.fill_bytes(&mut salt);
-        let key = derive_key(&self.password, &salt, self.m_cost, self.t_cost, self.p_cost)?;
-
-        let (ciphertext, nonce) = encrypt(&plaintext, &key)?;
+        VaultKey::derive(&self.password, salt, self.m_cost, self.t_cost, self.p_cost)
+    }
 
-        let header = VaultHeader {
-            salt,
-            m_cost: self.m_cost,
-            t_cost: self.t_cost,
-            p_cost: self.p_cost,
-            nonce,
-        };
+    /// Encrypt `plaintext` under `key` with a fresh (or synthetic) nonce and write
+    /// it atomically.
+    fn seal_and_write(
+        &self,
+        format: Format,
+        plaintext: &[u8],
+        mut extensions: Vec<u8>,
+        key: &VaultKey,
+    ) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let target = self.target_path(Operation::Save)?;
+        let _lock = self.lock(false, Operation::Save)?;
+        self.stamp_generation(&mut extensions)?;
+        let mut header = key.header(format, 0, extensions);
+        if self.deterministic {
+            deterministic::set_synthetic_nonce(key, &mut header, plaintext);
+        }
+        let ciphertext = encrypt(
+            plaintext,
+            &key.key,
+            &header.nonce,
+            &header.associated_data(),
+        )?;
 
         let encoded = encode(&header, &ciphertext);
-        atomic_write(&self.path, &encoded)?;
+        self.before_overwrite().map_err(self.ctx(Operation::Save))?;
+        atomic_write(&target, &encoded, &self.write_opts).map_err(self.ctx(Operation::Save))?;
 
         Ok(())
     }
 
     /// Read the vault file, decrypt it, and deserialize the data.
     pub fn load<T: for<'de> Deserialize<'de>>(&self) -> Result<T, SerdeVaultError> {
-        let raw = std::fs::read(&self.path)?;
+        let (format, plaintext) = self.read_plaintext()?;
+        self.decode_payload(format, &plaintext)
+    }
+
+    /// Load a value that may have been saved in any of several representations.
+    ///
+    /// `load_any::<(V2, V1)>()` returns a `V2`, trying `V2` first and falling
+    /// back to `V1` converted with `From`, so renaming a field doesn't break
+    /// vaults written by older builds. If none of them fits, the error from the
+    /// newest one is returned. The vault is decrypted only once. For chains that
+    /// should also rewrite the stored data, see [`with_migrations`](Self::with_migrations).
+    pub fn load_any<R: Representations>(&self) -> Result<R::Output, SerdeVaultError> {
+        let (format, plaintext) = self.read_plaintext()?;
+        R::decode(self, format, &plaintext)
+    }
 
-        let (header, ciphertext) = decode(&raw)?;
+    /// Re-encrypt this vault's contents under `new_password` at `new_path`, and
+    /// return a handle to the copy.
+    ///
+    /// The data is decrypted once and written with a fresh salt and nonce, using
+    /// this handle's current KDF parameters and settings — so this also upgrades
+    /// the KDF cost of an old vault. An existing file at `new_path` is replaced.
+    /// The source vault is left untouched.
+    pub fn copy_to(
+        &self,
+        new_path: impl AsRef<Path>,
+        new_password: &str,
+    ) -> Result<VaultFile, SerdeVaultError> {
+        let raw = self.read_raw()?;
+        let (header, _) = decode(&raw).map_err(self.ctx(Operation::Load))?;
+        let (format, plaintext) = self.open_blob_cached(&raw)?;
 
-        let key = derive_key(
-            &self.password,
-            &header.salt,
-            header.m_cost,
-            header.t_cost,
-            header.p_cost,
+        let mut dest = self.clone();
+        dest.path = paths::expand(new_path.as_ref());
+        dest.password = Zeroizing::new(new_password.to_owned());
+        dest.read_only = false;
+        dest.write_plaintext(format, &plaintext, header.extensions)?;
+
+        Ok(dest)
+    }
+
+    /// Encrypt `plaintext` as a complete vault file held in memory, under
+    /// `password` and this handle's KDF parameters.
+    pub(crate) fn seal_blob(
+        &self,
+        password: &str,
+        format: Format,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, SerdeVaultError> {
+        let mut sealer = self.clone();
+        sealer.password = Zeroizing::new(password.to_owned());
+        let key = sealer.fresh_key()?;
+        let header = key.header(format, 0, Vec::new());
+        let ciphertext = encrypt(
+            plaintext,
+            &key.key,
+            &header.nonce,
+            &header.associated_data(),
         )?;
+        Ok(encode(&header, &ciphertext))
+    }
+
+    /// Decrypt a complete vault file held in memory under `password`.
+    pub(crate) fn open_blob_with(
+        &self,
+        password: &str,
+        raw: &[u8],
+    ) -> Result<(Format, Zeroizing<Vec<u8>>), SerdeVaultError> {
+        let mut opener = self.clone();
+        opener.password = Zeroizing::new(password.to_owned());
+        opener.open_blob(raw)
+    }
+
+    /// Serialize `data`, inside the schema envelope if migrations are configured.
+    fn encode_payload<T: Serialize>(
+        &self,
+        format: Format,
+        data: &T,
+    ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+        match &self.migrations {
+            None => format.serialize(data),
+            Some(migrations) => format.serialize(&migrations.wrap(data)),
+        }
+    }
+
+    /// [`encode_payload`](Self::encode_payload) straight into a writer.
+    fn encode_payload_into<T: Serialize>(
+        &self,
+        format: Format,
+        data: &T,
+        out: impl std::io::Write,
+    ) -> Result<(), SerdeVaultError> {
+        match &self.migrations {
+            None => format.serialize_into(data, out),
+            Some(migrations) => format.serialize_into(&migrations.wrap(data), out),
+        }
+    }
+
+    /// Inverse of [`encode_payload`](Self::encode_payload), upgrading old schema
+    /// versions. `format` is the one recorded in the vault, not necessarily this
+    /// handle's.
+    pub(crate) fn decode_payload<T: for<'de> Deserialize<'de>>(
+        &self,
+        format: Format,
+        plaintext: &[u8],
+    ) -> Result<T, SerdeVaultError> {
+        match &self.migrations {
+            None => format.deserialize(plaintext),
+            Some(migrations) => {
+                let payload = format.deserialize(plaintext)?;
+                let upgraded = SecretValue(migrations.upgrade(payload)?);
+                T::deserialize(&*upgraded)
+                    .map_err(|e| SerdeVaultError::DeserializationError(e.to_string()))
+            }
+        }
+    }
+
+    /// Check that this handle may write, then take the exclusive update lock.
+    pub(crate) fn lock_for_update(&self) -> Result<VaultLock, SerdeVaultError> {
+        self.ensure_writable()?;
+        self.lock(false, Operation::Update)
+    }
+
+    /// The Argon2 `(m_cost, t_cost, p_cost)` this handle derives keys with.
+    pub(crate) fn argon2_params(&self) -> (u32, u32, u32) {
+        (self.m_cost, self.t_cost, self.p_cost)
+    }
 
-        let plaintext = decrypt(ciphertext, &key, &header.nonce)?;
+    /// Fail with `ReadOnly` if this handle was opened read-only.
+    fn ensure_writable(&self) -> Result<(), SerdeVaultError> {
+        if self.read_only {
+            return Err(SerdeVaultError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    /// Preserve the file about to be replaced, as configured (backups, history).
+    fn before_overwrite(&self) -> Result<(), SerdeVaultError> {
+        if self.backups > 0 {
+            backup::rotate(&self.path, self.backups)?;
+        }
+        if self.history > 0 {
+            self.archive_current()?;
+        }
+        Ok(())
+    }
+
+    /// Read the vault file and decrypt it, without deserializing.
+    fn read_plaintext(&self) -> Result<(Format, Zeroizing<Vec<u8>>), SerdeVaultError> {
+        let raw = self.read_raw()?;
+        self.open_blob_cached(&raw)
+    }
+
+    /// Read the encrypted vault file, applying the permission check if enabled.
+    fn read_raw(&self) -> Result<Vec<u8>, SerdeVaultError> {
+        let target = self.target_path(Operation::Load)?;
+        let _lock = self.lock_for_load()?;
+        let raw = std::fs::read(&target).map_err(|e| self.read_error(e))?;
+        if self.strict_permissions {
+            permissions::ensure_private(&target).map_err(self.ctx(Operation::Load))?;
+        }
+        Ok(raw)
+    }
+
+    /// Decrypt a complete vault file held in memory.
+    fn open_blob(&self, raw: &[u8]) -> Result<(Format, Zeroizing<Vec<u8>>), SerdeVaultError> {
+        let (header, ciphertext) = decode(raw).map_err(self.ctx(Operation::Load))?;
+        let format = self.payload_format(&header)?;
+        let key = VaultKey::for_header(&self.password, &header)?;
+        Ok((format, key.open(&header, ciphertext)?))
+    }
+
+    /// The format the payload behind `header` was written in. Version 1 vaults
+    /// didn't record it, so this handle's format is assumed.
+    fn payload_format(&self, header: &VaultHeader) -> Result<Format, SerdeVaultError> {
+        if header.version == LEGACY_VERSION {
+            return Ok(self.format);
+        }
+        Format::from_id(header.format)
+            .ok_or(SerdeVaultError::UnsupportedFormat(header.format))
+            .map_err(self.ctx(Operation::Load))
+    }
+
+    /// Check whether this handle's password opens the vault.
+    ///
+    /// Derives the key and verifies the AES-GCM authentication tag; the decrypted
+    /// bytes are wiped immediately and never parsed, so this works without knowing
+    /// the stored type. Returns `Ok(false)` for a wrong password (or a tampered
+    /// file — the two are indistinguishable by design) and `Err` for anything
+    /// else, such as a missing or malformed file.
+    pub fn verify_password(&self) -> Result<bool, SerdeVaultError> {
+        match self.read_plaintext() {
+            Ok(_) => Ok(true),
+            Err(SerdeVaultError::DecryptionFailed) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Like [`load`](Self::load), but returns `Ok(None)` when the vault file doesn't exist.
+    pub fn load_opt<T: for<'de> Deserialize<'de>>(&self) -> Result<Option<T>, SerdeVaultError> {
+        match self.load() {
+            Ok(data) => Ok(Some(data)),
+            Err(SerdeVaultError::VaultNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 
-        let value = serde_json::from_slice(&plaintext)
-            .map_err(|e| SerdeVaultError::DeserializationError(e.to_string()))?;
+    /// Load the stored value, or — if the vault doesn't exist yet — create it from
+    /// `init`, save it, and return it.
+    ///
+    /// A vault that exists but can't be read (wrong password, corruption) is an
+    /// error, never silently replaced by the default.
+    pub fn load_or_init<T, F>(&self, init: F) -> Result<T, SerdeVaultError>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+        F: FnOnce() -> T,
+    {
+        if let Some(data) = self.load_opt()? {
+            return Ok(data);
+        }
+        let data = init();
+        self.save(&data)?;
+        Ok(data)
+    }
 
-        Ok(value)
+    /// Load the stored value, let `f` modify it, and save the result — all while
+    /// holding an exclusive advisory lock, so concurrent `update` calls (from this
+    /// or another process) can't lose each other's changes.
+    ///
+    /// Nothing is written if loading fails. The vault must already exist; see
+    /// [`load_or_init`](Self::load_or_init). Returns whatever `f` returns.
+    pub fn update<T, F, R>(&self, f: F) -> Result<R, SerdeVaultError>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+        F: FnOnce(&mut T) -> R,
+    {
+        let _lock = self.lock_for_update()?;
+
+        let mut data: T = self.load()?;
+        let result = f(&mut data);
+        self.save(&data)?;
+
+        Ok(result)
+    }
+
+    /// Map an error from reading the vault file, naming the vault when it is missing.
+    fn read_error(&self, e: std::io::Error) -> SerdeVaultError {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            SerdeVaultError::VaultNotFound(self.path.clone())
+        } else {
+            self.ctx(Operation::Load)(e)
+        }
+    }
+
+    /// Error mapper attaching this vault's path and `operation` (see [`SerdeVaultError::context`]).
+    fn ctx<E: Into<SerdeVaultError>>(
+        &self,
+        operation: Operation,
+    ) -> impl Fn(E) -> SerdeVaultError + '_ {
+        move |e| e.into().context(operation, &self.path)
     }
 }
 
-/// Expand a leading `~/` to the user's home directory.
-/// Falls back to the literal path if `HOME` is not set.
-fn expand_tilde(path: &Path) -> PathBuf {
-    let s = path.to_string_lossy();
-    if let Some(rest) = s.strip_prefix("~/") {
-        if let Ok(home) = env::var("HOME") {
-            return PathBuf::from(home).join(rest);
+/// An AES key together with the salt and Argon2 parameters it was derived with.
+#[derive(Clone)]
+struct VaultKey {
+    salt: [u8; SALT_SIZE],
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    key: Zeroizing<[u8; KEY_SIZE]>,
+}
+
+impl VaultKey {
+    fn derive(
+        password: &str,
+        salt: [u8; SALT_SIZE],
+        m_cost: u32,
+        t_cost: u32,
+        p_cost: u32,
+    ) -> Result<Self, SerdeVaultError> {
+        Ok(Self {
+            key: derive_key(password, &salt, m_cost, t_cost, p_cost)?,
+            salt,
+            m_cost,
+            t_cost,
+            p_cost,
+        })
+    }
+
+    /// The key a vault with this header was encrypted under.
+    fn for_header(password: &str, header: &VaultHeader) -> Result<Self, SerdeVaultError> {
+        Self::derive(
+            password,
+            header.salt,
+            header.m_cost,
+            header.t_cost,
+            header.p_cost,
+        )
+    }
+
+    /// Whether a vault with this header was encrypted under this key's salt and parameters.
+    fn fits(&self, header: &VaultHeader) -> bool {
+        self.salt == header.salt
+            && (self.m_cost, self.t_cost, self.p_cost)
+                == (header.m_cost, header.t_cost, header.p_cost)
+    }
+
+    /// A current-version header for a payload in `format` under this key, with
+    /// a fresh nonce.
+    fn header(&self, format: Format, flags: u8, extensions: Vec<u8>) -> VaultHeader {
+        VaultHeader {
+            version: FORMAT_VERSION,
+            format: format.id(),
+            flags,
+            salt: self.salt,
+            m_cost: self.m_cost,
+            t_cost: self.t_cost,
+            p_cost: self.p_cost,
+            nonce: random_nonce(),
+            extensions,
+        }
+    }
+
+    /// Decrypt the body that follows `header`, in whichever layout it declares.
+    fn open(
+        &self,
+        header: &VaultHeader,
+        ciphertext: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, SerdeVaultError> {
+        let aad = header.associated_data();
+        if header.is_chunked() {
+            decrypt_chunked(ciphertext, &self.key, &header.nonce, &aad)
+        } else {
+            decrypt(ciphertext, &self.key, &header.nonce, &aad)
         }
     }
-    path.to_path_buf()
 }
 
 #[cfg(test)]
@@ -218,7 +810,7 @@ mod tests {
             .load::<TestData>()
             .unwrap_err();
 
-        assert!(matches!(err, SerdeVaultError::InvalidFormat(_)));
+        assert!(matches!(err.root(), SerdeVaultError::InvalidFormat(_)));
     }
 
     // 5. File with wrong magic number → InvalidFormat
@@ -235,7 +827,7 @@ mod tests {
             .load::<TestData>()
             .unwrap_err();
 
-        assert!(matches!(err, SerdeVaultError::InvalidFormat(_)));
+        assert!(matches!(err.root(), SerdeVaultError::InvalidFormat(_)));
     }
 
     // 6. File with correct magic but truncated body → DecryptionFailed (GCM tag missing)
@@ -247,7 +839,9 @@ mod tests {
 
         // Truncate the file to just the header — no ciphertext
         let path = dir.path().join("vault.svlt");
-        let header_only = std::fs::read(&path).unwrap()[..crate::format::HEADER_SIZE].to_vec();
+        let raw = std::fs::read(&path).unwrap();
+        let body = crate::format::decode(&raw).unwrap().1.len();
+        let header_only = raw[..raw.len() - body].to_vec();
         std::fs::write(&path, &header_only).unwrap();
 
         let err = vault.load::<TestData>().unwrap_err();
@@ -268,7 +862,10 @@ mod tests {
         std::fs::write(&path, &raw).unwrap();
 
         let err = vault.load::<TestData>().unwrap_err();
-        assert!(matches!(err, SerdeVaultError::UnsupportedVersion(99)));
+        assert!(matches!(
+            err.root(),
+            SerdeVaultError::UnsupportedVersion(99)
+        ));
     }
 
     // 8. Two saves produce different ciphertexts (fresh nonce + salt each time)
@@ -284,7 +881,10 @@ mod tests {
         vault.save(&data).unwrap();
         let second = std::fs::read(dir.path().join("vault.svlt")).unwrap();
 
-        assert_ne!(first, second, "two saves of identical data must produce different ciphertexts");
+        assert_ne!(
+            first, second,
+            "two saves of identical data must produce different ciphertexts"
+        );
     }
 
     // 9. VaultFile::exists() reflects disk state
@@ -297,4 +897,581 @@ mod tests {
         vault.save(&sample()).unwrap();
         assert!(vault.exists());
     }
+
+    // 10. Disabling the directory fsync does not affect the written data
+    #[test]
+    fn test_roundtrip_without_dir_sync() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd").with_dir_sync(false);
+        let data = sample();
+
+        vault.save(&data).unwrap();
+        let loaded: TestData = vault.load().unwrap();
+
+        assert_eq!(data, loaded);
+    }
+
+    // 11. Saved vaults are owner-only, even when replacing a looser file
+    #[cfg(unix)]
+    #[test]
+    fn test_saved_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        std::fs::write(&path, b"old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        vault_at(&dir, "vault.svlt", "pwd").save(&sample()).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    // 12. Strict mode refuses group/world-readable vaults
+    #[cfg(unix)]
+    #[test]
+    fn test_strict_permissions_rejects_readable_vault() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd").with_strict_permissions(true);
+        vault.save(&sample()).unwrap();
+        assert!(vault.load::<TestData>().is_ok());
+
+        let path = dir.path().join("vault.svlt");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let err = vault.load::<TestData>().unwrap_err();
+        assert!(matches!(
+            err.root(),
+            SerdeVaultError::InsecurePermissions(0o644)
+        ));
+    }
+
+    // 13. harden_permissions() tightens a file that was loosened after creation
+    #[cfg(unix)]
+    #[test]
+    fn test_harden_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+        vault.save(&sample()).unwrap();
+
+        let path = dir.path().join("vault.svlt");
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+        vault.harden_permissions().unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+
+    // 14. destroy() removes the vault and leftover temp files
+    #[test]
+    fn test_destroy() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+        vault.save(&sample()).unwrap();
+        let stray = dir.path().join(".vault.svlt.abc123.tmp");
+        std::fs::write(&stray, b"partial").unwrap();
+        let unrelated = dir.path().join("other.svlt");
+        std::fs::write(&unrelated, b"keep me").unwrap();
+
+        vault.destroy().unwrap();
+
+        assert!(!vault.exists());
+        assert!(!stray.exists());
+        assert!(unrelated.exists());
+        // Destroying an absent vault is not an error
+        vault.destroy().unwrap();
+    }
+
+    // 15. load_or_init() creates the vault once, then returns the stored value
+    #[test]
+    fn test_load_or_init() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        let first: TestData = vault.load_or_init(sample).unwrap();
+        assert_eq!(first, sample());
+        assert!(vault.exists());
+
+        let second: TestData = vault
+            .load_or_init(|| panic!("init must not run when the vault exists"))
+            .unwrap();
+        assert_eq!(second, sample());
+
+        // A vault we can't decrypt is an error, not a reason to overwrite it
+        let wrong = vault_at(&dir, "vault.svlt", "wrong");
+        let err = wrong.load_or_init(sample).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+    }
+
+    // 16. load_opt() distinguishes "no vault yet" from real errors
+    #[test]
+    fn test_load_opt() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        assert!(vault.load_opt::<TestData>().unwrap().is_none());
+
+        vault.save(&sample()).unwrap();
+        assert_eq!(vault.load_opt::<TestData>().unwrap(), Some(sample()));
+
+        let err = vault_at(&dir, "vault.svlt", "wrong")
+            .load_opt::<TestData>()
+            .unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+    }
+
+    // 17. Missing vaults and permission errors get dedicated variants and kinds
+    #[test]
+    fn test_error_kinds() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "missing.svlt", "pwd");
+
+        let err = vault.load::<TestData>().unwrap_err();
+        assert!(matches!(&err, SerdeVaultError::VaultNotFound(p) if p.ends_with("missing.svlt")));
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(
+            SerdeVaultError::from(denied).kind(),
+            ErrorKind::PermissionDenied
+        );
+
+        let other = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
+        assert_eq!(SerdeVaultError::from(other).kind(), ErrorKind::Io);
+
+        assert_eq!(
+            SerdeVaultError::DecryptionFailed.kind(),
+            ErrorKind::DecryptionFailed
+        );
+    }
+
+    // 18. I/O and format errors name the vault and the failed operation
+    #[test]
+    fn test_error_context() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        std::fs::write(&path, b"not a vault").unwrap();
+
+        let err = VaultFile::open(&path, "pwd")
+            .load::<TestData>()
+            .unwrap_err();
+        assert_eq!(err.operation(), Some(Operation::Load));
+        assert_eq!(err.path(), Some(path.as_path()));
+        assert_eq!(err.kind(), ErrorKind::InvalidFormat);
+        assert!(err.to_string().contains("while loading"));
+        assert!(err.to_string().contains("vault.svlt"));
+
+        // A directory where the vault should be makes the final rename fail
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir(&path).unwrap();
+        let err = vault_at(&dir, "vault.svlt", "pwd")
+            .save(&sample())
+            .unwrap_err();
+        assert_eq!(err.operation(), Some(Operation::Save));
+
+        // Content errors are not wrapped
+        let err = SerdeVaultError::DecryptionFailed.context(Operation::Load, &path);
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+    }
+
+    // 19. Errors carry a stable code and a remediation hint
+    #[test]
+    fn test_error_help() {
+        let dir = tempdir().unwrap();
+        vault_at(&dir, "vault.svlt", "right")
+            .save(&sample())
+            .unwrap();
+
+        let err = vault_at(&dir, "vault.svlt", "wrong")
+            .load::<TestData>()
+            .unwrap_err();
+        assert_eq!(err.code(), "serdevault::decryption_failed");
+        assert!(err.help().unwrap().contains("password"));
+    }
+
+    // 20. update() applies the closure and persists the result
+    #[test]
+    fn test_update() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+        vault.save(&sample()).unwrap();
+
+        let old = vault
+            .update(|data: &mut TestData| std::mem::replace(&mut data.value, 43))
+            .unwrap();
+
+        assert_eq!(old, 42);
+        assert_eq!(vault.load::<TestData>().unwrap().value, 43);
+    }
+
+    // 21. Concurrent updates serialize on the lock instead of losing increments
+    #[test]
+    fn test_update_is_serialized() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("counter.svlt");
+        VaultFile::open(&path, "pwd")
+            .with_params(M, T, P)
+            .save(&0u32)
+            .unwrap();
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let vault = VaultFile::open(&path, "pwd").with_params(M, T, P);
+                    for _ in 0..5 {
+                        vault.update(|n: &mut u32| *n += 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        let total: u32 = VaultFile::open(&path, "pwd").load().unwrap();
+        assert_eq!(total, 20);
+    }
+
+    // 22. update() on a missing vault fails without creating it
+    #[test]
+    fn test_update_missing_vault() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        let err = vault.update(|_: &mut TestData| ()).unwrap_err();
+
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert!(!vault.exists());
+    }
+
+    // 23. save_if_changed() leaves the file untouched when the data is identical
+    #[test]
+    fn test_save_if_changed() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+        let path = dir.path().join("vault.svlt");
+
+        assert!(
+            vault.save_if_changed(&sample()).unwrap(),
+            "missing vault must be written"
+        );
+        let first = std::fs::read(&path).unwrap();
+
+        assert!(!vault.save_if_changed(&sample()).unwrap());
+        assert_eq!(std::fs::read(&path).unwrap(), first);
+
+        let mut changed = sample();
+        changed.value += 1;
+        assert!(vault.save_if_changed(&changed).unwrap());
+        assert_eq!(vault.load::<TestData>().unwrap(), changed);
+
+        // Writes go the way of save, streamed here.
+        let streaming = vault.clone().with_streaming(true);
+        assert!(streaming.save_if_changed(&sample()).unwrap());
+        let raw = std::fs::read(&path).unwrap();
+        assert!(decode(&raw).unwrap().0.is_chunked());
+    }
+
+    // 24. with_backups() keeps the previous versions, pruned to the limit
+    #[test]
+    fn test_backups() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd").with_backups(2);
+
+        for value in 1..=4 {
+            let mut data = sample();
+            data.value = value;
+            vault.save(&data).unwrap();
+        }
+
+        let backups = vault.backups().unwrap();
+        assert_eq!(backups.len(), 2);
+        let values: Vec<u64> = backups
+            .iter()
+            .map(|p| VaultFile::open(p, "pwd").load::<TestData>().unwrap().value)
+            .collect();
+        assert_eq!(values, vec![2, 3]);
+        assert_eq!(vault.load::<TestData>().unwrap().value, 4);
+
+        vault.destroy().unwrap();
+        assert!(vault.backups().unwrap().is_empty());
+    }
+
+    // 25. copy_to() re-encrypts under a new password without touching the source
+    #[test]
+    fn test_copy_to() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "old-pwd");
+        vault.save(&sample()).unwrap();
+        let source_bytes = std::fs::read(dir.path().join("vault.svlt")).unwrap();
+
+        let copy = vault
+            .copy_to(dir.path().join("copy.svlt"), "new-pwd")
+            .unwrap();
+
+        assert_eq!(copy.load::<TestData>().unwrap(), sample());
+        assert_eq!(
+            vault_at(&dir, "copy.svlt", "new-pwd")
+                .load::<TestData>()
+                .unwrap(),
+            sample()
+        );
+        let err = vault_at(&dir, "copy.svlt", "old-pwd")
+            .load::<TestData>()
+            .unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+        assert_eq!(
+            std::fs::read(dir.path().join("vault.svlt")).unwrap(),
+            source_bytes
+        );
+    }
+
+    // 26. delete() removes the vault and every managed artifact
+    #[test]
+    fn test_delete() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd")
+            .with_backups(2)
+            .with_history(2);
+        vault.save(&sample()).unwrap();
+        vault.save(&sample()).unwrap();
+        vault.snapshot("s1").unwrap();
+        vault.update(|_: &mut TestData| ()).unwrap();
+
+        vault.delete().unwrap();
+
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert!(leftovers.is_empty(), "left behind: {leftovers:?}");
+        vault.delete().unwrap();
+    }
+
+    // 27. A read-only handle can load but never modify the vault
+    #[test]
+    fn test_read_only() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        vault_at(&dir, "vault.svlt", "pwd").save(&sample()).unwrap();
+        let before = std::fs::read(&path).unwrap();
+
+        let ro = VaultFile::open_read_only(&path, "pwd").with_params(M, T, P);
+        assert_eq!(ro.load::<TestData>().unwrap(), sample());
+
+        let errors = [
+            ro.save(&sample()).unwrap_err(),
+            ro.save_if_changed(&sample()).unwrap_err(),
+            ro.update(|_: &mut TestData| ()).unwrap_err(),
+            ro.delete().unwrap_err(),
+            ro.harden_permissions().unwrap_err(),
+            ro.snapshot("label").unwrap_err(),
+        ];
+        for err in errors {
+            assert_eq!(err.kind(), ErrorKind::ReadOnly);
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), before);
+        assert!(ro.snapshots().unwrap().is_empty());
+
+        // Copies are ordinary, writable vaults
+        let copy = ro.copy_to(dir.path().join("copy.svlt"), "pwd").unwrap();
+        copy.save(&sample()).unwrap();
+    }
+
+    // 28. verify_password() checks credentials without a target type
+    #[test]
+    fn test_verify_password() {
+        let dir = tempdir().unwrap();
+        vault_at(&dir, "vault.svlt", "right")
+            .save(&sample())
+            .unwrap();
+
+        assert!(vault_at(&dir, "vault.svlt", "right")
+            .verify_password()
+            .unwrap());
+        assert!(!vault_at(&dir, "vault.svlt", "wrong")
+            .verify_password()
+            .unwrap());
+
+        let err = vault_at(&dir, "missing.svlt", "right")
+            .verify_password()
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    // 29. Migrations upgrade an old payload on load and stamp the version on save
+    #[test]
+    fn test_migrations() {
+        #[derive(Serialize, Deserialize)]
+        struct V1 {
+            name: String,
+        }
+
+        let dir = tempdir().unwrap();
+        vault_at(&dir, "vault.svlt", "pwd")
+            .save(&V1 {
+                name: "GitHub perso".into(),
+            })
+            .unwrap();
+
+        let migrations = crate::migration::Migrations::new().step(|v1: V1| TestData {
+            name: v1.name,
+            value: 42,
+            tags: vec![],
+            optional: None,
+        });
+        let vault = vault_at(&dir, "vault.svlt", "pwd").with_migrations(migrations);
+
+        let mut data: TestData = vault.load().unwrap();
+        assert_eq!((data.name.as_str(), data.value), ("GitHub perso", 42));
+
+        data.value = 7;
+        vault.save(&data).unwrap();
+        let raw = vault_at(&dir, "vault.svlt", "pwd").load_value().unwrap();
+        assert_eq!(raw["serdevault_schema"], 2);
+        assert_eq!(vault.load::<TestData>().unwrap().value, 7);
+
+        // A build that only knows version 1 refuses rather than misreading it
+        let old = vault_at(&dir, "vault.svlt", "pwd")
+            .with_migrations(crate::migration::Migrations::new());
+        assert_eq!(old.load::<V1>().err().unwrap().kind(), ErrorKind::Migration);
+    }
+
+    // 30. load_any() falls back to older representations via From
+    #[test]
+    fn test_load_any() {
+        #[derive(Serialize, Deserialize)]
+        struct Legacy {
+            label: String,
+        }
+        impl From<Legacy> for TestData {
+            fn from(old: Legacy) -> Self {
+                TestData {
+                    name: old.label,
+                    value: 0,
+                    tags: vec![],
+                    optional: None,
+                }
+            }
+        }
+
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        vault
+            .save(&Legacy {
+                label: "old".into(),
+            })
+            .unwrap();
+        assert_eq!(vault.load_any::<(TestData, Legacy)>().unwrap().name, "old");
+
+        vault.save(&sample()).unwrap();
+        assert_eq!(vault.load_any::<(TestData, Legacy)>().unwrap(), sample());
+
+        vault.save(&1u32).unwrap();
+        let err = vault.load_any::<(TestData, Legacy)>().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Deserialization);
+    }
+
+    // 31. Version 1 files (no format byte, nothing authenticated) still load
+    #[test]
+    fn test_load_legacy_version() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        let (salt, nonce) = ([1u8; SALT_SIZE], [2u8; crate::crypto::cipher::NONCE_SIZE]);
+        let key = derive_key("pwd", &salt, M, T, P).unwrap();
+        let plaintext = serde_json::to_vec(&sample()).unwrap();
+        let mut raw = b"SVLT\x01".to_vec();
+        raw.extend_from_slice(&salt);
+        for cost in [M, T, P] {
+            raw.extend_from_slice(&cost.to_le_bytes());
+        }
+        raw.extend_from_slice(&nonce);
+        raw.extend_from_slice(&encrypt(&plaintext, &key, &nonce, &[]).unwrap());
+        std::fs::write(vault.path(), &raw).unwrap();
+
+        assert_eq!(vault.load::<TestData>().unwrap(), sample());
+    }
+
+    // 32. The payload format is recorded in the header, which is authenticated
+    #[test]
+    fn test_payload_format_in_header() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+        vault.save(&sample()).unwrap();
+        let raw = std::fs::read(vault.path()).unwrap();
+        assert_eq!((raw[4], raw[5]), (FORMAT_VERSION, Format::Json.id()));
+
+        let mut unknown = raw.clone();
+        unknown[5] = 99;
+        std::fs::write(vault.path(), &unknown).unwrap();
+        let err = vault.load::<TestData>().unwrap_err();
+        assert!(matches!(err.root(), SerdeVaultError::UnsupportedFormat(99)));
+
+        let mut flipped = raw;
+        flipped[6] |= 0x80; // an unknown flag
+        std::fs::write(vault.path(), &flipped).unwrap();
+        let err = vault.load::<TestData>().unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+    }
+
+    // 33. Registered codecs are recorded by id and picked automatically on load
+    #[test]
+    fn test_custom_format() {
+        use crate::serializer::Codec;
+
+        /// JSON, backwards.
+        struct Reversed;
+        impl Codec for Reversed {
+            fn encode(&self, value: &serde_json::Value) -> Result<Vec<u8>, SerdeVaultError> {
+                let mut bytes = serde_json::to_vec(value).unwrap();
+                bytes.reverse();
+                Ok(bytes)
+            }
+            fn decode(&self, bytes: &[u8]) -> Result<serde_json::Value, SerdeVaultError> {
+                let mut bytes = bytes.to_vec();
+                bytes.reverse();
+                serde_json::from_slice(&bytes)
+                    .map_err(|e| SerdeVaultError::DeserializationError(e.to_string()))
+            }
+        }
+
+        assert!(Format::register(1, Reversed).is_err());
+        let reversed = Format::register(200, Reversed).unwrap();
+        assert!(Format::register(200, Reversed).is_err());
+
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd").with_format(reversed);
+        vault.save(&sample()).unwrap();
+        assert_eq!(std::fs::read(vault.path()).unwrap()[5], 200);
+
+        // A JSON handle decodes with the recorded codec
+        assert_eq!(
+            vault_at(&dir, "vault.svlt", "pwd")
+                .load::<TestData>()
+                .unwrap(),
+            sample()
+        );
+    }
+
+    // 34. save_as() overrides the format for one write only
+    #[test]
+    fn test_save_as_overrides_format_once() {
+        let dir = tempdir().unwrap();
+        let vault = vault_at(&dir, "vault.svlt", "pwd");
+
+        vault.save_as(Format::CanonicalJson, &sample()).unwrap();
+        assert_eq!(
+            std::fs::read(vault.path()).unwrap()[5],
+            Format::CanonicalJson.id()
+        );
+        assert_eq!(vault.load::<TestData>().unwrap(), sample());
+
+        vault.save(&sample()).unwrap();
+        assert_eq!(std::fs::read(vault.path()).unwrap()[5], Format::Json.id());
+    }
 }
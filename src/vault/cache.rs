@@ -0,0 +1,212 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+use crate::error::SerdeVaultError;
+use crate::format::HEADER_SIZE;
+use crate::serializer::Format;
+
+use super::VaultFile;
+
+/// A bounded cache of decrypted vault contents, shared between handles.
+///
+/// Attach it with [`VaultFile::with_cache`]. A hit skips the key derivation and
+/// decryption, which dominate the cost of `load` for small vaults. Entries are
+/// checked against the file's header on every load — its salt and nonce change
+/// on every save — so a vault rewritten by anyone is decrypted afresh, and
+/// against the handle's password, so a wrong password never reads cached data.
+/// The least recently used entry is evicted when the cache is full; evicted
+/// plaintext is zeroized.
+#[derive(Clone)]
+pub struct VaultCache {
+    inner: Arc<Mutex<Lru>>,
+}
+
+struct Lru {
+    capacity: usize,
+    /// Least recently used first.
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    path: PathBuf,
+    password: Zeroizing<String>,
+    header: [u8; HEADER_SIZE],
+    format: Format,
+    plaintext: Zeroizing<Vec<u8>>,
+}
+
+impl VaultCache {
+    /// A cache holding up to `capacity` vaults.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Lru {
+                capacity,
+                entries: Vec::new(),
+            })),
+        }
+    }
+
+    /// Number of cached vaults.
+    pub fn len(&self) -> usize {
+        self.lru().entries.len()
+    }
+
+    /// Whether nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drop (and zeroize) every entry.
+    pub fn clear(&self) {
+        self.lru().entries.clear();
+    }
+
+    fn lru(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The cached plaintext of `vault` if `raw` is still what was decrypted.
+    fn get(&self, vault: &VaultFile, raw: &[u8]) -> Option<(Format, Zeroizing<Vec<u8>>)> {
+        let header = raw.get(..HEADER_SIZE)?;
+        let mut lru = self.lru();
+        let i = lru.entries.iter().position(|e| {
+            e.path == vault.path
+                && e.header[..] == *header
+                && bool::from(e.password.as_bytes().ct_eq(vault.password.as_bytes()))
+        })?;
+        let entry = lru.entries.remove(i);
+        let cached = (entry.format, entry.plaintext.clone());
+        lru.entries.push(entry);
+        Some(cached)
+    }
+
+    fn insert(&self, vault: &VaultFile, raw: &[u8], format: Format, plaintext: &[u8]) {
+        let Some(header) = raw.get(..HEADER_SIZE).and_then(|h| h.try_into().ok()) else {
+            return;
+        };
+        let mut lru = self.lru();
+        lru.entries.retain(|e| e.path != vault.path);
+        if lru.capacity == 0 {
+            return;
+        }
+        if lru.entries.len() >= lru.capacity {
+            lru.entries.remove(0);
+        }
+        lru.entries.push(Entry {
+            path: vault.path.clone(),
+            password: vault.password.clone(),
+            header,
+            format,
+            plaintext: Zeroizing::new(plaintext.to_vec()),
+        });
+    }
+}
+
+impl fmt::Debug for VaultCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lru = self.lru();
+        f.debug_struct("VaultCache")
+            .field("capacity", &lru.capacity)
+            .field("len", &lru.entries.len())
+            .finish()
+    }
+}
+
+impl VaultFile {
+    /// Keep decrypted contents in `cache`, so repeated loads of an unchanged
+    /// vault skip the key derivation. See [`VaultCache`].
+    pub fn with_cache(mut self, cache: VaultCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// [`open_blob`](Self::open_blob) through the cache, if there is one.
+    pub(super) fn open_blob_cached(
+        &self,
+        raw: &[u8],
+    ) -> Result<(Format, Zeroizing<Vec<u8>>), SerdeVaultError> {
+        let Some(cache) = &self.cache else {
+            return self.open_blob(raw);
+        };
+        if let Some(cached) = cache.get(self, raw) {
+            return Ok(cached);
+        }
+        let (format, plaintext) = self.open_blob(raw)?;
+        cache.insert(self, raw, format, &plaintext);
+        Ok((format, plaintext))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_cache_hits_until_the_file_changes() {
+        let dir = tempdir().unwrap();
+        let cache = VaultCache::new(4);
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_cache(cache.clone());
+
+        vault.save(&1u32).unwrap();
+        assert_eq!(vault.load::<u32>().unwrap(), 1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(vault.load::<u32>().unwrap(), 1);
+
+        // Rewritten by a handle that doesn't share the cache
+        VaultFile::open(vault.path(), "pwd")
+            .with_params(8, 1, 1)
+            .save(&2u32)
+            .unwrap();
+        assert_eq!(vault.load::<u32>().unwrap(), 2);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_checks_password() {
+        let dir = tempdir().unwrap();
+        let cache = VaultCache::new(4);
+        let path = dir.path().join("vault.svlt");
+        let vault = VaultFile::open(&path, "pwd")
+            .with_params(8, 1, 1)
+            .with_cache(cache.clone());
+        vault.save(&1u32).unwrap();
+        vault.load::<u32>().unwrap();
+
+        let wrong = VaultFile::open(&path, "nope").with_cache(cache.clone());
+        assert!(matches!(
+            wrong.load::<u32>(),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let dir = tempdir().unwrap();
+        let cache = VaultCache::new(2);
+        let vaults: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let vault = VaultFile::open(dir.path().join(name), "pwd")
+                    .with_params(8, 1, 1)
+                    .with_cache(cache.clone());
+                vault.save(&name.to_string()).unwrap();
+                vault
+            })
+            .collect();
+
+        vaults[0].load::<String>().unwrap();
+        vaults[1].load::<String>().unwrap();
+        vaults[0].load::<String>().unwrap();
+        vaults[2].load::<String>().unwrap();
+
+        let cached: Vec<_> = cache.lru().entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(cached, [vaults[0].path(), vaults[2].path()]);
+    }
+}
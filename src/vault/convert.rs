@@ -0,0 +1,65 @@
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::decode;
+use crate::serializer::Format;
+use crate::wipe::SecretValue;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Re-encode the vault's contents as `format`, e.g. from `Format::Json` to a
+    /// binary [`Codec`](crate::Codec), without knowing their Rust type.
+    ///
+    /// The payload is decrypted, transcoded through a `serde_json::Value` (as
+    /// every format can hold one) and saved again, atomically and under the
+    /// update lock, with a new nonce. Schema versions and other header records
+    /// are kept as they are; no migration runs. Nothing is written if the vault
+    /// is already in `format`.
+    ///
+    /// Later saves still use the handle's own [`with_format`](Self::with_format),
+    /// so switch that too to stay on `format`.
+    pub fn convert(&self, format: Format) -> Result<(), SerdeVaultError> {
+        let _lock = self.lock_for_update()?;
+        let raw = self.read_raw()?;
+        let (header, _) = decode(&raw).map_err(self.ctx(Operation::Save))?;
+        let (current, plaintext) = self.open_blob(&raw)?;
+        if current == format {
+            return Ok(());
+        }
+        let value: SecretValue = current.deserialize(&plaintext)?;
+        let converted = format.serialize(&*value)?;
+        self.write_plaintext(format, &converted, header.extensions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_convert_between_formats() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&vec!["a", "b"]).unwrap();
+        let format_of = || {
+            decode(&std::fs::read(vault.path()).unwrap())
+                .unwrap()
+                .0
+                .format
+        };
+
+        vault.convert(Format::NdJson).unwrap();
+        assert_eq!(format_of(), Format::NdJson.id());
+        assert_eq!(vault.records::<String>().unwrap().count(), 2);
+        assert_eq!(vault.load::<Vec<String>>().unwrap(), ["a", "b"]);
+
+        vault.convert(Format::CanonicalJson).unwrap();
+        assert_eq!(format_of(), Format::CanonicalJson.id());
+        assert_eq!(vault.load::<Vec<String>>().unwrap(), ["a", "b"]);
+
+        vault.save(&7u32).unwrap();
+        let err = vault.convert(Format::NdJson).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SerializationError(_)));
+        assert_eq!(vault.load::<u32>().unwrap(), 7);
+    }
+}
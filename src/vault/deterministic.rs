@@ -0,0 +1,111 @@
+use blake2::digest::consts::U12;
+use blake2::digest::Mac;
+use blake2::Blake2bMac;
+
+use crate::crypto::cipher::NONCE_SIZE;
+use crate::error::SerdeVaultError;
+use crate::format::{decode, VaultHeader};
+
+use super::{VaultFile, VaultKey};
+
+impl VaultFile {
+    /// Make `save` produce identical files for identical data.
+    ///
+    /// The salt of the existing vault is kept when its Argon2 parameters match
+    /// this handle's, and the nonce is derived from the key, header and
+    /// plaintext (a synthetic IV) instead of drawn at random. Saving the same
+    /// payload twice then rewrites the same bytes, which suits content-addressed
+    /// storage and keeps diffs of encrypted configs quiet. Use it with
+    /// [`Format::CanonicalJson`](crate::Format::CanonicalJson) so that maps with
+    /// unstable iteration order encode the same way too.
+    ///
+    /// The trade-off is that anyone with two versions of the file can tell
+    /// whether they hold the same data. Different data still never shares a
+    /// nonce. Implies no [streaming](Self::with_streaming): the nonce needs the
+    /// whole plaintext before the first byte is encrypted.
+    pub fn with_deterministic_encryption(mut self, enabled: bool) -> Self {
+        self.deterministic = enabled;
+        self
+    }
+
+    /// The key to encrypt the next save under: the existing vault's salt in
+    /// deterministic mode, a fresh one otherwise.
+    pub(super) fn write_key(&self) -> Result<VaultKey, SerdeVaultError> {
+        if !self.deterministic {
+            return self.fresh_key();
+        }
+        let current = self
+            .read_raw()
+            .ok()
+            .and_then(|raw| decode(&raw).ok().map(|(h, _)| h));
+        match current {
+            Some(header)
+                if (header.m_cost, header.t_cost, header.p_cost)
+                    == (self.m_cost, self.t_cost, self.p_cost) =>
+            {
+                VaultKey::for_header(&self.password, &header)
+            }
+            _ => self.fresh_key(),
+        }
+    }
+}
+
+/// Replace `header`'s nonce with one derived from `plaintext`, keyed by the
+/// vault key: a BLAKE2b MAC over everything the header authenticates, with the
+/// nonce zeroed, followed by the plaintext.
+pub(super) fn set_synthetic_nonce(key: &VaultKey, header: &mut VaultHeader, plaintext: &[u8]) {
+    header.nonce = [0; NONCE_SIZE];
+    let mut mac =
+        Blake2bMac::<U12>::new_with_salt_and_personal(key.key.as_ref(), &[], b"serdevault-siv")
+            .expect("32-byte key and 14-byte persona are within BLAKE2b limits");
+    mac.update(&header.associated_data());
+    mac.update(plaintext);
+    header.nonce = mac.finalize().into_bytes().into();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::Format;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_equal_data_gives_identical_files() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_format(Format::CanonicalJson)
+            .with_deterministic_encryption(true);
+
+        let data: HashMap<String, u32> = (0..32).map(|i| (format!("k{i}"), i)).collect();
+        vault.save(&data).unwrap();
+        let first = std::fs::read(vault.path()).unwrap();
+        // A new map has its own hasher seed, so it iterates in another order.
+        let reordered: HashMap<String, u32> = (0..32).rev().map(|i| (format!("k{i}"), i)).collect();
+        vault.save(&reordered).unwrap();
+        assert_eq!(std::fs::read(vault.path()).unwrap(), first);
+        assert_eq!(vault.load::<HashMap<String, u32>>().unwrap(), data);
+
+        // Different data, different nonce.
+        vault
+            .save(&HashMap::from([("k0".to_string(), 1u32)]))
+            .unwrap();
+        let other = std::fs::read(vault.path()).unwrap();
+        let (h1, h2) = (decode(&first).unwrap().0, decode(&other).unwrap().0);
+        assert_eq!(h1.salt, h2.salt);
+        assert_ne!(h1.nonce, h2.nonce);
+
+        // Without the option every save re-salts.
+        let random = VaultFile::open(vault.path(), "pwd").with_params(8, 1, 1);
+        random.save(&data).unwrap();
+        assert_ne!(
+            decode(&std::fs::read(vault.path()).unwrap())
+                .unwrap()
+                .0
+                .salt,
+            h1.salt
+        );
+    }
+}
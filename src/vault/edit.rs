@@ -0,0 +1,145 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::SerdeVaultError;
+use crate::lock::VaultLock;
+
+use super::VaultFile;
+
+/// Mutable access to a vault's contents that is saved when the guard goes away.
+///
+/// Returned by [`VaultFile::edit`]. Dropping the guard saves the data, but
+/// `Drop` can't report failures — call [`commit`](Self::commit) to see them,
+/// or [`cancel`](Self::cancel) to discard the changes.
+pub struct EditGuard<'a, T: Serialize> {
+    vault: &'a VaultFile,
+    data: Option<T>,
+    _lock: VaultLock,
+}
+
+impl VaultFile {
+    /// Load the vault for editing under the same lock as [`update`](Self::update).
+    ///
+    /// ```no_run
+    /// # use serdevault::VaultFile;
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Config { retries: u32 }
+    /// # let vault = VaultFile::open("~/.my.vault", "my_password");
+    /// let mut config = vault.edit::<Config>()?;
+    /// config.retries += 1;
+    /// config.commit()?; // or just let it drop
+    /// # Ok::<(), serdevault::SerdeVaultError>(())
+    /// ```
+    pub fn edit<T>(&self) -> Result<EditGuard<'_, T>, SerdeVaultError>
+    where
+        T: Serialize + for<'de> Deserialize<'de>,
+    {
+        let lock = self.lock_for_update()?;
+        let data = self.load()?;
+        Ok(EditGuard {
+            vault: self,
+            data: Some(data),
+            _lock: lock,
+        })
+    }
+}
+
+impl<T: Serialize> EditGuard<'_, T> {
+    /// Save the edited data now and release the lock.
+    pub fn commit(mut self) -> Result<(), SerdeVaultError> {
+        match self.data.take() {
+            Some(data) => self.vault.save(&data),
+            None => Ok(()),
+        }
+    }
+
+    /// Release the lock without saving anything.
+    pub fn cancel(mut self) {
+        self.data = None;
+    }
+}
+
+impl<T: Serialize> Deref for EditGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.data.as_ref().expect("guard holds data until consumed")
+    }
+}
+
+impl<T: Serialize> DerefMut for EditGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.data.as_mut().expect("guard holds data until consumed")
+    }
+}
+
+impl<T: Serialize> Drop for EditGuard<'_, T> {
+    fn drop(&mut self) {
+        if let Some(data) = self.data.take() {
+            let _ = self.vault.save(&data);
+        }
+    }
+}
+
+impl<T: Serialize> fmt::Debug for EditGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EditGuard")
+            .field("path", &self.vault.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn counter(dir: &tempfile::TempDir) -> VaultFile {
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&vec![1u32]).unwrap();
+        vault
+    }
+
+    #[test]
+    fn test_edit_saves_on_drop_and_commit() {
+        let dir = tempdir().unwrap();
+        let vault = counter(&dir);
+
+        {
+            let mut data = vault.edit::<Vec<u32>>().unwrap();
+            data.push(2);
+        }
+        assert_eq!(vault.load::<Vec<u32>>().unwrap(), vec![1, 2]);
+
+        let mut data = vault.edit::<Vec<u32>>().unwrap();
+        data.push(3);
+        data.commit().unwrap();
+        assert_eq!(vault.load::<Vec<u32>>().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_edit_cancel_discards_changes() {
+        let dir = tempdir().unwrap();
+        let vault = counter(&dir);
+        let before = std::fs::read(vault.path()).unwrap();
+
+        let mut data = vault.edit::<Vec<u32>>().unwrap();
+        data.clear();
+        data.cancel();
+
+        assert_eq!(std::fs::read(vault.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn test_edit_read_only() {
+        let dir = tempdir().unwrap();
+        counter(&dir);
+        let ro = VaultFile::open_read_only(dir.path().join("vault.svlt"), "pwd");
+        assert!(matches!(
+            ro.edit::<Vec<u32>>().err().unwrap(),
+            SerdeVaultError::ReadOnly
+        ));
+    }
+}
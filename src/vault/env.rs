@@ -0,0 +1,147 @@
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::error::SerdeVaultError;
+use crate::wipe::SecretValue;
+
+use super::VaultFile;
+
+/// Restores the variables set by [`VaultFile::export_env_scoped`] to what they
+/// were before, when dropped.
+#[must_use = "the environment is restored as soon as the guard is dropped"]
+pub struct EnvGuard {
+    previous: Vec<(String, Option<OsString>)>,
+}
+
+impl VaultFile {
+    /// Set an environment variable for each field of the stored document, for
+    /// twelve-factor apps and the tools they run. Returns the names set.
+    ///
+    /// The document must be a flat JSON object, like a struct of strings and
+    /// numbers or a [`DotEnvVault`](crate::DotEnvVault)'s variables. Each field
+    /// becomes `prefix` followed by its name in upper case, e.g. `db_url` with
+    /// prefix `APP_` sets `APP_DB_URL`. Numbers and booleans are set as their
+    /// JSON text, `null` fields are skipped, and nested objects or arrays are
+    /// an `InvalidFormat` error, before anything is set.
+    ///
+    /// Once in the environment the secrets are out of the vault's hands: every
+    /// child process inherits them, other processes of the same user can often
+    /// read them (`/proc/<pid>/environ` on Linux), they end up in crash reports
+    /// and debug output, and they are never wiped from memory. Prefer passing
+    /// values around in the program when possible, and
+    /// [`export_env_scoped`](Self::export_env_scoped) to limit how long they
+    /// stay.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`std::env::set_var`]: no other thread may read or write the
+    /// environment at the same time, including through C libraries (e.g. DNS
+    /// lookups). Call it early in `main`, before spawning threads.
+    pub unsafe fn export_env(&self, prefix: &str) -> Result<Vec<String>, SerdeVaultError> {
+        let vars = self.env_vars(prefix)?;
+        for (name, value) in &vars {
+            env::set_var(name, value.as_str());
+        }
+        Ok(vars.into_iter().map(|(name, _)| name).collect())
+    }
+
+    /// Like [`export_env`](Self::export_env), but the variables are set back to
+    /// their previous values (or removed) when the returned guard is dropped,
+    /// e.g. around spawning one child process.
+    ///
+    /// # Safety
+    ///
+    /// As for [`export_env`](Self::export_env), for both this call and the
+    /// guard's drop.
+    pub unsafe fn export_env_scoped(&self, prefix: &str) -> Result<EnvGuard, SerdeVaultError> {
+        let vars = self.env_vars(prefix)?;
+        let mut previous = Vec::with_capacity(vars.len());
+        for (name, value) in vars {
+            previous.push((name.clone(), env::var_os(&name)));
+            env::set_var(&name, value.as_str());
+        }
+        Ok(EnvGuard { previous })
+    }
+
+    /// The variables the stored document maps to, checked before any is set.
+    fn env_vars(&self, prefix: &str) -> Result<Vec<(String, Zeroizing<String>)>, SerdeVaultError> {
+        let document: SecretValue = self.load()?;
+        let Value::Object(fields) = &*document else {
+            return Err(SerdeVaultError::InvalidFormat(
+                "export_env needs the vault to hold a JSON object".into(),
+            ));
+        };
+        let mut vars = Vec::with_capacity(fields.len());
+        for (field, value) in fields {
+            let name = format!("{prefix}{}", field.to_uppercase());
+            let value = Zeroizing::new(match value {
+                Value::Null => continue,
+                Value::String(s) => s.clone(),
+                Value::Bool(_) | Value::Number(_) => value.to_string(),
+                Value::Array(_) | Value::Object(_) => {
+                    return Err(SerdeVaultError::InvalidFormat(format!(
+                        "export_env needs a flat object, but {field:?} is nested"
+                    )))
+                }
+            });
+            if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+                return Err(SerdeVaultError::InvalidFormat(format!(
+                    "{name:?} can't be set as an environment variable"
+                )));
+            }
+            vars.push((name, value));
+        }
+        Ok(vars)
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (name, value) in self.previous.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EnvGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self.previous.iter().map(|(name, _)| name).collect();
+        f.debug_struct("EnvGuard").field("names", &names).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_env_scoped_restores() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault
+            .save(&json!({ "db_url": "postgres://x", "port": 5432, "debug": null }))
+            .unwrap();
+        env::set_var("SVTEST_PORT", "80");
+
+        let guard = unsafe { vault.export_env_scoped("SVTEST_") }.unwrap();
+        assert_eq!(env::var("SVTEST_DB_URL").unwrap(), "postgres://x");
+        assert_eq!(env::var("SVTEST_PORT").unwrap(), "5432");
+        assert!(env::var_os("SVTEST_DEBUG").is_none());
+        drop(guard);
+        assert!(env::var_os("SVTEST_DB_URL").is_none());
+        assert_eq!(env::var("SVTEST_PORT").unwrap(), "80");
+
+        vault.save(&json!({ "nested": { "a": 1 } })).unwrap();
+        assert!(unsafe { vault.export_env("SVTEST_") }.is_err());
+        env::remove_var("SVTEST_PORT");
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::atomic_write_with;
+use crate::paths;
+use crate::wipe::{SecretBuf, SecretValue};
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Write the decrypted contents to `path` as pretty-printed JSON — **in
+    /// plaintext**.
+    ///
+    /// Meant for debugging and manual backups; anyone who can read the file
+    /// can read the secrets. The file is written atomically and owner-only, like
+    /// the vault itself, but nothing else protects it: delete it when done.
+    /// Refuses to overwrite the vault with its own plaintext.
+    pub fn export_plaintext_json(&self, path: impl AsRef<Path>) -> Result<(), SerdeVaultError> {
+        let path = paths::expand(path.as_ref());
+        if path == self.path {
+            return Err(SerdeVaultError::InvalidConfig(
+                "refusing to export a vault's plaintext over the vault itself".to_string(),
+            ));
+        }
+        let value: SecretValue = self.load()?;
+        let mut json = SecretBuf::new();
+        write_pretty(&value, &mut json)?;
+        atomic_write_with(&path, &self.write_opts, |file| {
+            Ok(file.write_all(&json.into_inner())?)
+        })
+        .map_err(|e| e.context(Operation::Export, &path))
+    }
+
+    /// Like [`export_plaintext_json`](Self::export_plaintext_json), but into any
+    /// writer, e.g. stdout.
+    pub fn export_plaintext_json_to(&self, mut out: impl Write) -> Result<(), SerdeVaultError> {
+        let value: SecretValue = self.load()?;
+        write_pretty(&value, &mut out)
+    }
+}
+
+fn write_pretty(value: &serde_json::Value, out: &mut impl Write) -> Result<(), SerdeVaultError> {
+    serde_json::to_writer_pretty(&mut *out, value)
+        .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?;
+    Ok(out.write_all(b"\n")?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_export_plaintext_json() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault
+            .save(&serde_json::json!({ "token": "s3cr3t" }))
+            .unwrap();
+
+        let mut out = Vec::new();
+        vault.export_plaintext_json_to(&mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\n  \"token\": \"s3cr3t\"\n}\n"
+        );
+
+        let dump = dir.path().join("dump.json");
+        vault.export_plaintext_json(&dump).unwrap();
+        assert!(std::fs::read_to_string(&dump)
+            .unwrap()
+            .contains("\"s3cr3t\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&dump).unwrap().permissions().mode();
+            assert_eq!(mode & 0o077, 0);
+        }
+
+        let err = vault.export_plaintext_json(vault.path()).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::InvalidConfig(_)));
+    }
+}
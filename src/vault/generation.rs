@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ErrorKind, Operation, SerdeVaultError};
+use crate::format::{
+    decode, push_extension, remove_extension, VaultHeader, EXT_GENERATION, HEADER_SIZE,
+};
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// The vault's generation: a counter in the header that every save raises
+    /// by one, for optimistic concurrency with
+    /// [`save_if_unchanged`](Self::save_if_unchanged).
+    ///
+    /// 0 if the vault doesn't exist yet, and for vaults last written by a build
+    /// without generations or in [deterministic mode](Self::with_deterministic_encryption),
+    /// which keeps identical data byte-identical and so records none. Only the
+    /// header is read; nothing is decrypted.
+    pub fn generation(&self) -> Result<u64, SerdeVaultError> {
+        self.read_generation(Operation::Load)
+    }
+
+    /// Like [`load`](Self::load), but also return the generation of the
+    /// loaded file, to pass to [`save_if_unchanged`](Self::save_if_unchanged).
+    pub fn load_with_generation<T: for<'de> Deserialize<'de>>(
+        &self,
+    ) -> Result<(T, u64), SerdeVaultError> {
+        let raw = self.read_raw()?;
+        let (header, _) = decode(&raw).map_err(self.ctx(Operation::Load))?;
+        let (format, plaintext) = self.open_blob_cached(&raw)?;
+        Ok((
+            self.decode_payload(format, &plaintext)?,
+            header_generation(&header),
+        ))
+    }
+
+    /// Save `data` only if the vault is still at generation `expected`, i.e.
+    /// nobody saved it since it was loaded with
+    /// [`load_with_generation`](Self::load_with_generation). Returns the new
+    /// generation.
+    ///
+    /// Otherwise fails with [`SerdeVaultError::GenerationConflict`] and writes
+    /// nothing, so the caller can load again, reapply or merge its change, and
+    /// retry — without holding the lock while the user edits, as
+    /// [`update`](Self::update) would. `expected` 0 creates a vault that
+    /// doesn't exist yet.
+    pub fn save_if_unchanged<T: Serialize>(
+        &self,
+        data: &T,
+        expected: u64,
+    ) -> Result<u64, SerdeVaultError> {
+        let _lock = self.lock_for_update()?;
+        let found = self.read_generation(Operation::Save)?;
+        if found != expected {
+            return Err(SerdeVaultError::GenerationConflict { expected, found });
+        }
+        self.save(data)?;
+        self.read_generation(Operation::Save)
+    }
+
+    /// Record the generation of the save about to replace the current file in
+    /// `extensions`. Must run under the exclusive lock.
+    pub(super) fn stamp_generation(&self, extensions: &mut Vec<u8>) -> Result<(), SerdeVaultError> {
+        remove_extension(extensions, EXT_GENERATION);
+        if self.deterministic {
+            return Ok(());
+        }
+        // A damaged vault is overwritten like any other; it starts over at 1.
+        let current = match self.read_generation(Operation::Save) {
+            Err(e) if e.kind() == ErrorKind::InvalidFormat => 0,
+            other => other?,
+        };
+        push_extension(extensions, EXT_GENERATION, &(current + 1).to_le_bytes());
+        Ok(())
+    }
+
+    /// The generation in the header of the vault file, 0 if there is none.
+    fn read_generation(&self, operation: Operation) -> Result<u64, SerdeVaultError> {
+        Ok(self
+            .read_header(operation)?
+            .map_or(0, |header| header_generation(&header)))
+    }
+
+    /// The header of the vault file, without reading the rest; `None` if the
+    /// vault doesn't exist.
+    pub(super) fn read_header(
+        &self,
+        operation: Operation,
+    ) -> Result<Option<VaultHeader>, SerdeVaultError> {
+        let target = self.target_path(operation)?;
+        let mut head = Vec::new();
+        // The header with the largest possible extension area.
+        let limit = (HEADER_SIZE + usize::from(u16::MAX)) as u64;
+        match File::open(&target).and_then(|file| file.take(limit).read_to_end(&mut head)) {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(self.ctx(operation)(e)),
+        }
+        let (header, _) = decode(&head).map_err(self.ctx(operation))?;
+        Ok(Some(header))
+    }
+}
+
+fn header_generation(header: &VaultHeader) -> u64 {
+    header
+        .extension(EXT_GENERATION)
+        .and_then(|v| <[u8; 8]>::try_from(v).ok())
+        .map_or(0, u64::from_le_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_if_unchanged_detects_other_writers() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        assert_eq!(vault.generation().unwrap(), 0);
+        assert_eq!(vault.save_if_unchanged(&vec![1], 0).unwrap(), 1);
+
+        let (mut mine, generation) = vault.load_with_generation::<Vec<u32>>().unwrap();
+        let other = VaultFile::open(vault.path(), "pwd").with_params(8, 1, 1);
+        other
+            .update(|theirs: &mut Vec<u32>| theirs.push(2))
+            .unwrap();
+        assert_eq!(vault.generation().unwrap(), 2);
+
+        mine.push(3);
+        let err = vault.save_if_unchanged(&mine, generation).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            SerdeVaultError::GenerationConflict {
+                expected: 1,
+                found: 2
+            }
+        ));
+        assert_eq!(err.kind(), crate::ErrorKind::Conflict);
+
+        let (mut merged, generation) = vault.load_with_generation::<Vec<u32>>().unwrap();
+        merged.push(3);
+        assert_eq!(vault.save_if_unchanged(&merged, generation).unwrap(), 3);
+        assert_eq!(other.load::<Vec<u32>>().unwrap(), [1, 2, 3]);
+
+        // Copies start their own count rather than inheriting the source's.
+        let copy = dir.path().join("copy.svlt");
+        vault.copy_to(&copy, "new").unwrap();
+        let copy = VaultFile::open(&copy, "new").with_params(8, 1, 1);
+        assert_eq!(copy.generation().unwrap(), 1);
+    }
+}
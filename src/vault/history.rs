@@ -0,0 +1,229 @@
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+
+use crate::artifacts;
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::atomic_write;
+
+use super::VaultFile;
+
+const HISTORY_MAGIC: &[u8; 4] = b"SVHS";
+const HISTORY_VERSION: u8 = 1;
+
+/// Layout:
+///   [4]  magic
+///   [1]  version
+///   then, oldest first, per revision:
+///   [8]  archived_at (u64 LE, seconds since the Unix epoch)
+///   [4]  len (u32 LE)
+///   [len] the complete encrypted vault file
+const RECORD_HEADER: usize = 8 + 4;
+
+/// One archived revision of a vault, as listed by [`VaultFile::list_versions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionInfo {
+    /// Pass to [`VaultFile::load_version`]. 0 is the most recent previous revision.
+    pub index: usize,
+    /// When this revision was replaced by a newer save.
+    pub archived_at: SystemTime,
+    /// Size of the encrypted revision in bytes.
+    pub size: usize,
+}
+
+struct Revision {
+    archived_at: u64,
+    data: Vec<u8>,
+}
+
+impl VaultFile {
+    /// Keep the last `keep` revisions of the vault in a `.<name>.history` sidecar.
+    ///
+    /// Each save moves the file it replaces into the sidecar, so an accidental
+    /// overwrite can be undone with [`load_version`](Self::load_version).
+    /// Revisions stay encrypted under the password they were saved with.
+    /// 0 (the default) disables history.
+    pub fn with_history(mut self, keep: usize) -> Self {
+        self.history = keep;
+        self
+    }
+
+    /// Archived revisions, most recent first.
+    pub fn list_versions(&self) -> Result<Vec<VersionInfo>, SerdeVaultError> {
+        let revisions = self.read_history().map_err(self.ctx(Operation::Load))?;
+        Ok(revisions
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(index, r)| VersionInfo {
+                index,
+                archived_at: UNIX_EPOCH + Duration::from_secs(r.archived_at),
+                size: r.data.len(),
+            })
+            .collect())
+    }
+
+    /// Decrypt and deserialize archived revision `index` (0 = most recent).
+    pub fn load_version<T: for<'de> Deserialize<'de>>(
+        &self,
+        index: usize,
+    ) -> Result<T, SerdeVaultError> {
+        let revisions = self.read_history().map_err(self.ctx(Operation::Load))?;
+        let revision = revisions
+            .iter()
+            .rev()
+            .nth(index)
+            .ok_or(SerdeVaultError::VersionNotFound(index))?;
+
+        let (format, plaintext) = self.open_blob(&revision.data)?;
+        self.decode_payload(format, &plaintext)
+    }
+
+    /// Move the current vault file into the history sidecar, dropping the
+    /// oldest revisions beyond the configured limit.
+    pub(super) fn archive_current(&self) -> Result<(), SerdeVaultError> {
+        let current = match fs::read(self.target_path(Operation::Save)?) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut revisions = self.read_history()?;
+        revisions.push(Revision {
+            archived_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            data: current,
+        });
+        let excess = revisions.len().saturating_sub(self.history);
+        revisions.drain(..excess);
+
+        atomic_write(
+            &artifacts::history_path(&self.path),
+            &encode_history(&revisions)?,
+            &self.write_opts,
+        )
+    }
+
+    fn read_history(&self) -> Result<Vec<Revision>, SerdeVaultError> {
+        read_history_file(&artifacts::history_path(&self.path))
+    }
+}
+
+fn read_history_file(path: &Path) -> Result<Vec<Revision>, SerdeVaultError> {
+    let raw = match fs::read(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    decode_history(&raw)
+}
+
+fn encode_history(revisions: &[Revision]) -> Result<Vec<u8>, SerdeVaultError> {
+    let total: usize = revisions.iter().map(|r| RECORD_HEADER + r.data.len()).sum();
+    let mut buf = Vec::with_capacity(5 + total);
+    buf.extend_from_slice(HISTORY_MAGIC);
+    buf.push(HISTORY_VERSION);
+    for r in revisions {
+        buf.extend_from_slice(&r.archived_at.to_le_bytes());
+        buf.extend_from_slice(&record_len(r.data.len())?.to_le_bytes());
+        buf.extend_from_slice(&r.data);
+    }
+    Ok(buf)
+}
+
+/// The length of a revision as recorded in the sidecar, which has 32 bits for it.
+fn record_len(len: usize) -> Result<u32, SerdeVaultError> {
+    u32::try_from(len).map_err(|_| {
+        SerdeVaultError::SerializationError(format!(
+            "vault of {len} bytes is too large for the history sidecar"
+        ))
+    })
+}
+
+fn decode_history(data: &[u8]) -> Result<Vec<Revision>, SerdeVaultError> {
+    let invalid = |msg: &str| SerdeVaultError::InvalidFormat(format!("history sidecar: {msg}"));
+
+    if data.len() < 5 || &data[0..4] != HISTORY_MAGIC {
+        return Err(invalid("invalid magic number"));
+    }
+    if data[4] != HISTORY_VERSION {
+        return Err(SerdeVaultError::UnsupportedVersion(data[4]));
+    }
+
+    let mut revisions = Vec::new();
+    let mut rest = &data[5..];
+    while !rest.is_empty() {
+        if rest.len() < RECORD_HEADER {
+            return Err(invalid("truncated record header"));
+        }
+        let archived_at = u64::from_le_bytes(rest[0..8].try_into().unwrap());
+        let len = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+        rest = &rest[RECORD_HEADER..];
+        if rest.len() < len {
+            return Err(invalid("truncated record"));
+        }
+        revisions.push(Revision {
+            archived_at,
+            data: rest[..len].to_vec(),
+        });
+        rest = &rest[len..];
+    }
+    Ok(revisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_history_keeps_last_revisions() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_history(2);
+
+        assert!(vault.list_versions().unwrap().is_empty());
+        for n in 1..=4u32 {
+            vault.save(&n).unwrap();
+        }
+
+        let versions = vault.list_versions().unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(versions[0].index, 0);
+        assert_eq!(vault.load::<u32>().unwrap(), 4);
+        assert_eq!(vault.load_version::<u32>(0).unwrap(), 3);
+        assert_eq!(vault.load_version::<u32>(1).unwrap(), 2);
+
+        let err = vault.load_version::<u32>(2).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::VersionNotFound(2)));
+
+        vault.destroy().unwrap();
+        assert!(!artifacts::history_path(&vault.path).exists());
+    }
+
+    #[test]
+    fn test_history_rejects_truncated_sidecar() {
+        let revisions = vec![Revision {
+            archived_at: 1,
+            data: vec![7; 10],
+        }];
+        let encoded = encode_history(&revisions).unwrap();
+        assert_eq!(decode_history(&encoded).unwrap()[0].data, vec![7; 10]);
+
+        let err = decode_history(&encoded[..encoded.len() - 1]).err().unwrap();
+        assert!(matches!(err, SerdeVaultError::InvalidFormat(_)));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn test_history_rejects_oversized_revision() {
+        assert_eq!(record_len(u32::MAX as usize).unwrap(), u32::MAX);
+        let err = record_len(u32::MAX as usize + 1).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SerializationError(_)));
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::bytes::{bloated_fields, BinaryBloat};
+use crate::error::SerdeVaultError;
+use crate::serializer::Format;
+
+use super::VaultFile;
+
+/// Callback for [`VaultFile::with_binary_lint`].
+pub(super) type BinaryLint = Arc<dyn Fn(&BinaryBloat) + Send + Sync>;
+
+impl VaultFile {
+    /// Call `warn` on every save in a JSON format, once for each byte buffer
+    /// stored as an integer array (see [`bloated_fields`]).
+    ///
+    /// Meant for development builds: it flags `Vec<u8>` fields that take up to
+    /// four times their size, which a binary [`Codec`](crate::Codec) plus
+    /// [`serdevault::bytes`](crate::bytes) would store raw. Costs one extra
+    /// serialization per save.
+    pub fn with_binary_lint(mut self, warn: impl Fn(&BinaryBloat) + Send + Sync + 'static) -> Self {
+        self.binary_lint = Some(Arc::new(warn));
+        self
+    }
+
+    /// Run the binary lint, if any, on `data` about to be saved as `format`.
+    pub(super) fn lint_binary<T: Serialize>(
+        &self,
+        format: Format,
+        data: &T,
+    ) -> Result<(), SerdeVaultError> {
+        let Some(warn) = &self.binary_lint else {
+            return Ok(());
+        };
+        if matches!(
+            format,
+            Format::Json | Format::CanonicalJson | Format::NdJson
+        ) {
+            bloated_fields(data)?.iter().for_each(|bloat| warn(bloat));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_binary_lint_reports_json_saves() {
+        let dir = tempdir().unwrap();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_binary_lint(move |bloat| sink.lock().unwrap().push(bloat.path.clone()));
+
+        let data = serde_json::json!({ "blob": vec![200u8; 64], "name": "x" });
+        vault.save(&data).unwrap();
+        assert_eq!(*seen.lock().unwrap(), ["blob"]);
+    }
+}
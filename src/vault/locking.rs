@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use crate::error::{Operation, SerdeVaultError};
+use crate::lock::VaultLock;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Wait at most `timeout` for the vault's advisory lock, then fail with
+    /// [`SerdeVaultError::WouldBlock`] instead of waiting on. By default calls
+    /// wait for as long as it takes; `Duration::ZERO` makes them try once.
+    ///
+    /// Every save (including backups and history) runs under an exclusive lock,
+    /// as do `update`, `edit` and the writes of stores and logs, so processes
+    /// that save the same vault at once take turns.
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Take a shared lock around each `load`, so reads wait for writers in
+    /// other processes instead of running alongside them. Off by default: saves
+    /// replace the file atomically, so a load sees either the old or the new
+    /// vault either way.
+    pub fn with_locked_loads(mut self, enabled: bool) -> Self {
+        self.locked_loads = enabled;
+        self
+    }
+
+    /// Take the vault's lock for `operation`, honoring the lock timeout.
+    pub(super) fn lock(
+        &self,
+        shared: bool,
+        operation: Operation,
+    ) -> Result<VaultLock, SerdeVaultError> {
+        VaultLock::acquire(&self.path, shared, self.lock_timeout).map_err(self.ctx(operation))
+    }
+
+    /// A shared lock for reading the vault, if [`with_locked_loads`](Self::with_locked_loads)
+    /// is on.
+    pub(super) fn lock_for_load(&self) -> Result<Option<VaultLock>, SerdeVaultError> {
+        match self.locked_loads {
+            true => self.lock(true, Operation::Load).map(Some),
+            false => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lock_timeout_fails_with_would_block() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_lock_timeout(Duration::from_millis(20))
+            .with_locked_loads(true);
+        vault.save(&1u32).unwrap();
+
+        // Nested locking on one thread doesn't wait.
+        vault.update(|n: &mut u32| *n += 1).unwrap();
+
+        let (locked, release) = (mpsc::channel(), mpsc::channel::<()>());
+        let holder = {
+            let vault = vault.clone();
+            thread::spawn(move || {
+                vault.edit::<u32>().map(|_guard| {
+                    locked.0.send(()).unwrap();
+                    release.1.recv().unwrap();
+                })
+            })
+        };
+        locked.1.recv().unwrap();
+        for result in [vault.save(&3u32), vault.load::<u32>().map(drop)] {
+            let err = result.unwrap_err();
+            assert!(matches!(err.root(), SerdeVaultError::WouldBlock));
+            assert_eq!(err.kind(), crate::ErrorKind::WouldBlock);
+        }
+        release.0.send(()).unwrap();
+        holder.join().unwrap().unwrap();
+        assert_eq!(vault.load::<u32>().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_nested_locks_across_paths_and_modes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("vault.svlt");
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        let now = Some(Duration::ZERO);
+
+        // The same vault through another path doesn't wait on its own lock.
+        let outer = VaultLock::acquire(&path, false, now).unwrap();
+        let detour = dir.path().join("sub").join("..").join("vault.svlt");
+        VaultLock::acquire(&detour, false, now).unwrap();
+        drop(outer);
+
+        let shared = VaultLock::acquire(&path, true, now).unwrap();
+        VaultLock::acquire(&detour, true, now).unwrap();
+        let err = VaultLock::acquire(&detour, false, now).err().unwrap();
+        assert!(matches!(err, SerdeVaultError::LockUpgrade));
+        assert_eq!(err.kind(), crate::ErrorKind::WouldBlock);
+        drop(shared);
+        VaultLock::acquire(&path, false, now).unwrap();
+    }
+}
@@ -0,0 +1,128 @@
+use serde_json::{Map, Value};
+
+use crate::error::SerdeVaultError;
+use crate::wipe::SecretValue;
+
+use super::VaultFile;
+
+/// A field both sides changed differently since the common ancestor, as
+/// reported by [`VaultFile::merge_three_way`]. `None` means the field is absent
+/// on that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergeConflict {
+    /// JSON pointer to the field (`""` for the whole document).
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl VaultFile {
+    /// Reconcile this vault with `theirs`, a copy that diverged from it since
+    /// `base`, their last common version (e.g. a copy kept at the last sync).
+    ///
+    /// Meant for vaults synced between machines by Dropbox, Syncthing and the
+    /// like. Objects are merged field by field: a change made on one side only
+    /// is taken, and a field changed on both sides to different values is a
+    /// conflict. Arrays and scalars are merged as a whole. Conflicting fields
+    /// keep this vault's value; the merged document is saved either way, under
+    /// the same lock as [`update`](Self::update), and the conflicts are
+    /// returned with all three values so none of them is lost.
+    ///
+    /// Each handle decrypts with its own password.
+    pub fn merge_three_way(
+        &self,
+        base: &VaultFile,
+        theirs: &VaultFile,
+    ) -> Result<Vec<MergeConflict>, SerdeVaultError> {
+        let base: SecretValue = base.load()?;
+        let theirs: SecretValue = theirs.load()?;
+        self.update(|ours: &mut SecretValue| {
+            let mut conflicts = Vec::new();
+            let merged = merge(
+                Some(&base),
+                Some(&*ours),
+                Some(&theirs),
+                &mut String::new(),
+                &mut conflicts,
+            );
+            **ours = merged.unwrap_or(Value::Null);
+            conflicts
+        })
+    }
+}
+
+/// Three-way merge of one field, `None` where it is absent.
+fn merge(
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    path: &mut String,
+    conflicts: &mut Vec<MergeConflict>,
+) -> Option<Value> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    if let (Some(Value::Object(o)), Some(Value::Object(t))) = (ours, theirs) {
+        let empty = Map::new();
+        let b = base.and_then(Value::as_object).unwrap_or(&empty);
+        let mut merged = Map::new();
+        for key in o.keys().chain(t.keys().filter(|k| !o.contains_key(*k))) {
+            let len = path.len();
+            path.push('/');
+            path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+            if let Some(value) = merge(b.get(key), o.get(key), t.get(key), path, conflicts) {
+                merged.insert(key.clone(), value);
+            }
+            path.truncate(len);
+        }
+        return Some(Value::Object(merged));
+    }
+    conflicts.push(MergeConflict {
+        path: path.clone(),
+        base: base.cloned(),
+        ours: ours.cloned(),
+        theirs: theirs.cloned(),
+    });
+    ours.cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_three_way_merge_with_conflicts() {
+        let dir = tempdir().unwrap();
+        let vault_at =
+            |file: &str| VaultFile::open(dir.path().join(file), "pwd").with_params(8, 1, 1);
+        let (base, ours, theirs) = (vault_at("base"), vault_at("ours"), vault_at("theirs"));
+        base.save(&json!({ "db": { "user": "a", "pass": "x" }, "port": 1, "gone": true }))
+            .unwrap();
+        ours.save(&json!({ "db": { "user": "b", "pass": "x" }, "port": 2, "gone": true }))
+            .unwrap();
+        theirs
+            .save(&json!({ "db": { "user": "a", "pass": "y" }, "port": 3, "new/key": [1] }))
+            .unwrap();
+
+        let conflicts = ours.merge_three_way(&base, &theirs).unwrap();
+        assert_eq!(
+            conflicts,
+            [MergeConflict {
+                path: "/port".into(),
+                base: Some(json!(1)),
+                ours: Some(json!(2)),
+                theirs: Some(json!(3)),
+            }]
+        );
+        assert_eq!(
+            ours.load_value().unwrap(),
+            json!({ "db": { "user": "b", "pass": "y" }, "port": 2, "new/key": [1] })
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use zeroize::Zeroizing;
+
+use crate::error::{ErrorKind, SerdeVaultError};
+use crate::serializer::{parse_record, write_record, Format};
+use crate::wipe::SecretBuf;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Append one record to an [`NdJson`](Format::NdJson) vault, creating the
+    /// vault if it doesn't exist.
+    ///
+    /// See [`append_records`](Self::append_records).
+    pub fn append_record<T: Serialize>(&self, record: &T) -> Result<(), SerdeVaultError> {
+        self.append_records(std::slice::from_ref(record))
+    }
+
+    /// Append records to an [`NdJson`](Format::NdJson) vault, one line each,
+    /// under the same exclusive lock as [`update`](Self::update).
+    ///
+    /// The existing records are never parsed, only decrypted and re-encrypted
+    /// with the new lines after them. Records are stored as they are, without a
+    /// [migration](Self::with_migrations) envelope. Fails with
+    /// `DeserializationError` if the vault holds a payload in another format.
+    pub fn append_records<T: Serialize>(&self, records: &[T]) -> Result<(), SerdeVaultError> {
+        let _lock = self.lock_for_update()?;
+
+        let mut plaintext = match self.read_plaintext() {
+            Ok((format, plaintext)) => {
+                ensure_records(format)?;
+                SecretBuf::from_zeroizing(plaintext)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => SecretBuf::new(),
+            Err(e) => return Err(e),
+        };
+        for record in records {
+            write_record(record, &mut plaintext)?;
+        }
+        self.write_plaintext(Format::NdJson, &plaintext.into_inner(), Vec::new())
+    }
+
+    /// Decrypt an [`NdJson`](Format::NdJson) vault and iterate over its records,
+    /// parsing each only when it is reached.
+    ///
+    /// Blank lines are skipped. A record that doesn't parse as `T` yields an error
+    /// naming its line, and iteration can continue past it. The decrypted payload
+    /// is wiped when the iterator is dropped.
+    pub fn records<T: DeserializeOwned>(&self) -> Result<Records<T>, SerdeVaultError> {
+        let (format, plaintext) = self.read_plaintext()?;
+        ensure_records(format)?;
+        Ok(Records {
+            plaintext,
+            pos: 0,
+            line: 0,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Lazy iterator over the records of an NDJSON vault, from [`VaultFile::records`].
+pub struct Records<T> {
+    plaintext: Zeroizing<Vec<u8>>,
+    pos: usize,
+    line: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> Iterator for Records<T> {
+    type Item = Result<T, SerdeVaultError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.plaintext.len() {
+            let rest = &self.plaintext[self.pos..];
+            let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+            let record = &rest[..end];
+            self.pos += end + 1;
+            self.line += 1;
+            if !record.iter().all(u8::is_ascii_whitespace) {
+                return Some(parse_record(self.line, record));
+            }
+        }
+        None
+    }
+}
+
+impl<T> fmt::Debug for Records<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Records")
+            .field("line", &self.line)
+            .finish_non_exhaustive()
+    }
+}
+
+fn ensure_records(format: Format) -> Result<(), SerdeVaultError> {
+    if format != Format::NdJson {
+        return Err(SerdeVaultError::DeserializationError(format!(
+            "vault holds a {format:?} payload, not NDJSON records"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Event {
+        id: u32,
+        kind: String,
+    }
+
+    fn event(id: u32) -> Event {
+        Event {
+            id,
+            kind: format!("k{id}"),
+        }
+    }
+
+    #[test]
+    fn test_append_and_iterate_records() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("events.svlt"), "pwd").with_params(8, 1, 1);
+
+        vault.append_record(&event(1)).unwrap();
+        vault.append_records(&[event(2), event(3)]).unwrap();
+
+        let events: Vec<Event> = vault.records().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(events, [event(1), event(2), event(3)]);
+        // The whole payload also loads as a sequence.
+        assert_eq!(vault.load::<Vec<Event>>().unwrap().len(), 3);
+
+        // A record of another shape fails on its own line only.
+        vault
+            .append_record(&serde_json::json!({ "id": "x" }))
+            .unwrap();
+        let results: Vec<_> = vault.records::<Event>().unwrap().collect();
+        assert_eq!(results.len(), 4);
+        assert!(results[..3].iter().all(Result::is_ok));
+        let err = results[3].as_ref().unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DeserializationError(m) if m.contains("line 4")));
+    }
+
+    #[test]
+    fn test_records_need_ndjson_payload() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&vec![event(1)]).unwrap();
+
+        assert!(vault.records::<Event>().is_err());
+        assert!(vault.append_record(&event(2)).is_err());
+
+        let ndjson = vault.clone().with_format(Format::NdJson);
+        ndjson.save(&vec![event(1)]).unwrap();
+        assert!(matches!(
+            ndjson.save(&event(1)),
+            Err(SerdeVaultError::SerializationError(_))
+        ));
+        assert_eq!(vault.records::<Event>().unwrap().count(), 1);
+    }
+}
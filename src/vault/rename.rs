@@ -0,0 +1,203 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::Path;
+
+use crate::artifacts;
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{sync_dir, WriteOptions};
+use crate::paths;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Move the vault, with its backups, history and snapshots, to `new_path`,
+    /// and point this handle at it.
+    ///
+    /// Each file is hard-linked at its destination and then unlinked at its
+    /// source when both are on the same filesystem. Otherwise it is copied to
+    /// the destination, the copy and its directory are fsynced, and only then is
+    /// it deleted at the source, so an interruption can leave a duplicate but
+    /// never lose data. Runs under the vault's exclusive lock, whose lock file is
+    /// removed at the end. Fails, having moved nothing, if something already
+    /// exists at `new_path`.
+    ///
+    /// A symlinked vault path is handled per its [`SymlinkPolicy`](super::SymlinkPolicy):
+    /// under `Follow` the link's target is moved and the link removed, under
+    /// `Refuse` nothing is, and under `Replace` the link itself is moved.
+    pub fn rename(&mut self, new_path: impl AsRef<Path>) -> Result<(), SerdeVaultError> {
+        let new_path = paths::expand(new_path.as_ref());
+        self.move_all(&new_path)?;
+        self.path = new_path;
+        Ok(())
+    }
+
+    fn move_all(&self, new_path: &Path) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let _lock = self.lock(false, Operation::Rename)?;
+        let ctx = self.ctx(Operation::Rename);
+
+        // A symlinked vault is moved as `save` would write it: its target under
+        // `Follow`, the link itself under `Replace`.
+        let source = self.target_path(Operation::Rename)?;
+        if !source.exists() {
+            return Err(SerdeVaultError::VaultNotFound(self.path.clone()));
+        }
+
+        // The vault claims `new_path` first, so an occupied destination fails
+        // before anything moved, and leaves its old path last: until then, this
+        // handle's path stays valid.
+        fs::create_dir_all(artifacts::parent_dir(new_path)).map_err(&ctx)?;
+        place_file(&source, new_path, &self.write_opts).map_err(&ctx)?;
+
+        for backup in artifacts::backups(&self.path).map_err(&ctx)? {
+            if let Some(stamp) = artifacts::backup_stamp(&self.path, &backup) {
+                let target = artifacts::backup_path(new_path, stamp);
+                move_file(&backup, &target, &self.write_opts).map_err(&ctx)?;
+            }
+        }
+
+        let history = artifacts::history_path(&self.path);
+        if history.exists() {
+            let target = artifacts::history_path(new_path);
+            move_file(&history, &target, &self.write_opts).map_err(&ctx)?;
+        }
+
+        let snapshots = artifacts::snapshots(&self.path).map_err(&ctx)?;
+        if !snapshots.is_empty() {
+            let target_dir = artifacts::snapshot_dir(new_path);
+            fs::create_dir_all(&target_dir).map_err(&ctx)?;
+            for snapshot in &snapshots {
+                let label = artifacts::snapshot_label(snapshot).unwrap_or_default();
+                let target = artifacts::snapshot_path(new_path, &label);
+                move_file(snapshot, &target, &self.write_opts).map_err(&ctx)?;
+            }
+            // Leftover temp files keep the old directory alive; that's fine.
+            let _ = fs::remove_dir(artifacts::snapshot_dir(&self.path));
+        }
+
+        fs::remove_file(&source).map_err(&ctx)?;
+        if source != self.path {
+            fs::remove_file(&self.path).map_err(&ctx)?;
+        }
+        // Still held by this handle, so no writer is waiting on it.
+        match fs::remove_file(artifacts::lock_path(&self.path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(ctx(e)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Move `from` to `to`, failing rather than replacing anything at `to`.
+fn move_file(from: &Path, to: &Path, opts: &WriteOptions) -> io::Result<()> {
+    place_file(from, to, opts)?;
+    fs::remove_file(from)
+}
+
+/// Put a copy of `from` at `to`, which must not exist: a hard link, or where
+/// there can be none (another filesystem, or one without links) a new file that
+/// the contents are copied to and synced. Either way `to` is created atomically,
+/// so a file that appears there meanwhile is never overwritten, and its
+/// directory is synced (unless `opts` says otherwise) so that `from` can be
+/// removed once this returns.
+fn place_file(from: &Path, to: &Path, opts: &WriteOptions) -> io::Result<()> {
+    match fs::hard_link(from, to) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", to.display()),
+            ))
+        }
+        Err(_) => {
+            let mut target = OpenOptions::new().write(true).create_new(true).open(to)?;
+            io::copy(&mut File::open(from)?, &mut target)?;
+            target.sync_all()?;
+        }
+    }
+    if opts.sync_dir {
+        sync_dir(artifacts::parent_dir(to))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_rename_moves_vault_and_artifacts() {
+        let dir = tempdir().unwrap();
+        let mut vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_backups(3)
+            .with_history(3);
+        vault.save(&1u8).unwrap();
+        vault.save(&2u8).unwrap();
+        vault.snapshot("before-move").unwrap();
+
+        let new_path = dir.path().join("moved").join("renamed.svlt");
+        vault.rename(&new_path).unwrap();
+
+        assert_eq!(vault.path(), new_path.as_path());
+        assert!(!dir.path().join("vault.svlt").exists());
+        assert_eq!(vault.load::<u8>().unwrap(), 2);
+        assert_eq!(vault.backups().unwrap().len(), 1);
+        assert_eq!(vault.load_version::<u8>(0).unwrap(), 1);
+        assert_eq!(vault.snapshots().unwrap(), vec!["before-move"]);
+
+        let old = VaultFile::open(dir.path().join("vault.svlt"), "pwd");
+        assert!(old.backups().unwrap().is_empty());
+        assert!(old.snapshots().unwrap().is_empty());
+        assert!(!artifacts::lock_path(old.path()).exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rename_moves_symlink_target() {
+        use crate::SymlinkPolicy;
+
+        let dir = tempdir().unwrap();
+        let real = dir.path().join("real.svlt");
+        VaultFile::open(&real, "pwd")
+            .with_params(8, 1, 1)
+            .save(&1u8)
+            .unwrap();
+        let link = dir.path().join("link.svlt");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let mut refused = VaultFile::open(&link, "pwd").with_symlink_policy(SymlinkPolicy::Refuse);
+        let err = refused.rename(dir.path().join("moved.svlt")).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SymlinkRefused(_)));
+
+        let mut vault = VaultFile::open(&link, "pwd").with_symlink_policy(SymlinkPolicy::Follow);
+        vault.rename(dir.path().join("moved.svlt")).unwrap();
+        assert!(!real.exists());
+        assert!(fs::symlink_metadata(&link).is_err());
+        let moved = fs::symlink_metadata(vault.path()).unwrap();
+        assert!(moved.file_type().is_file());
+        assert_eq!(vault.load::<u8>().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_rename_refuses_to_overwrite() {
+        let dir = tempdir().unwrap();
+        let mut vault = VaultFile::open(dir.path().join("a.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_backups(1);
+        vault.save(&0u8).unwrap();
+        vault.save(&1u8).unwrap();
+        std::fs::write(dir.path().join("b.svlt"), b"occupied").unwrap();
+
+        let err = vault.rename(dir.path().join("b.svlt")).unwrap_err();
+
+        assert_eq!(err.operation(), Some(Operation::Rename));
+        assert_eq!(vault.path(), dir.path().join("a.svlt").as_path());
+        assert_eq!(vault.load::<u8>().unwrap(), 1);
+        assert_eq!(vault.backups().unwrap().len(), 1);
+        assert_eq!(
+            std::fs::read(dir.path().join("b.svlt")).unwrap(),
+            b"occupied"
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+
+use serde::Deserialize;
+use zeroize::Zeroize;
+
+use crate::error::SerdeVaultError;
+
+use super::VaultFile;
+
+/// A decrypted value that is wiped from memory when dropped.
+///
+/// Returned by [`VaultFile::load_secret`]. The value is only reachable through
+/// [`expose`](Self::expose), and `Debug` prints nothing of it, so it doesn't end
+/// up in logs by accident.
+pub struct VaultSecret<T: Zeroize> {
+    value: T,
+}
+
+impl<T: Zeroize> VaultSecret<T> {
+    /// Wrap a value so it is zeroized on drop.
+    pub fn new(value: T) -> Self {
+        Self { value }
+    }
+
+    /// Borrow the secret.
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    /// Borrow the secret mutably.
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Zeroize> Drop for VaultSecret<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for VaultSecret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VaultSecret([REDACTED])")
+    }
+}
+
+impl VaultFile {
+    /// Like [`load`](Self::load), but return the data in a [`VaultSecret`] that
+    /// zeroizes it on drop.
+    ///
+    /// Use this for credentials: `String`, `Vec<u8>` and structs deriving
+    /// `Zeroize` are wiped instead of lingering on the heap after use.
+    pub fn load_secret<T>(&self) -> Result<VaultSecret<T>, SerdeVaultError>
+    where
+        T: Zeroize + for<'de> Deserialize<'de>,
+    {
+        self.load().map(VaultSecret::new)
+    }
+
+    /// Decrypt the vault, pass the data to `f`, and wipe it as soon as `f` returns.
+    ///
+    /// The plaintext only exists for the duration of the call:
+    ///
+    /// ```no_run
+    /// # use serdevault::VaultFile;
+    /// # let vault = VaultFile::open("~/.my.vault", "my_password");
+    /// let token_len = vault.with_data(|token: &String| token.len())?;
+    /// # Ok::<(), serdevault::SerdeVaultError>(())
+    /// ```
+    pub fn with_data<T, R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, SerdeVaultError>
+    where
+        T: Zeroize + for<'de> Deserialize<'de>,
+    {
+        let secret = self.load_secret::<T>()?;
+        Ok(f(secret.expose()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_secret() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&"hunter2").unwrap();
+
+        let mut secret = vault.load_secret::<String>().unwrap();
+        assert_eq!(secret.expose(), "hunter2");
+        assert_eq!(format!("{secret:?}"), "VaultSecret([REDACTED])");
+
+        secret.expose_mut().push('!');
+        assert_eq!(secret.expose(), "hunter2!");
+    }
+
+    #[test]
+    fn test_with_data() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&vec![1u8, 2, 3]).unwrap();
+
+        let sum = vault
+            .with_data(|bytes: &Vec<u8>| bytes.iter().sum::<u8>())
+            .unwrap();
+        assert_eq!(sum, 6);
+
+        let missing = VaultFile::open(dir.path().join("missing.svlt"), "pwd");
+        assert!(missing.with_data(|_: &Vec<u8>| ()).is_err());
+    }
+}
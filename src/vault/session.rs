@@ -0,0 +1,340 @@
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto::kdf::SALT_SIZE;
+use crate::error::{ErrorKind, Operation, SerdeVaultError};
+use crate::format::decode;
+use crate::serializer::Format;
+
+use super::{VaultFile, VaultKey};
+
+/// A vault whose contents can't be read until it is [`unlock`](Self::unlock)ed.
+///
+/// Holds the vault's settings but no password or key material. Together with
+/// [`UnlockedVault`] this gives password-manager-style apps a locked state the
+/// compiler enforces.
+#[derive(Clone)]
+pub struct LockedVault {
+    vault: VaultFile,
+}
+
+/// An unlocked vault holding the derived key, so `load` and `save` skip the
+/// key derivation. [`lock`](Self::lock) wipes the key, as does the idle timer
+/// set with [`auto_lock`](Self::auto_lock).
+///
+/// Saves reuse the salt the vault was unlocked with (the nonce is fresh every
+/// time). If another handle rewrites the vault under a new salt, or the handle
+/// has auto-locked, calls return [`SerdeVaultError::Locked`].
+pub struct UnlockedVault {
+    vault: VaultFile,
+    session: Arc<Session>,
+}
+
+struct Session {
+    state: Mutex<SessionState>,
+    wake: Condvar,
+}
+
+struct SessionState {
+    /// `None` once locked.
+    key: Option<VaultKey>,
+    last_used: Instant,
+    on_lock: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl LockedVault {
+    /// A locked handle to the vault at `path`, with default settings.
+    pub fn open(path: impl AsRef<Path>) -> Self {
+        VaultFile::open(path, "").into()
+    }
+
+    /// Derive the key from `password` and check it against the vault.
+    ///
+    /// Fails with [`SerdeVaultError::DecryptionFailed`] for a wrong password. A
+    /// vault that doesn't exist yet unlocks with any password, which it will be
+    /// created under on the first `save`.
+    pub fn unlock(&self, password: &str) -> Result<UnlockedVault, SerdeVaultError> {
+        let vault = &self.vault;
+        let key = match vault.read_raw() {
+            Ok(raw) => {
+                let (header, ciphertext) = decode(&raw).map_err(vault.ctx(Operation::Load))?;
+                let key = VaultKey::for_header(password, &header)?;
+                key.open(&header, ciphertext)?;
+                key
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                let mut salt = [0u8; SALT_SIZE];
+                OsRng.fill_bytes(&mut salt);
+                VaultKey::derive(password, salt, vault.m_cost, vault.t_cost, vault.p_cost)?
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(UnlockedVault {
+            vault: vault.clone(),
+            session: Arc::new(Session {
+                state: Mutex::new(SessionState {
+                    key: Some(key),
+                    last_used: Instant::now(),
+                    on_lock: None,
+                }),
+                wake: Condvar::new(),
+            }),
+        })
+    }
+
+    /// Location of the vault file.
+    pub fn path(&self) -> &Path {
+        self.vault.path()
+    }
+}
+
+impl From<VaultFile> for LockedVault {
+    /// Keep the handle's settings and forget its password.
+    fn from(mut vault: VaultFile) -> Self {
+        vault.password = Zeroizing::new(String::new());
+        Self { vault }
+    }
+}
+
+impl UnlockedVault {
+    /// Decrypt the vault with the cached key and deserialize it.
+    pub fn load<T: for<'de> Deserialize<'de>>(&self) -> Result<T, SerdeVaultError> {
+        let (format, plaintext) = self.open()?;
+        self.vault.decode_payload(format, &plaintext)
+    }
+
+    /// Serialize `data` and write it encrypted under the cached key.
+    ///
+    /// Fails with [`SerdeVaultError::Locked`], writing nothing, if the vault was
+    /// rewritten under another salt (e.g. a new password) since it was unlocked.
+    pub fn save<T: Serialize>(&self, data: &T) -> Result<(), SerdeVaultError> {
+        let plaintext = self.vault.encode_payload(self.vault.format, data)?;
+        let extensions = self.vault.extensions_for(data)?;
+        let key = self.session.key()?;
+        let _lock = self.vault.lock_for_update()?;
+        match self.vault.read_header(Operation::Save)? {
+            Some(header) if !key.fits(&header) => Err(SerdeVaultError::Locked),
+            _ => self
+                .vault
+                .seal_and_write(self.vault.format, &plaintext, extensions, &key),
+        }
+    }
+
+    /// Wipe the key after `idle` without a `load` or `save`.
+    ///
+    /// The handle then behaves as locked: every call returns
+    /// [`SerdeVaultError::Locked`] until it is [`lock`](Self::lock)ed and unlocked
+    /// again. Use [`on_auto_lock`](Self::on_auto_lock) to update the UI when it happens.
+    pub fn auto_lock(self, idle: Duration) -> Self {
+        let session = Arc::clone(&self.session);
+        thread::spawn(move || session.expire_after(idle));
+        self
+    }
+
+    /// Run `callback` (on the timer thread) when the idle timer locks the vault.
+    pub fn on_auto_lock(self, callback: impl FnOnce() + Send + 'static) -> Self {
+        self.session.lock_state().on_lock = Some(Box::new(callback));
+        self
+    }
+
+    /// Whether the key has been wiped by the idle timer.
+    pub fn is_locked(&self) -> bool {
+        self.session.lock_state().key.is_none()
+    }
+
+    /// Wipe the key and return to the locked state.
+    pub fn lock(self) -> LockedVault {
+        LockedVault {
+            vault: self.vault.clone(),
+        }
+    }
+
+    /// Location of the vault file.
+    pub fn path(&self) -> &Path {
+        self.vault.path()
+    }
+
+    fn open(&self) -> Result<(Format, Zeroizing<Vec<u8>>), SerdeVaultError> {
+        let raw = self.vault.read_raw()?;
+        let (header, ciphertext) = decode(&raw).map_err(self.vault.ctx(Operation::Load))?;
+        let format = self.vault.payload_format(&header)?;
+        let key = self.session.key()?;
+        if !key.fits(&header) {
+            return Err(SerdeVaultError::Locked);
+        }
+        Ok((format, key.open(&header, ciphertext)?))
+    }
+}
+
+impl Drop for UnlockedVault {
+    fn drop(&mut self) {
+        // Wipes the key and lets the idle timer, if any, exit.
+        self.session.lock_state().key = None;
+        self.session.wake.notify_all();
+    }
+}
+
+impl Session {
+    fn lock_state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record a use of the key and return a copy of it, failing if it is gone.
+    /// The copy lets callers do their I/O without holding up the idle timer.
+    fn key(&self) -> Result<VaultKey, SerdeVaultError> {
+        let mut state = self.lock_state();
+        let key = state.key.clone().ok_or(SerdeVaultError::Locked)?;
+        state.last_used = Instant::now();
+        Ok(key)
+    }
+
+    /// Idle timer: wipe the key once it has gone unused for `idle`.
+    fn expire_after(&self, idle: Duration) {
+        let mut state = self.lock_state();
+        while state.key.is_some() {
+            let deadline = state.last_used + idle;
+            let now = Instant::now();
+            if now < deadline {
+                state = self
+                    .wake
+                    .wait_timeout(state, deadline - now)
+                    .unwrap_or_else(|e| e.into_inner())
+                    .0;
+                continue;
+            }
+            state.key = None;
+            let callback = state.on_lock.take();
+            drop(state);
+            if let Some(callback) = callback {
+                callback();
+            }
+            return;
+        }
+    }
+}
+
+impl fmt::Debug for LockedVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockedVault")
+            .field("path", &self.vault.path)
+            .finish()
+    }
+}
+
+impl fmt::Debug for UnlockedVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlockedVault")
+            .field("path", &self.vault.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn locked(dir: &tempfile::TempDir) -> LockedVault {
+        VaultFile::open(dir.path().join("vault.svlt"), "")
+            .with_params(8, 1, 1)
+            .into()
+    }
+
+    #[test]
+    fn test_unlock_load_save_lock() {
+        let dir = tempdir().unwrap();
+        let locked = locked(&dir);
+
+        let unlocked = locked.unlock("pwd").unwrap();
+        unlocked.save(&vec![1u32]).unwrap();
+        assert_eq!(unlocked.load::<Vec<u32>>().unwrap(), vec![1]);
+
+        let locked = unlocked.lock();
+        let err = locked.unlock("wrong").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DecryptionFailed));
+
+        let unlocked = locked.unlock("pwd").unwrap();
+        assert_eq!(unlocked.load::<Vec<u32>>().unwrap(), vec![1]);
+
+        // Interoperates with password-based handles
+        let plain = VaultFile::open(dir.path().join("vault.svlt"), "pwd");
+        assert_eq!(plain.load::<Vec<u32>>().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_rewritten_vault_needs_unlocking_again() {
+        let dir = tempdir().unwrap();
+        let unlocked = locked(&dir).unlock("pwd").unwrap();
+        unlocked.save(&1u32).unwrap();
+
+        VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .save(&2u32)
+            .unwrap();
+
+        let err = unlocked.load::<u32>().unwrap_err();
+        assert!(matches!(err, SerdeVaultError::Locked));
+        assert_eq!(
+            unlocked
+                .lock()
+                .unlock("pwd")
+                .unwrap()
+                .load::<u32>()
+                .unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_save_after_password_change_fails() {
+        let dir = tempdir().unwrap();
+        let unlocked = locked(&dir).unlock("old").unwrap();
+        unlocked.save(&1u32).unwrap();
+
+        let other = VaultFile::open(dir.path().join("vault.svlt"), "new").with_params(8, 1, 1);
+        other.save(&5u32).unwrap();
+
+        let err = unlocked.save(&2u32).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::Locked));
+        assert_eq!(other.load::<u32>().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_auto_lock() {
+        use std::sync::mpsc;
+
+        let dir = tempdir().unwrap();
+        let (tx, rx) = mpsc::channel();
+        let unlocked = locked(&dir)
+            .unlock("pwd")
+            .unwrap()
+            .on_auto_lock(move || tx.send(()).unwrap())
+            .auto_lock(Duration::from_millis(50));
+        unlocked.save(&1u32).unwrap();
+
+        rx.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(unlocked.is_locked());
+        assert!(matches!(
+            unlocked.load::<u32>(),
+            Err(SerdeVaultError::Locked)
+        ));
+        assert!(matches!(unlocked.save(&2u32), Err(SerdeVaultError::Locked)));
+
+        assert_eq!(
+            unlocked
+                .lock()
+                .unlock("pwd")
+                .unwrap()
+                .load::<u32>()
+                .unwrap(),
+            1
+        );
+    }
+}
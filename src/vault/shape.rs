@@ -0,0 +1,170 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{decode, push_extension, EXT_SCHEMA_HASH};
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Record a hash of the saved data's shape in the header, for
+    /// [`load_checked`](Self::load_checked) to compare against.
+    ///
+    /// The shape is the data with every scalar replaced by its JSON type: field
+    /// names, nesting and types count, values don't. Costs one extra
+    /// serialization per save.
+    pub fn with_schema_hash(mut self, enabled: bool) -> Self {
+        self.schema_hash = enabled;
+        self
+    }
+
+    /// Like [`load`](Self::load), but fail with
+    /// [`SerdeVaultError::SchemaChanged`] if `T` doesn't reproduce the shape of
+    /// what was saved.
+    ///
+    /// The loaded value is serialized again and its shape compared with the hash
+    /// recorded at save time. A mismatch means the struct changed in a way serde
+    /// tolerated silently — a removed field whose data would be dropped on the
+    /// next save, a new `#[serde(default)]` field — and that's worth a migration.
+    /// Vaults saved without [`with_schema_hash`](Self::with_schema_hash) are not
+    /// checked.
+    pub fn load_checked<T: Serialize + DeserializeOwned>(&self) -> Result<T, SerdeVaultError> {
+        let raw = self.read_raw()?;
+        let (header, _) = decode(&raw).map_err(self.ctx(Operation::Load))?;
+        let (format, plaintext) = self.open_blob_cached(&raw)?;
+        let data: T = self.decode_payload(format, &plaintext)?;
+
+        let stored = header
+            .extension(EXT_SCHEMA_HASH)
+            .and_then(|v| <[u8; 8]>::try_from(v).ok())
+            .map(u64::from_le_bytes);
+        if let Some(stored) = stored {
+            let current = shape_hash(&data)?;
+            if current != stored {
+                return Err(SerdeVaultError::SchemaChanged { stored, current });
+            }
+        }
+        Ok(data)
+    }
+
+    /// Header extensions describing `data`, as configured on this handle.
+    pub(super) fn extensions_for<T: Serialize>(
+        &self,
+        data: &T,
+    ) -> Result<Vec<u8>, SerdeVaultError> {
+        let mut extensions = Vec::new();
+        if self.schema_hash {
+            push_extension(
+                &mut extensions,
+                EXT_SCHEMA_HASH,
+                &shape_hash(data)?.to_le_bytes(),
+            );
+        }
+        Ok(extensions)
+    }
+}
+
+/// FNV-1a hash of `data`'s shape. Object keys are visited in sorted order, so
+/// maps hash the same whatever their iteration order.
+fn shape_hash<T: Serialize>(data: &T) -> Result<u64, SerdeVaultError> {
+    let value = serde_json::to_value(data)
+        .map_err(|e| SerdeVaultError::SerializationError(e.to_string()))?;
+    let mut hash = Fnv(0xcbf2_9ce4_8422_2325);
+    hash_shape(&value, &mut hash);
+    Ok(hash.0)
+}
+
+fn hash_shape(value: &Value, hash: &mut Fnv) {
+    match value {
+        Value::Null => hash.write(b"n"),
+        Value::Bool(_) => hash.write(b"b"),
+        Value::Number(_) => hash.write(b"#"),
+        Value::String(_) => hash.write(b"s"),
+        Value::Array(items) => {
+            hash.write(b"[");
+            for item in items {
+                hash_shape(item, hash);
+            }
+            hash.write(b"]");
+        }
+        Value::Object(map) => {
+            let mut keys: Vec<_> = map.keys().collect();
+            keys.sort();
+            hash.write(b"{");
+            for key in keys {
+                hash.write(&(key.len() as u64).to_le_bytes());
+                hash.write(key.as_bytes());
+                hash_shape(&map[key], hash);
+            }
+            hash.write(b"}");
+        }
+    }
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use tempfile::tempdir;
+
+    #[derive(Serialize, Deserialize)]
+    struct V1 {
+        name: String,
+        retries: u32,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct V2 {
+        name: String,
+    }
+
+    #[test]
+    fn test_load_checked_detects_dropped_fields() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_schema_hash(true);
+        vault
+            .save(&V1 {
+                name: "a".into(),
+                retries: 3,
+            })
+            .unwrap();
+
+        assert_eq!(vault.load_checked::<V1>().unwrap().retries, 3);
+        // Plain load tolerates the unknown field, load_checked doesn't.
+        assert_eq!(vault.load::<V2>().unwrap().name, "a");
+        let err = vault.load_checked::<V2>().unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SchemaChanged { .. }));
+
+        // Without a recorded hash there is nothing to check against.
+        let unhashed = VaultFile::open(vault.path(), "pwd").with_params(8, 1, 1);
+        unhashed
+            .save(&V1 {
+                name: "a".into(),
+                retries: 3,
+            })
+            .unwrap();
+        assert_eq!(unhashed.load_checked::<V2>().unwrap().name, "a");
+    }
+
+    #[test]
+    fn test_shape_ignores_values_and_key_order() {
+        let a = serde_json::json!({ "x": 1, "y": ["s", null] });
+        let b = serde_json::json!({ "y": ["t", null], "x": 2.5 });
+        let c = serde_json::json!({ "x": "1", "y": ["s", null] });
+        assert_eq!(shape_hash(&a).unwrap(), shape_hash(&b).unwrap());
+        assert_ne!(shape_hash(&a).unwrap(), shape_hash(&c).unwrap());
+    }
+}
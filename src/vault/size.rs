@@ -0,0 +1,70 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::crypto::cipher::TAG_SIZE;
+use crate::crypto::stream::sealed_len;
+use crate::error::SerdeVaultError;
+use crate::format::HEADER_SIZE;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Size in bytes of the file `save(data)` would write, without writing it.
+    ///
+    /// Exact for this handle's settings (format, migrations, streaming): it is
+    /// the header plus the serialized payload plus the authentication tags. The
+    /// payload is serialized into a byte counter, so no plaintext is buffered and
+    /// no key is derived.
+    pub fn estimate_size<T: Serialize>(&self, data: &T) -> Result<u64, SerdeVaultError> {
+        let mut counter = ByteCount(0);
+        self.encode_payload_into(self.format, data, &mut counter)?;
+        let payload = counter.0;
+        let body = if self.streaming && !self.deterministic {
+            sealed_len(payload)
+        } else {
+            payload + TAG_SIZE as u64
+        };
+        let mut extensions = self.extensions_for(data)?.len() as u64;
+        if !self.deterministic {
+            extensions += 3 + 8; // the generation
+        }
+        Ok(HEADER_SIZE as u64 + extensions + body)
+    }
+}
+
+/// A sink that only counts what is written to it.
+struct ByteCount(u64);
+
+impl Write for ByteCount {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_estimate_size_matches_saved_file() {
+        let dir = tempdir().unwrap();
+        let data: Vec<u64> = (0..20_000).collect();
+
+        for streaming in [false, true] {
+            let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+                .with_params(8, 1, 1)
+                .with_streaming(streaming)
+                .with_schema_hash(streaming);
+            let estimate = vault.estimate_size(&data).unwrap();
+            vault.save(&data).unwrap();
+            assert_eq!(estimate, std::fs::metadata(vault.path()).unwrap().len());
+        }
+    }
+}
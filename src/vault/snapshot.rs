@@ -0,0 +1,141 @@
+use std::fs;
+
+use crate::artifacts;
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{atomic_write, decode};
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Copy the current vault file into the managed snapshots directory under `label`.
+    ///
+    /// Snapshots are the encrypted file as-is — nothing is decrypted — and live in
+    /// `.<name>.snapshots/` next to the vault. An existing snapshot with the same
+    /// label is replaced. Labels may contain ASCII letters, digits, `-`, `_` and `.`
+    /// (not leading). Saves wait for the copy, under the vault's lock. Fails with
+    /// `ReadOnly` on a read-only handle.
+    pub fn snapshot(&self, label: &str) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let source = self.target_path(Operation::Snapshot)?;
+        let target = self.snapshot_path(label)?;
+        let _lock = self.lock(true, Operation::Snapshot)?;
+        let raw = fs::read(&source).map_err(|e| self.read_error(e))?;
+        atomic_write(&target, &raw, &self.write_opts).map_err(self.ctx(Operation::Snapshot))
+    }
+
+    /// Atomically replace the vault file with the snapshot saved under `label`.
+    ///
+    /// The snapshot is decrypted with this handle's password and saved again
+    /// like any other data, so the vault's [generation](Self::generation) keeps
+    /// increasing rather than going back to the snapshot's. The current file is
+    /// backed up / archived first if backups or history are enabled. The
+    /// snapshot itself is kept, so it can be restored again.
+    pub fn restore(&self, label: &str) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let source = self.snapshot_path(label)?;
+        let raw = fs::read(&source).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => SerdeVaultError::VaultNotFound(source.clone()),
+            _ => self.ctx(Operation::Restore)(e),
+        })?;
+        let (header, _) = decode(&raw).map_err(self.ctx(Operation::Restore))?;
+        let (format, plaintext) = self.open_blob(&raw)?;
+        self.write_plaintext(format, &plaintext, header.extensions)
+    }
+
+    /// Labels of the existing snapshots, sorted.
+    pub fn snapshots(&self) -> Result<Vec<String>, SerdeVaultError> {
+        let files = artifacts::snapshots(&self.path).map_err(self.ctx(Operation::Load))?;
+        Ok(files
+            .iter()
+            .filter_map(|p| artifacts::snapshot_label(p))
+            .collect())
+    }
+
+    fn snapshot_path(&self, label: &str) -> Result<std::path::PathBuf, SerdeVaultError> {
+        let valid = !label.is_empty()
+            && !label.starts_with('.')
+            && label
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+        if !valid {
+            return Err(SerdeVaultError::InvalidConfig(format!(
+                "invalid snapshot label {label:?}"
+            )));
+        }
+        Ok(artifacts::snapshot_path(&self.path, label))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+
+        vault.save(&"monday").unwrap();
+        vault.snapshot("monday").unwrap();
+        vault.save(&"tuesday").unwrap();
+        vault.snapshot("tuesday").unwrap();
+        assert_eq!(vault.snapshots().unwrap(), vec!["monday", "tuesday"]);
+
+        vault.restore("monday").unwrap();
+        assert_eq!(vault.load::<String>().unwrap(), "monday");
+
+        // Restoring doesn't consume the snapshot
+        vault.restore("tuesday").unwrap();
+        vault.restore("monday").unwrap();
+        assert_eq!(vault.load::<String>().unwrap(), "monday");
+
+        vault.destroy().unwrap();
+        assert!(vault.snapshots().unwrap().is_empty());
+        assert!(!artifacts::snapshot_dir(&vault.path).exists());
+    }
+
+    #[test]
+    fn test_snapshot_errors() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+
+        let err = vault.snapshot("nothing-saved-yet").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::VaultNotFound(_)));
+
+        vault.save(&1u8).unwrap();
+        for label in ["", "../escape", ".hidden", "a/b"] {
+            let err = vault.snapshot(label).unwrap_err();
+            assert!(
+                matches!(err, SerdeVaultError::InvalidConfig(_)),
+                "{label:?}"
+            );
+        }
+
+        let err = vault.restore("missing").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::VaultNotFound(_)));
+    }
+
+    #[test]
+    fn test_restore_keeps_generation_increasing() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault.save(&1u8).unwrap();
+        vault.snapshot("one").unwrap();
+        vault.save(&2u8).unwrap();
+        let (_, stale) = vault.load_with_generation::<u8>().unwrap();
+        vault.save(&3u8).unwrap();
+
+        vault.restore("one").unwrap();
+        assert_eq!(vault.generation().unwrap(), 4);
+        let err = vault.save_if_unchanged(&9u8, stale).unwrap_err();
+        assert!(matches!(
+            err,
+            SerdeVaultError::GenerationConflict {
+                expected: 2,
+                found: 4
+            }
+        ));
+        assert_eq!(vault.load::<u8>().unwrap(), 1);
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::{BufWriter, Write};
+
+use serde::Serialize;
+
+use crate::crypto::stream::ChunkedEncryptor;
+use crate::error::{Operation, SerdeVaultError};
+use crate::format::{atomic_write_with, encode_header, FLAG_CHUNKED};
+use crate::serializer::Format;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Serialize straight into a chunked encryptor on `save`, instead of into a
+    /// plaintext buffer that is then encrypted as a whole.
+    ///
+    /// For large values this keeps peak memory near one 64 KiB chunk rather than
+    /// two copies of the whole payload. The header marks such vaults as chunked,
+    /// and `load` reads both layouts transparently. Loading still decrypts into
+    /// one buffer, since the data is deserialized from it.
+    ///
+    /// Memory stays bounded only if nothing else needs the whole value at once:
+    /// [`with_schema_hash(true)`](Self::with_schema_hash) serializes it into a
+    /// `serde_json::Value` on every save to hash its shape, as does a
+    /// [binary lint](Self::with_binary_lint), and every format but
+    /// [`Format::Json`] encodes from a `Value` too.
+    pub fn with_streaming(mut self, enabled: bool) -> Self {
+        self.streaming = enabled;
+        self
+    }
+
+    /// `save_as` for handles [`with_streaming`](Self::with_streaming).
+    pub(super) fn save_streamed<T: Serialize>(
+        &self,
+        format: Format,
+        data: &T,
+    ) -> Result<(), SerdeVaultError> {
+        self.ensure_writable()?;
+        let key = self.fresh_key()?;
+        let mut extensions = self.extensions_for(data)?;
+        let target = self.target_path(Operation::Save)?;
+        let _lock = self.lock(false, Operation::Save)?;
+        self.stamp_generation(&mut extensions)?;
+        let header = key.header(format, FLAG_CHUNKED, extensions);
+        let encoded = encode_header(&header);
+
+        self.before_overwrite().map_err(self.ctx(Operation::Save))?;
+        atomic_write_with(&target, &self.write_opts, |file| {
+            file.write_all(&encoded)?;
+            let out = BufWriter::new(file);
+            let mut sink = ChunkedEncryptor::new(out, &key.key, &header.nonce, encoded.clone());
+            self.encode_payload_into(format, data, &mut sink)?;
+            sink.finish()?.flush()?;
+            Ok(())
+        })
+        .map_err(self.ctx(Operation::Save))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::stream::CHUNK_SIZE;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_streamed_save_roundtrip() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+            .with_params(8, 1, 1)
+            .with_streaming(true);
+
+        // Several chunks' worth of JSON.
+        let data: Vec<String> = (0..3 * CHUNK_SIZE / 8).map(|i| format!("{i:06}")).collect();
+        vault.save(&data).unwrap();
+        let raw = std::fs::read(vault.path()).unwrap();
+        assert!(crate::format::decode(&raw).unwrap().0.is_chunked());
+        assert_eq!(vault.load::<Vec<String>>().unwrap(), data);
+
+        // Handles without streaming read it too, and write it unchunked again.
+        let plain = VaultFile::open(vault.path(), "pwd").with_params(8, 1, 1);
+        assert_eq!(plain.load::<Vec<String>>().unwrap(), data);
+        plain.save(&data[..2].to_vec()).unwrap();
+        assert_eq!(vault.load::<Vec<String>>().unwrap(), data[..2]);
+
+        let wrong = VaultFile::open(vault.path(), "nope").with_params(8, 1, 1);
+        vault.save(&data).unwrap();
+        assert!(matches!(
+            wrong.load::<Vec<String>>(),
+            Err(SerdeVaultError::DecryptionFailed)
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::artifacts;
+use crate::error::{Operation, SerdeVaultError};
+
+use super::VaultFile;
+
+/// Same limit as Linux's `MAXSYMLINKS`.
+const MAX_LINK_HOPS: usize = 40;
+
+/// What `save` and `load` do when the vault path is a symbolic link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum SymlinkPolicy {
+    /// Read and write the file the link points to, leaving the link in place.
+    #[default]
+    Follow,
+    /// Fail with [`SerdeVaultError::SymlinkRefused`].
+    Refuse,
+    /// Read through the link, but replace the link itself with a regular file on save.
+    Replace,
+}
+
+impl VaultFile {
+    /// Choose how a vault path that is a symbolic link is handled
+    /// ([`SymlinkPolicy::Follow`] by default).
+    ///
+    /// Following keeps setups where the vault is a link into a synced folder
+    /// working: the new file is written next to the link's target. Backups,
+    /// history and snapshots always live next to the path this handle was opened with.
+    pub fn with_symlink_policy(mut self, policy: SymlinkPolicy) -> Self {
+        self.symlinks = policy;
+        self
+    }
+
+    /// The file that reads and writes of the vault should actually touch.
+    pub(super) fn target_path(&self, operation: Operation) -> Result<PathBuf, SerdeVaultError> {
+        match self.symlinks {
+            SymlinkPolicy::Replace => Ok(self.path.clone()),
+            SymlinkPolicy::Refuse => {
+                if is_symlink(&self.path).map_err(self.ctx(operation))? {
+                    return Err(SerdeVaultError::SymlinkRefused(self.path.clone()));
+                }
+                Ok(self.path.clone())
+            }
+            SymlinkPolicy::Follow => resolve_links(&self.path).map_err(self.ctx(operation)),
+        }
+    }
+}
+
+fn is_symlink(path: &Path) -> io::Result<bool> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) => Ok(meta.file_type().is_symlink()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Follow `path` through any chain of links to the final target. Unlike
+/// `fs::canonicalize`, a dangling link resolves to the file it would create.
+fn resolve_links(path: &Path) -> io::Result<PathBuf> {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_LINK_HOPS {
+        if !is_symlink(&path)? {
+            return Ok(path);
+        }
+        let target = fs::read_link(&path)?;
+        path = artifacts::parent_dir(&path).join(target);
+    }
+    Err(io::Error::other("too many levels of symbolic links"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::tempdir;
+
+    fn linked_vault(dir: &Path, policy: SymlinkPolicy) -> (VaultFile, PathBuf) {
+        let synced = dir.join("synced");
+        fs::create_dir(&synced).unwrap();
+        let real = synced.join("vault.svlt");
+        let link = dir.join("vault.svlt");
+        symlink(&real, &link).unwrap();
+
+        let vault = VaultFile::open(&link, "pwd")
+            .with_params(8, 1, 1)
+            .with_symlink_policy(policy);
+        (vault, real)
+    }
+
+    #[test]
+    fn test_follow_writes_through_link() {
+        let dir = tempdir().unwrap();
+        let (vault, real) = linked_vault(dir.path(), SymlinkPolicy::Follow);
+
+        vault.save(&1u32).unwrap();
+        vault.save(&2u32).unwrap();
+
+        assert!(is_symlink(vault.path()).unwrap());
+        assert!(real.is_file());
+        assert_eq!(vault.load::<u32>().unwrap(), 2);
+        assert_eq!(VaultFile::open(&real, "pwd").load::<u32>().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_refuse_rejects_links() {
+        let dir = tempdir().unwrap();
+        let (vault, real) = linked_vault(dir.path(), SymlinkPolicy::Refuse);
+
+        let err = vault.save(&1u32).unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SymlinkRefused(_)));
+        assert!(!real.exists());
+
+        VaultFile::open(&real, "pwd")
+            .with_params(8, 1, 1)
+            .save(&1u32)
+            .unwrap();
+        let err = vault.load::<u32>().unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SymlinkRefused(_)));
+        let err = vault.snapshot("linked").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::SymlinkRefused(_)));
+    }
+
+    #[test]
+    fn test_replace_swaps_link_for_file() {
+        let dir = tempdir().unwrap();
+        let (vault, real) = linked_vault(dir.path(), SymlinkPolicy::Replace);
+        VaultFile::open(&real, "pwd")
+            .with_params(8, 1, 1)
+            .save(&1u32)
+            .unwrap();
+
+        assert_eq!(vault.load::<u32>().unwrap(), 1);
+        vault.save(&2u32).unwrap();
+
+        assert!(!is_symlink(vault.path()).unwrap());
+        assert_eq!(vault.load::<u32>().unwrap(), 2);
+        assert_eq!(VaultFile::open(&real, "pwd").load::<u32>().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_resolve_relative_and_dangling_links() {
+        let dir = tempdir().unwrap();
+        symlink("b", dir.path().join("a")).unwrap();
+        symlink("missing", dir.path().join("b")).unwrap();
+        assert_eq!(
+            resolve_links(&dir.path().join("a")).unwrap(),
+            dir.path().join("missing")
+        );
+
+        symlink("loop", dir.path().join("loop")).unwrap();
+        assert!(resolve_links(&dir.path().join("loop")).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::error::SerdeVaultError;
+use crate::wipe::SecretValue;
+
+use super::VaultFile;
+
+impl VaultFile {
+    /// Load the stored document without knowing the Rust type it was written from.
+    ///
+    /// Meant for generic tools — editors, inspectors, migration scripts.
+    pub fn load_value(&self) -> Result<Value, SerdeVaultError> {
+        self.load::<SecretValue>().map(SecretValue::expose)
+    }
+
+    /// Load only the part of the stored document at `pointer` (RFC 6901, e.g.
+    /// `/database/credentials`) as `T`.
+    ///
+    /// Lets an app that stores one big config read a single section without a
+    /// type for the whole document. `""` addresses the entire document.
+    pub fn load_path<T: DeserializeOwned>(&self, pointer: &str) -> Result<T, SerdeVaultError> {
+        let document: SecretValue = self.load()?;
+        let section = document
+            .pointer(pointer)
+            .ok_or_else(|| SerdeVaultError::PointerNotFound(pointer.to_string()))?;
+
+        T::deserialize(section)
+            .map_err(|e| SerdeVaultError::DeserializationError(format!("{pointer}: {e}")))
+    }
+
+    /// Replace the stored document with an untyped JSON value.
+    pub fn save_value(&self, value: &Value) -> Result<(), SerdeVaultError> {
+        self.save(value)
+    }
+
+    /// Apply an RFC 7396 JSON merge patch to the stored document and re-encrypt it.
+    ///
+    /// Objects in `patch` are merged key by key, `null` removes a key, and any
+    /// other value replaces what was there. Runs under the same lock as
+    /// [`update`](Self::update), so concurrent patches don't lose writes.
+    pub fn patch(&self, patch: Value) -> Result<(), SerdeVaultError> {
+        self.update(|document: &mut SecretValue| merge_patch(document, patch))
+    }
+}
+
+/// RFC 7396 `MergePatch(Target, Patch)`, applied in place.
+fn merge_patch(target: &mut Value, patch: Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().expect("target is an object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(&key);
+        } else {
+            merge_patch(target.entry(key).or_insert(Value::Null), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Config {
+        server_url: String,
+        max_connections: u32,
+    }
+
+    #[test]
+    fn test_load_path() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault
+            .save_value(&json!({
+                "database": {
+                    "credentials": { "server_url": "postgres://db", "max_connections": 10 }
+                },
+                "ui": { "theme": "dark" }
+            }))
+            .unwrap();
+
+        let creds: Config = vault.load_path("/database/credentials").unwrap();
+        assert_eq!(creds.server_url, "postgres://db");
+        assert_eq!(vault.load_path::<String>("/ui/theme").unwrap(), "dark");
+
+        let err = vault.load_path::<Config>("/database/missing").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::PointerNotFound(p) if p == "/database/missing"));
+
+        let err = vault.load_path::<Config>("/ui").unwrap_err();
+        assert!(matches!(err, SerdeVaultError::DeserializationError(_)));
+    }
+
+    #[test]
+    fn test_patch() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault
+            .save_value(&json!({
+                "server_url": "http://old",
+                "max_connections": 10,
+                "tls": { "verify": true, "ca": "/etc/ca.pem" },
+                "tags": ["a", "b"]
+            }))
+            .unwrap();
+
+        vault
+            .patch(json!({
+                "server_url": "https://new",
+                "tls": { "ca": null, "pin": "sha256:ab" },
+                "tags": ["c"]
+            }))
+            .unwrap();
+
+        assert_eq!(
+            vault.load_value().unwrap(),
+            json!({
+                "server_url": "https://new",
+                "max_connections": 10,
+                "tls": { "verify": true, "pin": "sha256:ab" },
+                "tags": ["c"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_patch_rfc7396_examples() {
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge_patch(&mut target, patch);
+            assert_eq!(target, expected);
+        }
+    }
+
+    #[test]
+    fn test_value_roundtrip_with_typed_data() {
+        let dir = tempdir().unwrap();
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        vault
+            .save(&Config {
+                server_url: "https://api.example.com".into(),
+                max_connections: 100,
+            })
+            .unwrap();
+
+        let mut value = vault.load_value().unwrap();
+        assert_eq!(value["max_connections"], 100);
+
+        value["max_connections"] = json!(5);
+        vault.save_value(&value).unwrap();
+        assert_eq!(vault.load::<Config>().unwrap().max_connections, 5);
+    }
+}
@@ -0,0 +1,195 @@
+//! Wiping of intermediate plaintext.
+//!
+//! `Zeroizing<Vec<u8>>` wipes its final allocation on drop, but every time a
+//! `Vec` grows the old allocation is freed as it is, plaintext included.
+//! [`SecretBuf`] grows by copying into a fresh buffer and wiping the old one.
+//! [`SecretValue`] wipes the strings (and object keys) of a `serde_json::Value`
+//! on drop; numbers and booleans are left to the allocator.
+
+use std::io::{self, Write};
+use std::ops::{Deref, DerefMut};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::{Zeroize, Zeroizing};
+
+/// A write-only byte buffer that never leaves a copy of its contents behind.
+#[derive(Default)]
+pub(crate) struct SecretBuf(Zeroizing<Vec<u8>>);
+
+impl SecretBuf {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Continue writing after the end of `buf`.
+    pub(crate) fn from_zeroizing(buf: Zeroizing<Vec<u8>>) -> Self {
+        Self(buf)
+    }
+
+    pub(crate) fn into_inner(self) -> Zeroizing<Vec<u8>> {
+        self.0
+    }
+}
+
+impl Write for SecretBuf {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let needed = self.0.len() + data.len();
+        if needed > self.0.capacity() {
+            let mut grown = Zeroizing::new(Vec::with_capacity(needed.max(2 * self.0.capacity())));
+            grown.extend_from_slice(&self.0);
+            // The old buffer is wiped as it drops.
+            self.0 = grown;
+        }
+        self.0.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A JSON value whose strings are wiped when it drops.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct SecretValue(pub(crate) Value);
+
+impl SecretValue {
+    /// Hand the value over to the caller, who becomes responsible for it.
+    pub(crate) fn expose(mut self) -> Value {
+        self.0.take()
+    }
+}
+
+impl Deref for SecretValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.0
+    }
+}
+
+impl DerefMut for SecretValue {
+    fn deref_mut(&mut self) -> &mut Value {
+        &mut self.0
+    }
+}
+
+impl Drop for SecretValue {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+fn wipe(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Array(items) => items.iter_mut().for_each(wipe),
+        Value::Object(map) => {
+            for (mut key, mut item) in std::mem::take(map) {
+                key.zeroize();
+                wipe(&mut item);
+            }
+        }
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Verifies wiping end to end: while armed, a global allocator scans every
+/// block it frees for a marker that only occurs in the saved secrets.
+#[cfg(test)]
+mod tests {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use serde::{Deserialize, Serialize};
+    use tempfile::tempdir;
+
+    use crate::{Format, VaultFile};
+
+    const MARKER: &[u8] = b"wipe-me-0xC0FFEE";
+
+    static ARMED: AtomicBool = AtomicBool::new(false);
+    static LEAKS: AtomicUsize = AtomicUsize::new(0);
+    /// Tests arm the allocator one at a time.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    struct Watch;
+
+    unsafe impl GlobalAlloc for Watch {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            if ARMED.load(Ordering::Relaxed) {
+                let block = std::slice::from_raw_parts(ptr, layout.size());
+                if block.windows(MARKER.len()).any(|w| w == MARKER) {
+                    LEAKS.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOC: Watch = Watch;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Secret {
+        name: String,
+        token: String,
+    }
+
+    /// Freed blocks holding the marker while `f` runs.
+    fn leaks_during(f: impl FnOnce()) -> usize {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        LEAKS.store(0, Ordering::Relaxed);
+        ARMED.store(true, Ordering::Relaxed);
+        f();
+        ARMED.store(false, Ordering::Relaxed);
+        LEAKS.load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn test_save_and_load_leave_no_plaintext() {
+        let marker = std::str::from_utf8(MARKER).unwrap();
+        // Enough records that every buffer has to grow a few times.
+        let data: Vec<Secret> = (0..200)
+            .map(|i| Secret {
+                name: format!("entry {i}"),
+                token: marker.to_string(),
+            })
+            .collect();
+        let dir = tempdir().unwrap();
+
+        for format in [Format::Json, Format::CanonicalJson, Format::NdJson] {
+            let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd")
+                .with_params(8, 1, 1)
+                .with_format(format);
+            let mut loaded = None;
+            let leaks = leaks_during(|| {
+                vault.save(&data).unwrap();
+                loaded = Some(vault.load::<Vec<Secret>>().unwrap());
+            });
+            assert_eq!(leaks, 0, "{format:?}");
+            assert_eq!(loaded.unwrap(), data);
+        }
+
+        let vault = VaultFile::open(dir.path().join("vault.svlt"), "pwd").with_params(8, 1, 1);
+        let mut out = Vec::with_capacity(64 * 1024);
+        let mut records = Vec::with_capacity(data.len() + 1);
+        let mut token = String::new();
+        let leaks = leaks_during(|| {
+            vault.append_record(&data[0]).unwrap();
+            vault.export_plaintext_json_to(&mut out).unwrap();
+            records.extend(vault.records::<Secret>().unwrap());
+            token = vault.load_path("/0/token").unwrap();
+        });
+        assert_eq!(leaks, 0);
+        assert_eq!(records.len(), data.len() + 1);
+        assert_eq!(token, marker);
+    }
+}